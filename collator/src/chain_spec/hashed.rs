@@ -81,6 +81,7 @@ fn hashed_genesis(
     balances: hashed_parachain_runtime::BalancesConfig {
       balances: endowed_accounts.iter().cloned().map(|k| (k, 1 << 60)).collect(),
     },
+    afloat: Default::default(),
     sudo: SudoConfig { key: Some(root_key) },
     council: Default::default(),
     treasury: Default::default(),
@@ -191,6 +191,7 @@ fn md5_genesis(
     balances: hashed_parachain_runtime::BalancesConfig {
       balances: endowed_accounts.iter().cloned().map(|k| (k, 1 << 60)).collect(),
     },
+    afloat: Default::default(),
     sudo: SudoConfig { key: Some(root_key) },
     treasury: Default::default(),
     council: CouncilConfig { members: endowed_accounts, phantom: Default::default() },
@@ -202,6 +202,11 @@ fn testnet_genesis(
     balances: hashed_parachain_runtime::BalancesConfig {
       balances: endowed_accounts.iter().cloned().map(|k| (k, 1 << 60)).collect(),
     },
+    afloat: hashed_parachain_runtime::AfloatConfig {
+      owner: Some(root_key.clone()),
+      admin: Some(root_key.clone()),
+      ..Default::default()
+    },
     sudo: SudoConfig { key: Some(root_key) },
     council: Default::default(),
     treasury: Default::default(),
@@ -1,6 +1,7 @@
 use hashed_runtime::{
-  AccountId, AuraConfig, BalancesConfig, BitcoinVaultsConfig, CouncilConfig, GenesisConfig,
-  GrandpaConfig, NodeAuthorizationConfig, Signature, SudoConfig, SystemConfig, WASM_BINARY,
+  AccountId, AfloatConfig, AuraConfig, BalancesConfig, BitcoinVaultsConfig, CouncilConfig,
+  GenesisConfig, GrandpaConfig, NodeAuthorizationConfig, Signature, SudoConfig, SystemConfig,
+  WASM_BINARY,
 };
 use sc_chain_spec::Properties;
 use sc_service::ChainType;
@@ -284,11 +285,16 @@ fn testnet_genesis(
     treasury: Default::default(),
     assets: Default::default(),
     // bounties: Default::default(),
-    sudo: SudoConfig { key: Some(root_key) },
+    sudo: SudoConfig { key: Some(root_key.clone()) },
     transaction_payment: Default::default(),
     bitcoin_vaults: BitcoinVaultsConfig {
       bdk_services_url: BDK_SERVICES_MAINNET_URL.as_bytes().to_vec(),
     },
     mapped_assets: Default::default(),
+    afloat: AfloatConfig {
+      owner: Some(root_key.clone()),
+      admin: Some(root_key),
+      ..Default::default()
+    },
   }
 }
@@ -0,0 +1,61 @@
+//! Runtime API definition for the afloat pallet
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use pallet_afloat::types::{OfferStatus, StorageId};
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+  /// Paginated listings of offers and transactions. Each page holds at most `limit` entries,
+  /// capped by `MAX_PAGE_SIZE`, and starts after the id `start_after`, usually the last id of
+  /// the previous page.
+  pub trait AfloatApi<AccountId, ItemId, Offer, Transaction>
+  where
+    AccountId: Codec,
+    ItemId: Codec,
+    Offer: Codec,
+    Transaction: Codec,
+  {
+    /// Offers created by an account
+    fn offers_by_creator(
+      creator: AccountId,
+      start_after: Option<StorageId>,
+      limit: u32,
+    ) -> Vec<(StorageId, Offer)>;
+
+    /// Offers of a tax credit
+    fn offers_by_tax_credit(
+      tax_credit_id: ItemId,
+      start_after: Option<StorageId>,
+      limit: u32,
+    ) -> Vec<(StorageId, Offer)>;
+
+    /// Offers in a status
+    fn offers_by_status(
+      status: OfferStatus,
+      start_after: Option<StorageId>,
+      limit: u32,
+    ) -> Vec<(StorageId, Offer)>;
+
+    /// Transactions where an account is the seller or the buyer
+    fn transactions_by_user(
+      user: AccountId,
+      start_after: Option<StorageId>,
+      limit: u32,
+    ) -> Vec<(StorageId, Transaction)>;
+
+    /// Transactions of a tax credit
+    fn transactions_by_tax_credit(
+      tax_credit_id: ItemId,
+      start_after: Option<StorageId>,
+      limit: u32,
+    ) -> Vec<(StorageId, Transaction)>;
+
+    /// Transactions in a status
+    fn transactions_by_status(
+      status: OfferStatus,
+      start_after: Option<StorageId>,
+      limit: u32,
+    ) -> Vec<(StorageId, Transaction)>;
+  }
+}
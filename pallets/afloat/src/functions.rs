@@ -1,27 +1,122 @@
 use super::*;
 
 use crate::types::*;
-use frame_support::{pallet_prelude::*, traits::UnixTime};
-use frame_system::pallet_prelude::*;
-use pallet_fruniques::types::{Attributes, CollectionDescription, FruniqueRole, ParentInfo};
-use pallet_gated_marketplace::types::MarketplaceRole;
+use frame_support::{
+  pallet_prelude::*,
+  storage::with_storage_layer,
+  traits::{tokens::fungibles, UnixTime},
+};
+use frame_system::{pallet_prelude::*, RawOrigin};
+use pallet_fruniques::types::{
+  Attributes, CollectionDescription, FruniqueRole, ParentInfo, VerificationPolicy, CID,
+};
+use pallet_gated_marketplace::types::{
+  AccountOrApplication, Application, ApplicationStatus, Marketplace, MarketplaceRole,
+};
 // use frame_support::traits::OriginTrait;
-use frame_support::sp_io::hashing::blake2_256;
-use pallet_rbac::types::{IdOrVec, RoleBasedAccessControl, RoleId};
+use frame_support::sp_io::{hashing::blake2_256, MultiRemovalResults};
+use pallet_rbac::{
+  purge::{PurgeProgress, StoragePurge},
+  types::{IdOrVec, RoleBasedAccessControl, RoleId},
+};
 use scale_info::prelude::vec;
 use sp_runtime::{
   sp_std::{str, vec::Vec},
-  traits::StaticLookup,
+  traits::{CheckedMul, One, Saturating, Zero},
+  PerThing, Permill,
 };
 
 impl<T: Config> Pallet<T> {
-  pub fn do_initial_setup(creator: T::AccountId, admin: T::AccountId) -> DispatchResult {
+  /// Creates the Afloat asset and sets its metadata, an asset that already exists is kept as is.
+  ///
+  /// - `owner`: owner of the asset, pays the metadata deposit.
+  /// - `asset_id`: id of the asset.
+  /// - `name`, `symbol`, `decimals`: metadata of the asset.
+  ///
+  /// # Errors
+  ///
+  /// Fails if the metadata is too long or the owner can't pay its deposit.
+  pub fn do_create_afloat_asset(
+    owner: T::AccountId,
+    asset_id: T::AssetId,
+    name: Vec<u8>,
+    symbol: Vec<u8>,
+    decimals: u8,
+  ) -> DispatchResult {
+    type Assets<T> = pallet_mapped_assets::Pallet<T>;
+    if <Assets<T> as fungibles::Inspect<T::AccountId>>::asset_exists(asset_id) {
+      return Ok(());
+    }
+    <Assets<T> as fungibles::Create<T::AccountId>>::create(
+      asset_id,
+      owner.clone(),
+      true,
+      One::one(),
+    )?;
+    <Assets<T> as fungibles::metadata::Mutate<T::AccountId>>::set(
+      asset_id, &owner, name, symbol, decimals,
+    )
+  }
+
+  /// Creates the Afloat collection and marketplace and registers their owner and admin, called
+  /// at genesis.
+  ///
+  /// - `creator`: owner of the marketplace and collection.
+  /// - `admin`: administrator of the marketplace and collection.
+  /// - `asset_id`: asset the marketplace trades with, see `do_create_afloat_asset`.
+  pub fn do_initial_setup(
+    creator: T::AccountId,
+    admin: T::AccountId,
+    asset_id: T::AssetId,
+  ) -> DispatchResult {
+    AfloatAssetId::<T>::put(asset_id);
+
+    let metadata: CollectionDescription<T> =
+      BoundedVec::try_from(b"Afloat".to_vec()).expect("Label too long");
+
+    pallet_fruniques::Pallet::<T>::do_initial_setup()?;
+
+    Self::create_afloat_collection(
+      RawOrigin::Signed(creator.clone()).into(),
+      metadata,
+      admin.clone(),
+    )?;
+    Self::set_afloat_verification_policy(creator.clone())?;
+
+    pallet_gated_marketplace::Pallet::<T>::do_initial_setup()?;
+
+    let label: BoundedVec<u8, T::LabelMaxLen> =
+      BoundedVec::try_from(b"Afloat".to_vec()).expect("Label too long");
+    let marketplace: Marketplace<T> = Marketplace {
+      label,
+      // Afloat charges its own fee on settlement, see `AfloatFeeSchedule`
+      buy_fee: Permill::zero(),
+      sell_fee: Permill::zero(),
+      asset_id,
+      creator: creator.clone(),
+    };
+    let marketplace_id = marketplace.clone().using_encoded(blake2_256);
+
+    AfloatMarketPlaceId::<T>::put(marketplace_id);
+    AfloatFeeSchedule::<T>::put(FeeSchedule {
+      percentage: Permill::from_percent(4),
+      minimum: Zero::zero(),
+      tiers: BoundedVec::default(),
+    });
+    AfloatFeeAccount::<T>::put(creator.clone());
+    Self::add_to_afloat_collection(admin.clone(), FruniqueRole::Admin)?;
+    pallet_gated_marketplace::Pallet::do_create_marketplace(
+      RawOrigin::Signed(creator.clone()).into(),
+      admin.clone(),
+      marketplace,
+    )?;
+
     Self::initialize_rbac()?;
 
     let creator_user: User<T> = User {
       cid: ShortString::try_from(b"afloat".to_vec()).unwrap(),
       cid_creator: ShortString::try_from(b"HCD:afloat".to_vec()).unwrap(),
-      group: ShortString::try_from(b"afloat".to_vec()).unwrap(),
+      group: Jurisdiction::Federal,
       created_by: Some(creator.clone()),
       created_date: Some(T::TimeProvider::now().as_secs()),
       last_modified_by: Some(creator.clone()),
@@ -34,7 +129,7 @@ impl<T: Config> Pallet<T> {
       let admin_user: User<T> = User {
         cid: ShortString::try_from(b"afloat".to_vec()).unwrap(),
         cid_creator: ShortString::try_from(b"afloat".to_vec()).unwrap(),
-        group: ShortString::try_from(b"afloat".to_vec()).unwrap(),
+        group: Jurisdiction::Federal,
         created_by: Some(admin.clone()),
         created_date: Some(T::TimeProvider::now().as_secs()),
         last_modified_by: Some(admin.clone()),
@@ -49,68 +144,121 @@ impl<T: Config> Pallet<T> {
 
   // ! User management
 
-  /// This function creates a new user with the given actor, user address, and sign up arguments.
+  /// Registers a user and files their KYC application in the Afloat marketplace, the requested
+  /// role is granted once an admin approves it, see `do_review_sign_up`.
   ///
-  /// # Inputs
-  ///
-  /// * `actor` - An account ID of the user who initiated this action.
-  /// * `user_address` - An account ID of the user to be created.
-  /// * `args` - Sign up arguments. It could be either a `BuyerOrSeller` or a `CPA`, and contains the
-  ///   first name, last name, email, and state of the user.
+  /// - `actor`: The `AccountId` signing up the user.
+  /// - `user_address`: The `AccountId` of the new user.
+  /// - `args`: The requested role, the user info, its jurisdiction and the CIDs of its KYC
+  ///   documents.
   ///
   /// # Errors
   ///
-  /// This function may return an error if there is an issue with the `pallet_gated_marketplace`
-  /// pallet, which is used to enroll the user in the Afloat marketplace. It may also return an
-  /// error if the user already exists.
-  ///
-  /// # Returns
-  ///
-  /// Returns `Ok(())` on success.
+  /// Returns an `Error` if the user already exists or if its application can't be filed, e.g.
+  /// when a previous one is still pending.
   pub fn do_create_user(
     actor: T::AccountId,
     user_address: T::AccountId,
-    args: SignUpArgs,
+    args: SignUpArgs<T>,
   ) -> DispatchResult {
     ensure!(!<UserInfo<T>>::contains_key(user_address.clone()), Error::<T>::UserAlreadyExists);
-    match args {
-      SignUpArgs::BuyerOrSeller { cid, cid_creator, group } => {
-        let user: User<T> = User {
-          cid,
-          cid_creator,
-          group,
-          created_by: Some(actor.clone()),
-          created_date: Some(T::TimeProvider::now().as_secs()),
-          last_modified_by: Some(actor.clone()),
-          last_modified_date: Some(T::TimeProvider::now().as_secs()),
-        };
-        <UserInfo<T>>::insert(user_address.clone(), user);
-        Self::give_role_to_user(user_address.clone(), AfloatRole::BuyerOrSeller)?;
-        Self::deposit_event(Event::NewUser(user_address.clone()));
+    let marketplace_id = AfloatMarketPlaceId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
+
+    let (role, cid, cid_creator, group, fields) = match args {
+      SignUpArgs::BuyerOrSeller { cid, cid_creator, group, fields } => {
+        (AfloatRole::BuyerOrSeller, cid, cid_creator, group, fields)
       },
-      SignUpArgs::CPA { cid, cid_creator, group } => {
-        let user: User<T> = User {
-          cid,
-          cid_creator,
-          group,
-          created_by: Some(actor.clone()),
-          created_date: Some(T::TimeProvider::now().as_secs()),
-          last_modified_by: Some(actor.clone()),
-          last_modified_date: Some(T::TimeProvider::now().as_secs()),
-        };
-        <UserInfo<T>>::insert(user_address.clone(), user);
-        Self::give_role_to_user(user_address.clone(), AfloatRole::CPA)?;
-        Self::deposit_event(Event::NewUser(user_address.clone()));
+      SignUpArgs::CPA { cid, cid_creator, group, fields } => {
+        (AfloatRole::CPA, cid, cid_creator, group, fields)
       },
+    };
+    let user: User<T> = User {
+      cid,
+      cid_creator,
+      group,
+      created_by: Some(actor.clone()),
+      created_date: Some(T::TimeProvider::now().as_secs()),
+      last_modified_by: Some(actor.clone()),
+      last_modified_date: Some(T::TimeProvider::now().as_secs()),
+    };
+
+    // A rejected applicant can sign up again with new documents
+    if pallet_gated_marketplace::Pallet::<T>::applications_by_account(
+      user_address.clone(),
+      marketplace_id,
+    )
+    .is_some()
+    {
+      pallet_gated_marketplace::Pallet::<T>::is_application_in_rejected_status(
+        user_address.clone(),
+        marketplace_id,
+      )?;
     }
+    let (_, fields) = pallet_gated_marketplace::Pallet::<T>::set_up_application(fields, None);
+    let application: Application<T> =
+      Application { status: ApplicationStatus::default(), fields, feedback: BoundedVec::default() };
+    pallet_gated_marketplace::Pallet::<T>::do_apply(
+      user_address.clone(),
+      None,
+      marketplace_id,
+      application,
+    )?;
 
-    let marketplace_id = AfloatMarketPlaceId::<T>::get().unwrap();
+    <UserInfo<T>>::insert(user_address.clone(), user);
+    <SignUpRequests<T>>::insert(user_address.clone(), role);
+
+    Self::deposit_event(Event::SignUpRequested(user_address, role));
+    Ok(())
+  }
+
+  /// An admin reviews the KYC application of a user. Once approved, the user is enrolled in the
+  /// Afloat marketplace and gets its requested role, a rejected user is removed and may sign up
+  /// again.
+  ///
+  /// - `admin`: The `AccountId` of an Afloat admin.
+  /// - `user_address`: The `AccountId` of the user who signed up.
+  /// - `approved`: Whether the application is approved or rejected.
+  /// - `feedback`: The feedback stored in the application.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if the user doesn't have a pending sign up or if `admin` can't enroll
+  /// users in the Afloat marketplace.
+  pub fn do_review_sign_up(
+    admin: T::AccountId,
+    user_address: T::AccountId,
+    approved: bool,
+    feedback: BoundedVec<u8, T::MaxFeedbackLen>,
+  ) -> DispatchResult {
+    let role =
+      <SignUpRequests<T>>::get(user_address.clone()).ok_or(Error::<T>::SignUpRequestNotFound)?;
+    let marketplace_id = AfloatMarketPlaceId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
 
+    pallet_gated_marketplace::Pallet::<T>::do_enroll(
+      admin.clone(),
+      marketplace_id,
+      AccountOrApplication::Account(user_address.clone()),
+      approved,
+      feedback,
+    )?;
+    <SignUpRequests<T>>::remove(user_address.clone());
+
+    if !approved {
+      <UserInfo<T>>::remove(user_address.clone());
+      Self::deposit_event(Event::SignUpRejected(admin, user_address));
+      return Ok(());
+    }
+
+    Self::give_role_to_user(user_address.clone(), role)?;
     Self::add_to_afloat_collection(user_address.clone(), FruniqueRole::Collaborator)?;
-    pallet_gated_marketplace::Pallet::<T>::self_enroll(user_address, marketplace_id)?;
+    if role == AfloatRole::CPA {
+      Self::add_to_afloat_collection(user_address.clone(), FruniqueRole::Verifier)?;
+    }
 
+    Self::deposit_event(Event::NewUser(user_address));
     Ok(())
   }
+
   /// Function for editing user information.
   ///
   /// - `actor`: The `AccountId` of the actor performing the edit.
@@ -158,7 +306,7 @@ impl<T: Config> Pallet<T> {
     user_address: T::AccountId,
     cid: ShortString,
     cid_creator: ShortString,
-    group: ShortString,
+    group: Jurisdiction,
   ) -> DispatchResult {
     <UserInfo<T>>::try_mutate::<_, _, DispatchError, _>(user_address.clone(), |user| {
       let user = user.as_mut().ok_or(Error::<T>::FailedToEditUserAccount)?;
@@ -188,55 +336,268 @@ impl<T: Config> Pallet<T> {
   ///
   /// Returns Ok(()) on success.
   pub fn do_delete_user(_actor: T::AccountId, user_address: T::AccountId) -> DispatchResult {
-    Self::remove_from_afloat_collection(user_address.clone(), FruniqueRole::Collaborator)?;
-    Self::remove_from_afloat_marketplace(user_address.clone())?;
-
     let user_roles = Self::get_all_roles_for_user(user_address.clone());
 
+    // Users whose sign up is still pending weren't enrolled anywhere yet
     if !user_roles.is_empty() {
+      Self::remove_from_afloat_collection(user_address.clone(), FruniqueRole::Collaborator)?;
+      if Self::is_cpa(user_address.clone()) {
+        Self::remove_from_afloat_collection(user_address.clone(), FruniqueRole::Verifier)?;
+      }
+      Self::remove_from_afloat_marketplace(user_address.clone())?;
+
       for role in user_roles {
         Self::remove_role_from_user(user_address.clone(), role)?;
       }
     }
+    <SignUpRequests<T>>::remove(user_address.clone());
     <UserInfo<T>>::remove(user_address.clone());
     Self::deposit_event(Event::UserDeleted(user_address.clone()));
     Ok(())
   }
 
-  pub fn do_set_afloat_balance(
-    origin: OriginFor<T>,
-    user_address: T::AccountId,
+  // ! Fiat ramp
+
+  /// Creates a deposit or withdrawal request, backed by the reference of the bank transfer.
+  ///
+  /// - `user`: The `AccountId` of the user requesting the deposit or withdrawal.
+  /// - `request_type`: Whether the user deposited fiat or wants to withdraw it.
+  /// - `amount`: The amount of the Afloat asset to mint or burn.
+  /// - `bank_reference_cid`: The CID of the bank transfer reference.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if the user is not registered, if the amount is zero, or if the user
+  /// doesn't have enough balance to withdraw it.
+  pub fn do_create_ramp_request(
+    user: T::AccountId,
+    request_type: RampRequestType,
     amount: T::Balance,
+    bank_reference_cid: ShortString,
   ) -> DispatchResult {
-    let authority = ensure_signed(origin.clone())?;
-    let asset_id = AfloatAssetId::<T>::get().expect("AfloatAssetId should be set");
+    ensure!(<UserInfo<T>>::contains_key(user.clone()), Error::<T>::UserNotFound);
+    ensure!(!amount.is_zero(), Error::<T>::InvalidAmount);
+    if request_type == RampRequestType::Withdrawal {
+      ensure!(Self::do_get_afloat_balance(user.clone()) >= amount, Error::<T>::InsufficientBalance);
+    }
 
-    ensure!(UserInfo::<T>::contains_key(user_address.clone()), Error::<T>::UserNotFound);
+    let creation_date = T::TimeProvider::now().as_secs();
+    let request_id =
+      (user.clone(), request_type, amount, bank_reference_cid.clone(), creation_date)
+        .using_encoded(blake2_256);
+    ensure!(
+      !<RampRequests<T>>::contains_key(user.clone(), request_id),
+      Error::<T>::RampRequestAlreadyExists
+    );
+
+    let request: RampRequest<T> = RampRequest {
+      request_type,
+      amount,
+      bank_reference_cid,
+      status: RampRequestStatus::Pending,
+      creation_date,
+      reviewed_by: None,
+      review_date: None,
+    };
+    <RampRequests<T>>::insert(user.clone(), request_id, request);
 
-    let current_balance = Self::do_get_afloat_balance(user_address.clone());
+    Self::deposit_event(Event::RampRequestCreated(user, request_id, request_type));
+    Ok(())
+  }
 
-    if current_balance > amount {
-      let diff = current_balance - amount;
-      pallet_mapped_assets::Pallet::<T>::burn(
-        origin.clone(),
-        asset_id.into(),
-        T::Lookup::unlookup(user_address.clone()),
-        diff,
-      )?;
-    } else if current_balance < amount {
-      let diff = amount - current_balance;
-      pallet_mapped_assets::Pallet::<T>::mint(
-        origin.clone(),
-        asset_id.into(),
-        T::Lookup::unlookup(user_address.clone()),
-        diff,
-      )?;
-    }
+  /// Confirms or rejects a pending deposit or withdrawal request. Confirming a deposit mints the
+  /// amount to the user, confirming a withdrawal burns it.
+  ///
+  /// - `admin`: The `AccountId` of the Afloat admin reviewing the request.
+  /// - `user`: The `AccountId` of the user that created the request.
+  /// - `request_id`: The id of the request.
+  /// - `confirm`: Whether the request is confirmed or rejected.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if the request is not found or was already reviewed, or if the user no
+  /// longer has the balance to withdraw.
+  pub fn do_review_ramp_request(
+    admin: T::AccountId,
+    user: T::AccountId,
+    request_id: StorageId,
+    confirm: bool,
+  ) -> DispatchResult {
+    let asset_id = AfloatAssetId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
+
+    <RampRequests<T>>::try_mutate::<_, _, _, DispatchError, _>(
+      user.clone(),
+      request_id,
+      |request| {
+        let request = request.as_mut().ok_or(Error::<T>::RampRequestNotFound)?;
+        ensure!(
+          request.status == RampRequestStatus::Pending,
+          Error::<T>::RampRequestAlreadyReviewed
+        );
+
+        if confirm {
+          match request.request_type {
+            RampRequestType::Deposit => {
+              <pallet_mapped_assets::Pallet<T> as fungibles::Mutate<T::AccountId>>::mint_into(
+                asset_id,
+                &user,
+                request.amount,
+              )?;
+            },
+            RampRequestType::Withdrawal => {
+              ensure!(
+                Self::do_get_afloat_balance(user.clone()) >= request.amount,
+                Error::<T>::InsufficientBalance
+              );
+              <pallet_mapped_assets::Pallet<T> as fungibles::Mutate<T::AccountId>>::burn_from(
+                asset_id,
+                &user,
+                request.amount,
+              )?;
+            },
+          }
+        }
+
+        request.status =
+          if confirm { RampRequestStatus::Confirmed } else { RampRequestStatus::Rejected };
+        request.reviewed_by = Some(admin.clone());
+        request.review_date = Some(T::TimeProvider::now().as_secs());
+        Ok(())
+      },
+    )?;
 
-    Self::deposit_event(Event::AfloatBalanceSet(authority, user_address, amount));
+    if confirm {
+      Self::deposit_event(Event::RampRequestConfirmed(admin, user, request_id));
+    } else {
+      Self::deposit_event(Event::RampRequestRejected(admin, user, request_id));
+    }
     Ok(())
   }
 
+  /// Returns the deposit and withdrawal requests of a user, for reconciliation with the bank.
+  pub fn get_ramp_ledger(user: T::AccountId) -> Vec<(StorageId, RampRequest<T>)> {
+    <RampRequests<T>>::iter_prefix(user).collect()
+  }
+
+  /// Offers created by `creator`, a page of at most `limit` of them starting after `start_after`.
+  pub fn get_offers_by_creator(
+    creator: T::AccountId,
+    start_after: Option<StorageId>,
+    limit: u32,
+  ) -> Vec<(StorageId, Offer<T>)> {
+    let ids = match start_after {
+      Some(last_id) => <OfferIdsByCreator<T>>::iter_key_prefix_from(
+        &creator,
+        <OfferIdsByCreator<T>>::hashed_key_for(&creator, last_id),
+      ),
+      None => <OfferIdsByCreator<T>>::iter_key_prefix(&creator),
+    };
+    Self::page_of_offers(ids, limit)
+  }
+
+  /// Offers of a tax credit, a page of at most `limit` of them starting after `start_after`.
+  pub fn get_offers_by_tax_credit(
+    tax_credit_id: <T as pallet_uniques::Config>::ItemId,
+    start_after: Option<StorageId>,
+    limit: u32,
+  ) -> Vec<(StorageId, Offer<T>)> {
+    let ids = match start_after {
+      Some(last_id) => <OfferIdsByTaxCredit<T>>::iter_key_prefix_from(
+        &tax_credit_id,
+        <OfferIdsByTaxCredit<T>>::hashed_key_for(&tax_credit_id, last_id),
+      ),
+      None => <OfferIdsByTaxCredit<T>>::iter_key_prefix(&tax_credit_id),
+    };
+    Self::page_of_offers(ids, limit)
+  }
+
+  /// Offers in `status`, a page of at most `limit` of them starting after `start_after`.
+  pub fn get_offers_by_status(
+    status: OfferStatus,
+    start_after: Option<StorageId>,
+    limit: u32,
+  ) -> Vec<(StorageId, Offer<T>)> {
+    let ids = match start_after {
+      Some(last_id) => <OfferIdsByStatus<T>>::iter_key_prefix_from(
+        &status,
+        <OfferIdsByStatus<T>>::hashed_key_for(&status, last_id),
+      ),
+      None => <OfferIdsByStatus<T>>::iter_key_prefix(&status),
+    };
+    Self::page_of_offers(ids, limit)
+  }
+
+  /// Transactions where `user` is the seller or the buyer, a page of at most `limit` of them starting after `start_after`.
+  pub fn get_transactions_by_user(
+    user: T::AccountId,
+    start_after: Option<StorageId>,
+    limit: u32,
+  ) -> Vec<(StorageId, Transaction<T>)> {
+    let ids = match start_after {
+      Some(last_id) => <TransactionIdsByUser<T>>::iter_key_prefix_from(
+        &user,
+        <TransactionIdsByUser<T>>::hashed_key_for(&user, last_id),
+      ),
+      None => <TransactionIdsByUser<T>>::iter_key_prefix(&user),
+    };
+    Self::page_of_transactions(ids, limit)
+  }
+
+  /// Transactions of a tax credit, a page of at most `limit` of them starting after `start_after`.
+  pub fn get_transactions_by_tax_credit(
+    tax_credit_id: <T as pallet_uniques::Config>::ItemId,
+    start_after: Option<StorageId>,
+    limit: u32,
+  ) -> Vec<(StorageId, Transaction<T>)> {
+    let ids = match start_after {
+      Some(last_id) => <TransactionIdsByTaxCredit<T>>::iter_key_prefix_from(
+        &tax_credit_id,
+        <TransactionIdsByTaxCredit<T>>::hashed_key_for(&tax_credit_id, last_id),
+      ),
+      None => <TransactionIdsByTaxCredit<T>>::iter_key_prefix(&tax_credit_id),
+    };
+    Self::page_of_transactions(ids, limit)
+  }
+
+  /// Transactions in `status`, a page of at most `limit` of them starting after `start_after`.
+  pub fn get_transactions_by_status(
+    status: OfferStatus,
+    start_after: Option<StorageId>,
+    limit: u32,
+  ) -> Vec<(StorageId, Transaction<T>)> {
+    let ids = match start_after {
+      Some(last_id) => <TransactionIdsByStatus<T>>::iter_key_prefix_from(
+        &status,
+        <TransactionIdsByStatus<T>>::hashed_key_for(&status, last_id),
+      ),
+      None => <TransactionIdsByStatus<T>>::iter_key_prefix(&status),
+    };
+    Self::page_of_transactions(ids, limit)
+  }
+
+  fn page_of_offers(
+    offer_ids: impl Iterator<Item = StorageId>,
+    limit: u32,
+  ) -> Vec<(StorageId, Offer<T>)> {
+    offer_ids
+      .take(limit.min(MAX_PAGE_SIZE) as usize)
+      .filter_map(|offer_id| <AfloatOffers<T>>::get(offer_id).map(|offer| (offer_id, offer)))
+      .collect()
+  }
+
+  fn page_of_transactions(
+    transaction_ids: impl Iterator<Item = StorageId>,
+    limit: u32,
+  ) -> Vec<(StorageId, Transaction<T>)> {
+    transaction_ids
+      .take(limit.min(MAX_PAGE_SIZE) as usize)
+      .filter_map(|transaction_id| {
+        <AfloatTransactions<T>>::get(transaction_id)
+          .map(|transaction| (transaction_id, transaction))
+      })
+      .collect()
+  }
+
   pub fn do_get_afloat_balance(user_address: T::AccountId) -> T::Balance {
     let asset_id = AfloatAssetId::<T>::get().expect("AfloatAssetId should be set");
     pallet_mapped_assets::Pallet::<T>::balance(asset_id.into(), user_address)
@@ -250,23 +611,23 @@ impl<T: Config> Pallet<T> {
     expiration_date: Date,
   ) -> DispatchResult {
     ensure!(!Self::get_all_roles_for_user(authority.clone()).is_empty(), Error::<T>::Unauthorized);
-
-    let marketplace_id = AfloatMarketPlaceId::<T>::get().unwrap();
-    let collection_id = AfloatCollectionId::<T>::get().unwrap();
-
-    /*
-      TODO: at the moment we are not going to list anything in marketplace yet
-      the offers on marketplace will be created once both users create a transaction
-    */
-    // let offer_id = pallet_gated_marketplace::Pallet::<T>::do_enlist_sell_offer(
-    //   authority.clone(),
-    //   marketplace_id,
-    //   collection_id,
-    //   item_id,
-    //   price,
-    //   tax_credit_amount,
-    // )?;
-
+    ensure!(tax_credit_amount > 0, Error::<T>::InvalidTaxCreditAmount);
+    let collection_id = AfloatCollectionId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
+    ensure!(
+      pallet_uniques::Pallet::<T>::owner(collection_id, item_id) == Some(authority.clone()),
+      Error::<T>::NotOwnerOfTaxCredit
+    );
+    Self::ensure_can_trade(authority.clone(), item_id)?;
+    Self::ensure_tax_credit_attested(item_id)?;
+    // The credits of the other open sell offers of the owner can't be offered again
+    let credits_on_sale = Self::credits_on_sale(authority.clone(), item_id);
+    ensure!(
+      credits_on_sale.saturating_add(tax_credit_amount) <= <TaxCreditAmounts<T>>::get(item_id),
+      Error::<T>::NotEnoughTaxCreditsAvailable
+    );
+
+    // The offer is enlisted in the marketplace once a transaction is approved, see
+    // `do_settle_transaction`
     let offer: Offer<T> = Offer {
       tax_credit_amount,
       tax_credit_amount_remaining: tax_credit_amount,
@@ -280,15 +641,41 @@ impl<T: Config> Pallet<T> {
       cancellation_date: None,
     };
 
-    let offer_id = offer.using_encoded(blake2_256);
-
-    <AfloatOffers<T>>::insert(offer_id, offer);
+    Self::do_store_offer(offer)?;
 
     Self::deposit_event(Event::SellOrderCreated(authority));
 
     Ok(())
   }
 
+  // Tax credits of a tax credit that `seller` already committed: the ones left in its open sell
+  // offers and the ones in its transactions that aren't settled or cancelled yet
+  fn credits_on_sale(
+    seller: T::AccountId,
+    tax_credit_id: <T as pallet_uniques::Config>::ItemId,
+  ) -> u32 {
+    let now = T::TimeProvider::now().as_secs();
+    let in_offers = <OrderBook<T>>::get(tax_credit_id)
+      .into_iter()
+      .filter_map(<AfloatOffers<T>>::get)
+      .filter(|offer| {
+        offer.offer_type == OfferType::Sell
+          && offer.creator_id == seller
+          && offer.is_open()
+          && !offer.is_expired(now)
+      })
+      .fold(0u32, |credits, offer| credits.saturating_add(offer.tax_credit_amount_remaining));
+    // Settled transactions already took their credits from `TaxCreditAmounts`
+    <TransactionIdsByTaxCredit<T>>::iter_key_prefix(&tax_credit_id)
+      .filter_map(<AfloatTransactions<T>>::get)
+      .filter(|transaction| {
+        transaction.seller_id == seller
+          && transaction.status != OfferStatus::CANCELLED
+          && transaction.status != OfferStatus::AFLOAT_APPROVED
+      })
+      .fold(in_offers, |credits, transaction| credits.saturating_add(transaction.tax_credit_amount))
+  }
+
   pub fn do_create_buy_order(
     authority: T::AccountId,
     item_id: <T as pallet_uniques::Config>::ItemId,
@@ -297,23 +684,11 @@ impl<T: Config> Pallet<T> {
     expiration_date: Date,
   ) -> DispatchResult {
     ensure!(!Self::get_all_roles_for_user(authority.clone()).is_empty(), Error::<T>::Unauthorized);
+    ensure!(tax_credit_amount > 0, Error::<T>::InvalidTaxCreditAmount);
+    Self::ensure_can_trade(authority.clone(), item_id)?;
 
-    let marketplace_id = AfloatMarketPlaceId::<T>::get().unwrap();
-    let collection_id = AfloatCollectionId::<T>::get().unwrap();
-
-    /*
-      TODO: at the moment we are not going to list anything in marketplace yet
-      the offers on marketplace will be created once both users create a transaction
-    */
-    // let offer_id = pallet_gated_marketplace::Pallet::<T>::do_enlist_buy_offer(
-    //   authority.clone(),
-    //   marketplace_id,
-    //   collection_id,
-    //   item_id,
-    //   price,
-    //   tax_credit_amount,
-    // )?;
-
+    // The offer is enlisted in the marketplace once a transaction is approved, see
+    // `do_settle_transaction`
     let offer: Offer<T> = Offer {
       tax_credit_amount,
       tax_credit_amount_remaining: tax_credit_amount,
@@ -327,56 +702,876 @@ impl<T: Config> Pallet<T> {
       cancellation_date: None,
     };
 
+    Self::do_store_offer(offer)?;
+
+    Self::deposit_event(Event::BuyOrderCreated(authority));
+
+    Ok(())
+  }
+
+  /// Accepts an offer for the given amount of tax credits, or for all the remaining ones.
+  ///
+  /// - `authority`: The `AccountId` of the user accepting the offer, the buyer of a sell offer or
+  ///   the seller of a buy offer.
+  /// - `offer_id`: The id of the offer to accept.
+  /// - `amount`: The number of tax credits to trade, all the remaining ones when `None`.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if the offer is not found or can't be accepted, if the amount exceeds the
+  /// tax credits left in the offer, or if the seller of a buy offer doesn't own the tax credit.
+  ///
+  /// # Returns
+  ///
+  /// Returns `Ok(())` on success, the new transaction is stored in `AfloatTransactions`.
+  pub fn do_accept_offer(
+    authority: T::AccountId,
+    offer_id: StorageId,
+    amount: Option<u32>,
+  ) -> DispatchResult {
+    ensure!(!Self::get_all_roles_for_user(authority.clone()).is_empty(), Error::<T>::Unauthorized);
+
+    let offer = <AfloatOffers<T>>::get(offer_id).ok_or(Error::<T>::OfferNotFound)?;
+    Self::ensure_offer_available(&offer)?;
+    ensure!(offer.creator_id != authority, Error::<T>::CannotAcceptOwnOffer);
+    Self::ensure_can_trade(authority.clone(), offer.tax_credit_id)?;
+
+    let tax_credit_amount = amount.unwrap_or(offer.tax_credit_amount_remaining);
+    ensure!(tax_credit_amount > 0, Error::<T>::InvalidTaxCreditAmount);
+    ensure!(
+      tax_credit_amount <= offer.tax_credit_amount_remaining,
+      Error::<T>::NotEnoughTaxCreditsAvailable
+    );
+
+    if offer.offer_type == OfferType::Buy {
+      let collection_id = AfloatCollectionId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
+      ensure!(
+        pallet_uniques::Pallet::<T>::owner(collection_id, offer.tax_credit_id)
+          == Some(authority.clone()),
+        Error::<T>::NotOwnerOfTaxCredit
+      );
+      Self::ensure_tax_credit_attested(offer.tax_credit_id)?;
+    }
+
+    let transaction_id =
+      Self::do_create_transaction(offer_id, &offer, authority.clone(), tax_credit_amount, None)?;
+
+    Self::deposit_event(Event::OfferAccepted(authority, offer_id, transaction_id));
+    Ok(())
+  }
+  /// Creates a transaction for `tax_credit_amount` credits of an offer at its price, taking
+  /// them from the credits remaining in the offer.
+  ///
+  /// - `offer_id`: The offer the transaction is created from.
+  /// - `offer`: The offer as it is stored.
+  /// - `counterparty`: The seller of a buy offer or the buyer of a sell offer.
+  /// - `tax_credit_amount`: Tax credits transferred by the transaction, not more than the
+  ///   remaining ones.
+  /// - `matched_offer_id`: The counterparty's offer, when the transaction comes from the order
+  ///   book.
+  ///
+  /// # Returns
+  ///
+  /// The id of the new transaction.
+  fn do_create_transaction(
+    offer_id: StorageId,
+    offer: &Offer<T>,
+    counterparty: T::AccountId,
+    tax_credit_amount: u32,
+    matched_offer_id: Option<StorageId>,
+  ) -> Result<StorageId, DispatchError> {
+    let (seller_id, buyer_id) = match offer.offer_type {
+      OfferType::Sell => (offer.creator_id.clone(), counterparty.clone()),
+      OfferType::Buy => (counterparty.clone(), offer.creator_id.clone()),
+    };
+
+    let total_price = offer
+      .price_per_credit
+      .checked_mul(&tax_credit_amount.into())
+      .ok_or(Error::<T>::ArithmeticOverflow)?;
+    let fee = Self::quote_fee(&seller_id, total_price);
+
+    let creation_date = T::TimeProvider::now().as_secs();
+    let transaction: Transaction<T> = Transaction {
+      tax_credit_amount,
+      price_per_credit: offer.price_per_credit,
+      total_price,
+      fee,
+      creation_date,
+      cancellation_date: None,
+      tax_credit_id: offer.tax_credit_id,
+      seller_id,
+      buyer_id,
+      offer_id,
+      matched_offer_id,
+      marketplace_offer_id: None,
+      seller_confirmation_date: None,
+      buyer_confirmation_date: None,
+      status: OfferStatus::MATCHED,
+      documents: TransferFormDocuments::default(),
+    };
+
+    let transaction_id = (offer_id, counterparty, offer.tax_credit_amount_remaining, creation_date)
+      .using_encoded(blake2_256);
+    ensure!(
+      !<AfloatTransactions<T>>::contains_key(transaction_id),
+      Error::<T>::TransactionAlreadyExists
+    );
+
+    let filled = <AfloatOffers<T>>::try_mutate::<_, _, DispatchError, _>(offer_id, |offer| {
+      let offer = offer.as_mut().ok_or(Error::<T>::OfferNotFound)?;
+      offer.tax_credit_amount_remaining = offer
+        .tax_credit_amount_remaining
+        .checked_sub(tax_credit_amount)
+        .ok_or(Error::<T>::NotEnoughTaxCreditsAvailable)?;
+      Self::index_offer_status(offer_id, Some(offer.status), OfferStatus::MATCHED);
+      offer.status = OfferStatus::MATCHED;
+      Ok(offer.tax_credit_amount_remaining == 0)
+    })?;
+    if filled {
+      Self::remove_from_order_book(offer.tax_credit_id, offer_id);
+    }
+    Self::index_transaction(transaction_id, &transaction)?;
+    <AfloatTransactions<T>>::insert(transaction_id, transaction);
+
+    Ok(transaction_id)
+  }
+
+  /// Stores a new offer after matching it against the order book of its tax credit. The
+  /// credits that aren't matched stay open in the order book.
+  ///
+  /// # Errors
+  ///
+  /// - `OfferAlreadyExists` if the same offer was already created.
+  /// - `TooManyOffers` if the creator has `MaxOffersPerCreator` offers or the tax credit
+  ///   `MaxOfferHistoryPerTaxCredit`.
+  /// - `OrderBookFull` if the tax credit has `MaxOffersPerTaxCredit` open offers.
+  fn do_store_offer(mut offer: Offer<T>) -> Result<StorageId, DispatchError> {
     let offer_id = offer.using_encoded(blake2_256);
+    ensure!(!<AfloatOffers<T>>::contains_key(offer_id), Error::<T>::OfferAlreadyExists);
+    <OfferCountByCreator<T>>::try_mutate(&offer.creator_id, |count| {
+      Self::count_index_entry(count, T::MaxOffersPerCreator::get(), Error::<T>::TooManyOffers)
+    })?;
+    <OfferCountByTaxCredit<T>>::try_mutate(offer.tax_credit_id, |count| {
+      Self::count_index_entry(
+        count,
+        T::MaxOfferHistoryPerTaxCredit::get(),
+        Error::<T>::TooManyOffers,
+      )
+    })?;
+
+    Self::do_match_offer(offer_id, &mut offer)?;
 
+    if offer.is_open() {
+      <OrderBook<T>>::try_mutate(offer.tax_credit_id, |offers| {
+        offers.try_push(offer_id).map_err(|_| Error::<T>::OrderBookFull)
+      })?;
+    }
+    <OfferIdsByCreator<T>>::insert(&offer.creator_id, offer_id, ());
+    <OfferIdsByTaxCredit<T>>::insert(offer.tax_credit_id, offer_id, ());
+    Self::index_offer_status(offer_id, None, offer.status);
     <AfloatOffers<T>>::insert(offer_id, offer);
 
-    Self::deposit_event(Event::BuyOrderCreated(authority));
+    Ok(offer_id)
+  }
+
+  /// Matches a new offer against the open offers of the other side for the same tax credit
+  /// with price-time priority: the best price first and, at the same price, the oldest offer
+  /// first. Every crossing offer is traded at its own price for as many credits as both offers
+  /// have left, until the new offer is filled.
+  ///
+  /// - `offer_id`: The id of the new offer, it isn't stored yet.
+  /// - `offer`: The new offer, its remaining credits are updated with the matched ones.
+  ///
+  /// Offers of the same creator are never matched against each other.
+  fn do_match_offer(offer_id: StorageId, offer: &mut Offer<T>) -> DispatchResult {
+    let now = T::TimeProvider::now().as_secs();
+    let mut crossing_offers: Vec<(StorageId, Offer<T>)> = <OrderBook<T>>::get(offer.tax_credit_id)
+      .into_iter()
+      .filter_map(|resting_id| {
+        <AfloatOffers<T>>::get(resting_id).map(|resting| (resting_id, resting))
+      })
+      .filter(|(_, resting)| {
+        resting.offer_type != offer.offer_type
+          && resting.creator_id != offer.creator_id
+          && resting.is_open()
+          && !resting.is_expired(now)
+          && match offer.offer_type {
+            OfferType::Sell => resting.price_per_credit >= offer.price_per_credit,
+            OfferType::Buy => resting.price_per_credit <= offer.price_per_credit,
+          }
+      })
+      .collect();
+
+    // The order book keeps the offers in the order they were created and the sort is stable,
+    // so offers with the same price keep their time priority
+    match offer.offer_type {
+      OfferType::Sell => {
+        crossing_offers.sort_by(|(_, a), (_, b)| b.price_per_credit.cmp(&a.price_per_credit))
+      },
+      OfferType::Buy => {
+        crossing_offers.sort_by(|(_, a), (_, b)| a.price_per_credit.cmp(&b.price_per_credit))
+      },
+    }
+
+    for (resting_id, resting) in crossing_offers {
+      if offer.tax_credit_amount_remaining == 0 {
+        break;
+      }
+      let tax_credit_amount =
+        offer.tax_credit_amount_remaining.min(resting.tax_credit_amount_remaining);
+      let transaction_id = Self::do_create_transaction(
+        resting_id,
+        &resting,
+        offer.creator_id.clone(),
+        tax_credit_amount,
+        Some(offer_id),
+      )?;
+      offer.tax_credit_amount_remaining -= tax_credit_amount;
+      offer.status = OfferStatus::MATCHED;
 
+      Self::deposit_event(Event::OffersMatched(resting_id, offer_id, transaction_id));
+    }
+    Ok(())
+  }
+
+  fn remove_from_order_book(
+    tax_credit_id: <T as pallet_uniques::Config>::ItemId,
+    offer_id: StorageId,
+  ) {
+    <OrderBook<T>>::mutate_exists(tax_credit_id, |offers| {
+      if let Some(open_offers) = offers {
+        open_offers.retain(|id| *id != offer_id);
+        if open_offers.is_empty() {
+          *offers = None;
+        }
+      }
+    });
+  }
+
+  // Moves an offer to its new status in `OfferIdsByStatus`, `old_status` is `None` for new offers
+  fn index_offer_status(offer_id: StorageId, old_status: Option<OfferStatus>, status: OfferStatus) {
+    if let Some(old_status) = old_status {
+      <OfferIdsByStatus<T>>::remove(old_status, offer_id);
+    }
+    <OfferIdsByStatus<T>>::insert(status, offer_id, ());
+  }
+
+  // Adds a new transaction to the indexes of its parties, tax credit and status, failing with
+  // `TooManyTransactions` if any of them is full
+  fn index_transaction(transaction_id: StorageId, transaction: &Transaction<T>) -> DispatchResult {
+    for party in [&transaction.seller_id, &transaction.buyer_id] {
+      <TransactionCountByUser<T>>::try_mutate(party, |count| {
+        Self::count_index_entry(
+          count,
+          T::MaxTransactionsPerUser::get(),
+          Error::<T>::TooManyTransactions,
+        )
+      })?;
+    }
+    <TransactionCountByTaxCredit<T>>::try_mutate(transaction.tax_credit_id, |count| {
+      Self::count_index_entry(
+        count,
+        T::MaxTransactionsPerTaxCredit::get(),
+        Error::<T>::TooManyTransactions,
+      )
+    })?;
+    <TransactionIdsByUser<T>>::insert(&transaction.seller_id, transaction_id, ());
+    <TransactionIdsByUser<T>>::insert(&transaction.buyer_id, transaction_id, ());
+    <TransactionIdsByTaxCredit<T>>::insert(transaction.tax_credit_id, transaction_id, ());
+    <TransactionIdsByStatus<T>>::insert(transaction.status, transaction_id, ());
     Ok(())
   }
 
-  pub fn do_take_sell_order(authority: OriginFor<T>, order_id: [u8; 32]) -> DispatchResult
+  // Counts a new entry of an index that holds at most `max` entries under each key
+  fn count_index_entry(count: &mut u32, max: u32, error: Error<T>) -> DispatchResult {
+    ensure!(*count < max, error);
+    *count += 1;
+    Ok(())
+  }
+
+  // ! Transfer form workflow
+
+  // Runs `f` on a transaction, ensuring it's in the expected status and keeping its status indexed
+  fn mutate_transaction<F>(
+    transaction_id: StorageId,
+    expected_status: OfferStatus,
+    f: F,
+  ) -> DispatchResult
   where
-    <T as pallet_uniques::Config>::ItemId: From<u32>,
+    F: FnOnce(&mut Transaction<T>) -> DispatchResult,
   {
-    let who = ensure_signed(authority.clone())?;
+    <AfloatTransactions<T>>::try_mutate::<_, _, DispatchError, _>(transaction_id, |transaction| {
+      let transaction = transaction.as_mut().ok_or(Error::<T>::TransactionNotFound)?;
+      ensure!(transaction.status == expected_status, Error::<T>::InvalidTransactionStatus);
+      f(transaction)?;
+
+      if transaction.status != expected_status {
+        <TransactionIdsByStatus<T>>::remove(expected_status, transaction_id);
+        <TransactionIdsByStatus<T>>::insert(transaction.status, transaction_id, ());
+      }
+      Ok(())
+    })
+  }
+
+  /// The seller uploads the filled transfer form of a matched transaction.
+  ///
+  /// - `authority`: The `AccountId` of the seller.
+  /// - `transaction_id`: The id of the transaction.
+  /// - `cid`: The CID of the filled transfer form.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if the transaction is not found, is not `MATCHED`, or if `authority` is
+  /// not its seller.
+  pub fn do_fill_transfer_form(
+    authority: T::AccountId,
+    transaction_id: StorageId,
+    cid: ShortString,
+  ) -> DispatchResult {
+    Self::mutate_transaction(transaction_id, OfferStatus::MATCHED, |transaction| {
+      ensure!(transaction.seller_id == authority, Error::<T>::Unauthorized);
+      transaction.documents.transfer_form_cid = Some(cid);
+      transaction.status = OfferStatus::TF_FILLED;
+      Ok(())
+    })?;
+
+    Self::deposit_event(Event::TransactionStatusUpdated(
+      authority,
+      transaction_id,
+      OfferStatus::TF_FILLED,
+    ));
+    Ok(())
+  }
+
+  /// The seller or the buyer sign the transfer form, once both signed it's `TF_SIGNED`.
+  ///
+  /// - `authority`: The `AccountId` of the seller or the buyer.
+  /// - `transaction_id`: The id of the transaction.
+  /// - `cid`: The CID of the signed transfer form.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if the transaction is not found, is not `TF_PENDING_SIGNATURE`, if
+  /// `authority` is not one of its parties or if it already signed.
+  pub fn do_sign_transfer_form(
+    authority: T::AccountId,
+    transaction_id: StorageId,
+    cid: ShortString,
+  ) -> DispatchResult {
+    let now = T::TimeProvider::now().as_secs();
+    let mut status = OfferStatus::TF_PENDING_SIGNATURE;
+    Self::mutate_transaction(transaction_id, OfferStatus::TF_PENDING_SIGNATURE, |transaction| {
+      if transaction.seller_id == authority {
+        ensure!(
+          transaction.seller_confirmation_date.is_none(),
+          Error::<T>::TransferFormAlreadySigned
+        );
+        transaction.seller_confirmation_date = Some(now);
+        transaction.documents.seller_signature_cid = Some(cid);
+      } else if transaction.buyer_id == authority {
+        ensure!(
+          transaction.buyer_confirmation_date.is_none(),
+          Error::<T>::TransferFormAlreadySigned
+        );
+        transaction.buyer_confirmation_date = Some(now);
+        transaction.documents.buyer_signature_cid = Some(cid);
+      } else {
+        return Err(Error::<T>::Unauthorized.into());
+      }
+
+      if transaction.seller_confirmation_date.is_some()
+        && transaction.buyer_confirmation_date.is_some()
+      {
+        transaction.status = OfferStatus::TF_SIGNED;
+      }
+      status = transaction.status;
+      Ok(())
+    })?;
 
-    ensure!(!Self::get_all_roles_for_user(who.clone()).is_empty(), Error::<T>::Unauthorized);
+    Self::deposit_event(Event::TransferFormSigned(authority.clone(), transaction_id));
+    if status == OfferStatus::TF_SIGNED {
+      Self::deposit_event(Event::TransactionStatusUpdated(authority, transaction_id, status));
+    }
+    Ok(())
+  }
 
-    pallet_gated_marketplace::Pallet::<T>::do_take_sell_offer(authority.clone(), order_id)?;
+  /// A CPA reviews the transfer form, submits it to the tax agency and records its approval.
+  ///
+  /// - `authority`: The `AccountId` of the CPA.
+  /// - `transaction_id`: The id of the transaction.
+  /// - `args`: `RequestSignatures` moves a `TF_FILLED` form to `TF_PENDING_SIGNATURE`,
+  ///   `SubmitToAgency` a `TF_SIGNED` form to `TF_AGENCY_SUBMITTED` and `AgencyApproved` a
+  ///   `TF_AGENCY_SUBMITTED` form to `TF_AGENCY_APPROVED`, the last two with the CID of the
+  ///   agency document.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if `authority` is not a CPA, or if the transaction is not found or not in
+  /// the status the step requires.
+  pub fn do_cpa_update_transfer_form(
+    authority: T::AccountId,
+    transaction_id: StorageId,
+    args: CpaTransferFormArgs,
+  ) -> DispatchResult {
+    ensure!(Self::is_cpa(authority.clone()), Error::<T>::Unauthorized);
 
-    Self::deposit_event(Event::SellOrderTaken(who));
+    let (expected_status, status) = match args {
+      CpaTransferFormArgs::RequestSignatures => {
+        (OfferStatus::TF_FILLED, OfferStatus::TF_PENDING_SIGNATURE)
+      },
+      CpaTransferFormArgs::SubmitToAgency { .. } => {
+        (OfferStatus::TF_SIGNED, OfferStatus::TF_AGENCY_SUBMITTED)
+      },
+      CpaTransferFormArgs::AgencyApproved { .. } => {
+        (OfferStatus::TF_AGENCY_SUBMITTED, OfferStatus::TF_AGENCY_APPROVED)
+      },
+    };
+
+    Self::mutate_transaction(transaction_id, expected_status, |transaction| {
+      match args {
+        CpaTransferFormArgs::RequestSignatures => {},
+        CpaTransferFormArgs::SubmitToAgency { cid } => {
+          transaction.documents.agency_submission_cid = Some(cid);
+        },
+        CpaTransferFormArgs::AgencyApproved { cid } => {
+          transaction.documents.agency_approval_cid = Some(cid);
+        },
+      }
+      transaction.status = status;
+      Ok(())
+    })?;
+
+    Self::deposit_event(Event::TransactionStatusUpdated(authority, transaction_id, status));
     Ok(())
   }
 
-  pub fn do_take_buy_order(authority: T::AccountId, order_id: [u8; 32]) -> DispatchResult
+  /// Afloat approves a transaction once the tax agency approved its transfer form, and the
+  /// transaction is settled.
+  ///
+  /// - `authority`: The `AccountId` of an Afloat admin.
+  /// - `transaction_id`: The id of the transaction.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if the transaction is not found, is not `TF_AGENCY_APPROVED` or can't be
+  /// settled.
+  pub fn do_approve_transaction(
+    authority: T::AccountId,
+    transaction_id: StorageId,
+  ) -> DispatchResult
   where
     <T as pallet_uniques::Config>::ItemId: From<u32>,
   {
-    ensure!(!Self::get_all_roles_for_user(authority.clone()).is_empty(), Error::<T>::Unauthorized);
+    Self::mutate_transaction(transaction_id, OfferStatus::TF_AGENCY_APPROVED, |transaction| {
+      transaction.status = OfferStatus::AFLOAT_APPROVED;
+      Ok(())
+    })?;
+    Self::do_settle_transaction(transaction_id)?;
+
+    Self::deposit_event(Event::TransactionStatusUpdated(
+      authority,
+      transaction_id,
+      OfferStatus::AFLOAT_APPROVED,
+    ));
+    Ok(())
+  }
 
-    pallet_gated_marketplace::Pallet::<T>::do_take_buy_offer(authority.clone(), order_id)?;
+  /// Settles an approved transaction, paid with the Afloat asset. A seller that sells every
+  /// credit its tax credit holds transfers the tax credit to the buyer, otherwise the seller
+  /// enlists a sell offer in the Afloat marketplace for the traded share of the tax credit and the
+  /// buyer takes it, getting a child tax credit with the traded credits. Nothing is settled if any
+  /// step fails.
+  ///
+  /// - `transaction_id`: The id of the transaction.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if the transaction is not found, if the seller no longer holds the traded
+  /// credits, if the share of the tax credit is below 1% or above 99% without being all of it,
+  /// or if the payment or the marketplace offer fail, e.g. when the buyer doesn't have enough
+  /// balance.
+  ///
+  /// # Returns
+  ///
+  /// Returns `Ok(())` on success, the id of the marketplace offer, if any, is stored in the
+  /// transaction.
+  pub fn do_settle_transaction(transaction_id: StorageId) -> DispatchResult
+  where
+    <T as pallet_uniques::Config>::ItemId: From<u32>,
+  {
+    let transaction =
+      <AfloatTransactions<T>>::get(transaction_id).ok_or(Error::<T>::TransactionNotFound)?;
+    let marketplace_id = AfloatMarketPlaceId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
+    let collection_id = AfloatCollectionId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
+    ensure!(
+      pallet_uniques::Pallet::<T>::owner(collection_id, transaction.tax_credit_id)
+        == Some(transaction.seller_id.clone()),
+      Error::<T>::NotOwnerOfTaxCredit
+    );
+
+    // Share of the tax credit traded, relative to the credits it holds
+    let held_credits = <TaxCreditAmounts<T>>::get(transaction.tax_credit_id);
+    ensure!(
+      transaction.tax_credit_amount <= held_credits,
+      Error::<T>::NotEnoughTaxCreditsAvailable
+    );
+    let sells_everything = transaction.tax_credit_amount == held_credits;
+    let share = Permill::from_rational(transaction.tax_credit_amount, held_credits);
+    ensure!(
+      sells_everything || share >= Permill::from_percent(1),
+      Error::<T>::TaxCreditShareTooSmall
+    );
+
+    let marketplace_offer_id =
+      with_storage_layer(|| -> Result<Option<StorageId>, DispatchError> {
+        let marketplace_offer_id = if sells_everything {
+          Self::transfer_tax_credit(collection_id, &transaction)?;
+          None
+        } else {
+          let child_id: <T as pallet_uniques::Config>::ItemId =
+            pallet_fruniques::Pallet::<T>::next_frunique(collection_id).into();
+          let marketplace_offer_id = pallet_gated_marketplace::Pallet::<T>::do_enlist_sell_offer(
+            transaction.seller_id.clone(),
+            marketplace_id,
+            collection_id,
+            transaction.tax_credit_id,
+            transaction.total_price,
+            share,
+          )?;
+          pallet_gated_marketplace::Pallet::<T>::do_take_sell_offer(
+            RawOrigin::Signed(transaction.buyer_id.clone()).into(),
+            marketplace_offer_id,
+          )?;
+          <TaxCreditAmounts<T>>::insert(
+            transaction.tax_credit_id,
+            held_credits - transaction.tax_credit_amount,
+          );
+          <TaxCreditAmounts<T>>::insert(child_id, transaction.tax_credit_amount);
+          // The child is verified through the collection policy, see
+          // `set_afloat_verification_policy`
+          if let Some(jurisdiction) = <TaxCreditJurisdiction<T>>::get(transaction.tax_credit_id) {
+            <TaxCreditJurisdiction<T>>::insert(child_id, jurisdiction);
+          }
+          Some(marketplace_offer_id)
+        };
+        Self::charge_fee(&transaction)?;
+
+        <AfloatTransactions<T>>::try_mutate::<_, _, DispatchError, _>(
+          transaction_id,
+          |transaction| {
+            let transaction = transaction.as_mut().ok_or(Error::<T>::TransactionNotFound)?;
+            transaction.marketplace_offer_id = marketplace_offer_id;
+            Ok(())
+          },
+        )?;
+        Ok(marketplace_offer_id)
+      })?;
+
+    if !transaction.fee.is_zero() {
+      Self::deposit_event(Event::FeeCharged(
+        transaction_id,
+        transaction.seller_id,
+        transaction.fee,
+      ));
+    }
+    Self::deposit_event(Event::TransactionSettled(transaction_id, marketplace_offer_id));
+    Ok(())
+  }
+
+  // The buyer pays the seller and gets the whole tax credit, the marketplace only trades shares
+  // of an item
+  fn transfer_tax_credit(
+    collection_id: <T as pallet_uniques::Config>::CollectionId,
+    transaction: &Transaction<T>,
+  ) -> DispatchResult {
+    let asset_id = AfloatAssetId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
+    pallet_fruniques::Pallet::<T>::ensure_sellable(collection_id, transaction.tax_credit_id)?;
+    <pallet_mapped_assets::Pallet<T> as fungibles::Transfer<T::AccountId>>::transfer(
+      asset_id,
+      &transaction.buyer_id,
+      &transaction.seller_id,
+      transaction.total_price,
+      false,
+    )?;
+    pallet_uniques::Pallet::<T>::do_transfer(
+      collection_id,
+      transaction.tax_credit_id,
+      transaction.buyer_id.clone(),
+      |_, _| Ok(()),
+    )
+  }
+
+  /// Replaces the fee schedule and the platform account the fees are paid to. Transactions
+  /// that were already created keep their fee.
+  ///
+  /// - `authority`: The owner of Afloat.
+  /// - `schedule`: The new fee schedule.
+  /// - `fee_account`: The account that receives the fees.
+  ///
+  /// # Errors
+  ///
+  /// Returns `InvalidFeeSchedule` if the tiers aren't sorted by volume.
+  pub fn do_set_fee_schedule(
+    authority: T::AccountId,
+    schedule: FeeSchedule<T>,
+    fee_account: T::AccountId,
+  ) -> DispatchResult {
+    ensure!(schedule.is_valid(), Error::<T>::InvalidFeeSchedule);
+    <AfloatFeeSchedule<T>>::put(schedule);
+    <AfloatFeeAccount<T>>::put(fee_account);
+    Self::deposit_event(Event::FeeScheduleUpdated(authority));
+    Ok(())
+  }
+
+  /// Fee of a sale by `seller` at the current fee schedule and the seller's volume in the
+  /// current fee period, zero if there is no fee schedule.
+  pub fn quote_fee(seller: &T::AccountId, amount: T::Balance) -> T::Balance {
+    <AfloatFeeSchedule<T>>::get().map_or(Zero::zero(), |schedule| {
+      schedule.fee(<TradedVolume<T>>::get(Self::fee_period(), seller), amount)
+    })
+  }
+
+  /// Index of the current fee period.
+  pub fn fee_period() -> u64 {
+    T::TimeProvider::now().as_secs() / T::FeePeriod::get().max(1)
+  }
+
+  // Pays the fee of a settled transaction from the seller's proceeds to the fee account and
+  // adds the sale to the period totals
+  fn charge_fee(transaction: &Transaction<T>) -> DispatchResult {
+    let period = Self::fee_period();
+    if !transaction.fee.is_zero() {
+      let fee_account = <AfloatFeeAccount<T>>::get().ok_or(Error::<T>::NotInitialized)?;
+      let asset_id = AfloatAssetId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
+      <pallet_mapped_assets::Pallet<T> as fungibles::Transfer<T::AccountId>>::transfer(
+        asset_id,
+        &transaction.seller_id,
+        &fee_account,
+        transaction.fee,
+        false,
+      )?;
+      <FeeRevenue<T>>::mutate(period, |revenue| *revenue = revenue.saturating_add(transaction.fee));
+    }
+    <TradedVolume<T>>::mutate(period, &transaction.seller_id, |volume| {
+      *volume = volume.saturating_add(transaction.total_price)
+    });
+    Ok(())
+  }
+
+  // Buys all the tax credits left in a sell offer
+  pub fn do_take_sell_order(authority: T::AccountId, order_id: [u8; 32]) -> DispatchResult {
+    let offer = <AfloatOffers<T>>::get(order_id).ok_or(Error::<T>::OfferNotFound)?;
+    ensure!(offer.offer_type == OfferType::Sell, Error::<T>::InvalidOfferType);
+
+    Self::do_accept_offer(authority.clone(), order_id, None)?;
+
+    Self::deposit_event(Event::SellOrderTaken(authority));
+    Ok(())
+  }
+
+  // Sells all the tax credits left in a buy offer
+  pub fn do_take_buy_order(authority: T::AccountId, order_id: [u8; 32]) -> DispatchResult {
+    let offer = <AfloatOffers<T>>::get(order_id).ok_or(Error::<T>::OfferNotFound)?;
+    ensure!(offer.offer_type == OfferType::Buy, Error::<T>::InvalidOfferType);
+
+    Self::do_accept_offer(authority.clone(), order_id, None)?;
 
     Self::deposit_event(Event::BuyOrderTaken(authority));
     Ok(())
   }
 
+  // Offers can only be traded while they are open, not cancelled and not expired
+  fn ensure_offer_available(offer: &Offer<T>) -> DispatchResult {
+    ensure!(offer.status != OfferStatus::CANCELLED, Error::<T>::OfferCancelled);
+    ensure!(!offer.is_expired(T::TimeProvider::now().as_secs()), Error::<T>::OfferExpired);
+    ensure!(offer.is_open(), Error::<T>::OfferNotAvailable);
+    Ok(())
+  }
+
+  /// Cancels the tax credits left in an offer, the transactions already created are kept.
+  ///
+  /// - `authority`: The `AccountId` of the creator of the offer.
+  /// - `offer_id`: The id of the offer to cancel.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if the offer is not found, if `authority` didn't create it, or if it's
+  /// no longer open.
+  pub fn do_cancel_offer(authority: T::AccountId, offer_id: StorageId) -> DispatchResult {
+    <AfloatOffers<T>>::try_mutate::<_, _, DispatchError, _>(offer_id, |offer| {
+      let offer = offer.as_mut().ok_or(Error::<T>::OfferNotFound)?;
+      ensure!(offer.creator_id == authority, Error::<T>::Unauthorized);
+      Self::ensure_offer_available(offer)?;
+
+      Self::index_offer_status(offer_id, Some(offer.status), OfferStatus::CANCELLED);
+      offer.status = OfferStatus::CANCELLED;
+      offer.cancellation_date = Some(T::TimeProvider::now().as_secs());
+      Self::remove_from_order_book(offer.tax_credit_id, offer_id);
+      Ok(())
+    })?;
+
+    Self::deposit_event(Event::OfferCancelled(authority, offer_id));
+    Ok(())
+  }
+
+  /// Marks the open offers past their expiration date as expired, continuing from the last offer
+  /// checked in the previous call, and returns the weight consumed.
+  ///
+  /// - `remaining_weight`: The maximum weight this sweep can consume.
+  pub fn do_expire_offers(remaining_weight: Weight) -> Weight {
+    let db_weight = T::DbWeight::get();
+    // Reading and writing the offer, its order book and its status index
+    let offer_weight = db_weight.reads_writes(2, 4);
+    // Reading and writing the cursor
+    let mut used_weight = db_weight.reads_writes(1, 1);
+    if remaining_weight.any_lt(used_weight.saturating_add(offer_weight)) {
+      return Weight::zero();
+    }
+
+    let now = T::TimeProvider::now().as_secs();
+    let mut cursor = <OffersSweepCursor<T>>::get();
+    let mut offers = match cursor {
+      Some(last_offer_id) => {
+        <AfloatOffers<T>>::iter_from(<AfloatOffers<T>>::hashed_key_for(last_offer_id))
+      },
+      None => <AfloatOffers<T>>::iter(),
+    };
+
+    while !remaining_weight.any_lt(used_weight.saturating_add(offer_weight)) {
+      let (offer_id, mut offer) = match offers.next() {
+        Some(entry) => entry,
+        None => {
+          // Every offer was checked, the next sweep starts over
+          cursor = None;
+          break;
+        },
+      };
+      used_weight = used_weight.saturating_add(db_weight.reads(1));
+      cursor = Some(offer_id);
+
+      if offer.is_open() && offer.is_expired(now) {
+        Self::index_offer_status(offer_id, Some(offer.status), OfferStatus::EXPIRED);
+        offer.status = OfferStatus::EXPIRED;
+        Self::remove_from_order_book(offer.tax_credit_id, offer_id);
+        <AfloatOffers<T>>::insert(offer_id, offer);
+        used_weight = used_weight.saturating_add(db_weight.reads_writes(1, 4));
+        Self::deposit_event(Event::OfferExpired(offer_id));
+      }
+    }
+
+    <OffersSweepCursor<T>>::set(cursor);
+    used_weight
+  }
+
+  // ! CPA attestation
+
+  /// Assigns a CPA to a seller, replacing the previous one if any.
+  ///
+  /// - `seller`: The `AccountId` of the seller.
+  /// - `cpa`: The `AccountId` of the CPA that attests the tax credits of the seller.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if the seller is not registered or if `cpa` doesn't have the CPA role.
+  pub fn do_assign_cpa(seller: T::AccountId, cpa: T::AccountId) -> DispatchResult {
+    ensure!(<UserInfo<T>>::contains_key(seller.clone()), Error::<T>::UserNotFound);
+    ensure!(Self::is_cpa(cpa.clone()), Error::<T>::NotACpa);
+
+    <CpaBySeller<T>>::insert(seller.clone(), cpa.clone());
+
+    Self::deposit_event(Event::CpaAssigned(seller, cpa));
+    Ok(())
+  }
+
+  /// The CPA assigned to the owner of a tax credit attests it through the fruniques verification.
+  ///
+  /// - `cpa`: The `AccountId` of the CPA.
+  /// - `tax_credit_id`: The id of the tax credit frunique.
+  /// - `evidence`: An optional CID of the documents reviewed by the CPA.
+  ///
+  /// # Errors
+  ///
+  /// Returns an `Error` if the tax credit is not found, if `cpa` is not the CPA assigned to its
+  /// owner, or if the fruniques pallet rejects the attestation.
+  pub fn do_attest_tax_credit(
+    cpa: T::AccountId,
+    tax_credit_id: <T as pallet_uniques::Config>::ItemId,
+    evidence: Option<CID>,
+  ) -> DispatchResult {
+    let collection_id = AfloatCollectionId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
+    let owner = pallet_uniques::Pallet::<T>::owner(collection_id, tax_credit_id)
+      .ok_or(Error::<T>::TaxCreditNotFound)?;
+    ensure!(<CpaBySeller<T>>::get(owner) == Some(cpa.clone()), Error::<T>::CpaNotAssigned);
+
+    pallet_fruniques::Pallet::<T>::do_attest(cpa.clone(), collection_id, tax_credit_id, evidence)?;
+
+    Self::deposit_event(Event::TaxCreditAttested(cpa, tax_credit_id));
+    Ok(())
+  }
+
+  // Only tax credits verified through the CPA attestation can be sold
+  fn ensure_tax_credit_attested(
+    tax_credit_id: <T as pallet_uniques::Config>::ItemId,
+  ) -> DispatchResult {
+    let collection_id = AfloatCollectionId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
+    ensure!(
+      pallet_fruniques::Pallet::<T>::frunique_verified(collection_id, tax_credit_id)
+        .unwrap_or(false),
+      Error::<T>::TaxCreditNotAttested
+    );
+    Ok(())
+  }
+  // Users can only trade federal tax credits and the ones of their own jurisdiction
+  fn ensure_can_trade(
+    user_address: T::AccountId,
+    tax_credit_id: <T as pallet_uniques::Config>::ItemId,
+  ) -> DispatchResult {
+    let user = <UserInfo<T>>::get(user_address).ok_or(Error::<T>::UserNotFound)?;
+    let jurisdiction =
+      <TaxCreditJurisdiction<T>>::get(tax_credit_id).ok_or(Error::<T>::TaxCreditNotFound)?;
+    ensure!(user.group.can_trade(jurisdiction), Error::<T>::JurisdictionNotAllowed);
+    Ok(())
+  }
+
   pub fn do_create_tax_credit(
     owner: T::AccountId,
     metadata: CollectionDescription<T>,
     attributes: Option<Attributes<T>>,
     parent_info: Option<ParentInfo<T>>,
+    jurisdiction: Jurisdiction,
+    tax_credit_amount: u32,
   ) -> DispatchResult
   where
     <T as pallet_uniques::Config>::ItemId: From<u32>,
     <T as pallet_uniques::Config>::CollectionId: From<u32>,
   {
     ensure!(!Self::get_all_roles_for_user(owner.clone()).is_empty(), Error::<T>::Unauthorized);
+    ensure!(tax_credit_amount > 0, Error::<T>::InvalidTaxCreditAmount);
+    let user = <UserInfo<T>>::get(owner.clone()).ok_or(Error::<T>::UserNotFound)?;
+    ensure!(user.group.can_trade(jurisdiction), Error::<T>::JurisdictionNotAllowed);
 
     let collection = AfloatCollectionId::<T>::get().unwrap();
+    let tax_credit_id: <T as pallet_uniques::Config>::ItemId =
+      pallet_fruniques::Pallet::<T>::next_frunique(collection).into();
+
+    pallet_fruniques::Pallet::<T>::do_spawn(collection, owner, metadata, attributes, parent_info)?;
+    <TaxCreditJurisdiction<T>>::insert(tax_credit_id, jurisdiction);
+    <TaxCreditAmounts<T>>::insert(tax_credit_id, tax_credit_amount);
+    Ok(())
+  }
 
-    pallet_fruniques::Pallet::<T>::do_spawn(collection, owner, metadata, attributes, parent_info)
+  /// Lets the children spawned when part of a tax credit is sold keep the CPA attestation of
+  /// their parent.
+  ///
+  /// - `owner`: owner of the Afloat collection.
+  pub fn set_afloat_verification_policy(owner: T::AccountId) -> DispatchResult {
+    let collection_id = AfloatCollectionId::<T>::get().ok_or(Error::<T>::NotInitialized)?;
+    pallet_fruniques::Pallet::<T>::do_set_verification_policy(
+      owner,
+      collection_id,
+      VerificationPolicy { propagate_to_children: true, ..Default::default() },
+    )
   }
 
   pub fn create_afloat_collection(
@@ -535,6 +1730,9 @@ impl<T: Config> Pallet<T> {
   pub fn do_delete_all_users() -> DispatchResult {
     UserInfo::<T>::iter_keys().try_for_each(|account_id| {
       if !Self::is_admin_or_owner(account_id.clone()) {
+        if Self::is_cpa(account_id.clone()) {
+          Self::remove_from_afloat_collection(account_id.clone(), FruniqueRole::Verifier)?;
+        }
         let user_roles = Self::get_all_roles_for_user(account_id.clone());
 
         if !user_roles.is_empty() {
@@ -552,3 +1750,42 @@ impl<T: Config> Pallet<T> {
     Ok(())
   }
 }
+
+impl<T: Config> StoragePurge for Pallet<T> {
+  const STAGES: u32 = 21;
+
+  fn clear_stage(stage: u32, limit: u32, cursor: Option<&[u8]>) -> MultiRemovalResults {
+    match stage {
+      0 => <UserInfo<T>>::clear(limit, cursor),
+      1 => <AfloatOffers<T>>::clear(limit, cursor),
+      2 => <AfloatTransactions<T>>::clear(limit, cursor),
+      3 => <CpaBySeller<T>>::clear(limit, cursor),
+      4 => <RampRequests<T>>::clear(limit, cursor),
+      5 => <OrderBook<T>>::clear(limit, cursor),
+      6 => <OfferIdsByCreator<T>>::clear(limit, cursor),
+      7 => <OfferIdsByTaxCredit<T>>::clear(limit, cursor),
+      8 => <OfferIdsByStatus<T>>::clear(limit, cursor),
+      9 => <TransactionIdsByUser<T>>::clear(limit, cursor),
+      10 => <TransactionIdsByTaxCredit<T>>::clear(limit, cursor),
+      11 => <TransactionIdsByStatus<T>>::clear(limit, cursor),
+      12 => <SignUpRequests<T>>::clear(limit, cursor),
+      13 => <TaxCreditJurisdiction<T>>::clear(limit, cursor),
+      14 => <FeeRevenue<T>>::clear(limit, cursor),
+      15 => <TradedVolume<T>>::clear(limit, cursor),
+      16 => <TaxCreditAmounts<T>>::clear(limit, cursor),
+      17 => <OfferCountByCreator<T>>::clear(limit, cursor),
+      18 => <OfferCountByTaxCredit<T>>::clear(limit, cursor),
+      19 => <TransactionCountByUser<T>>::clear(limit, cursor),
+      20 => <TransactionCountByTaxCredit<T>>::clear(limit, cursor),
+      _ => MultiRemovalResults { maybe_cursor: None, backend: 0, unique: 0, loops: 0 },
+    }
+  }
+
+  fn purge_progress() -> Option<PurgeProgress> {
+    <StoragePurgeProgress<T>>::get()
+  }
+
+  fn set_purge_progress(progress: Option<PurgeProgress>) {
+    <StoragePurgeProgress<T>>::set(progress);
+  }
+}
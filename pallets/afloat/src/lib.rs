@@ -9,23 +9,28 @@ mod mock;
 mod tests;
 
 mod functions;
+pub mod migration;
 pub mod types;
 
 #[frame_support::pallet]
 pub mod pallet {
   use frame_support::{
     pallet_prelude::*,
-    sp_io::hashing::blake2_256,
     traits::{Currency, UnixTime},
   };
-  use frame_system::{pallet_prelude::*, RawOrigin};
-  use pallet_fruniques::types::{Attributes, CollectionDescription, FruniqueRole, ParentInfo};
+  use frame_system::pallet_prelude::*;
+  use pallet_fruniques::types::{Attributes, CollectionDescription, ParentInfo, CID};
   use pallet_gated_marketplace::types::*;
-  use sp_runtime::Permill;
-  const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+  use sp_runtime::sp_std::vec::Vec;
+  const STORAGE_VERSION: StorageVersion = StorageVersion::new(2);
 
   use crate::types::*;
-  use pallet_rbac::types::RoleBasedAccessControl;
+  // Both type modules define an `OfferStatus`, the Afloat one is used
+  use crate::types::OfferStatus;
+  use pallet_rbac::{
+    purge::{self, PurgeProgress, StoragePurge},
+    types::RoleBasedAccessControl,
+  };
 
   pub type BalanceOf<T> = <<T as pallet_uniques::Config>::Currency as Currency<
     <T as frame_system::Config>::AccountId,
@@ -37,7 +42,7 @@ pub mod pallet {
     frame_system::Config
     + pallet_gated_marketplace::Config
     + pallet_mapped_assets::Config
-    + pallet_uniques::Config
+    + pallet_uniques::Config<CollectionId = CollectionId>
   {
     type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
     type TimeProvider: UnixTime;
@@ -45,6 +50,24 @@ pub mod pallet {
     type RemoveOrigin: EnsureOrigin<Self::RuntimeOrigin>;
     type Currency: Currency<Self::AccountId>;
     type ItemId: Parameter + Member + Default;
+    /// Maximum number of open offers for a single tax credit in the order book.
+    #[pallet::constant]
+    type MaxOffersPerTaxCredit: Get<u32>;
+    /// Maximum number of offers a user can create.
+    #[pallet::constant]
+    type MaxOffersPerCreator: Get<u32>;
+    /// Maximum number of offers, open or not, that can be created for a single tax credit.
+    #[pallet::constant]
+    type MaxOfferHistoryPerTaxCredit: Get<u32>;
+    /// Maximum number of transactions a user can be a party of.
+    #[pallet::constant]
+    type MaxTransactionsPerUser: Get<u32>;
+    /// Maximum number of transactions of a single tax credit.
+    #[pallet::constant]
+    type MaxTransactionsPerTaxCredit: Get<u32>;
+    /// Length in seconds of the periods the fee revenue and the traded volume are totaled by.
+    #[pallet::constant]
+    type FeePeriod: Get<Date>;
   }
 
   #[pallet::pallet]
@@ -59,13 +82,29 @@ pub mod pallet {
   pub enum Event<T: Config> {
     SomethingStored(u32, T::AccountId),
     NewUser(T::AccountId),
+    SignUpRequested(T::AccountId, AfloatRole),
+    SignUpRejected(T::AccountId, T::AccountId),
     UserEdited(T::AccountId),
     UserDeleted(T::AccountId),
     SellOrderCreated(T::AccountId),
     BuyOrderCreated(T::AccountId),
     SellOrderTaken(T::AccountId),
     BuyOrderTaken(T::AccountId),
-    AfloatBalanceSet(T::AccountId, T::AccountId, T::Balance),
+    RampRequestCreated(T::AccountId, StorageId, RampRequestType),
+    RampRequestConfirmed(T::AccountId, T::AccountId, StorageId),
+    RampRequestRejected(T::AccountId, T::AccountId, StorageId),
+    OfferAccepted(T::AccountId, StorageId, StorageId),
+    OffersMatched(StorageId, StorageId, StorageId),
+    TransferFormSigned(T::AccountId, StorageId),
+    TransactionStatusUpdated(T::AccountId, StorageId, OfferStatus),
+    TransactionSettled(StorageId, Option<StorageId>),
+    OfferCancelled(T::AccountId, StorageId),
+    OfferExpired(StorageId),
+    CpaAssigned(T::AccountId, T::AccountId),
+    TaxCreditAttested(T::AccountId, <T as pallet_uniques::Config>::ItemId),
+    FeeScheduleUpdated(T::AccountId),
+    FeeCharged(StorageId, T::AccountId, T::Balance),
+    StoragePurged,
   }
 
   // Errors inform users that something went wrong.
@@ -93,6 +132,72 @@ pub mod pallet {
     NotInitialized,
     // Failed to remove afloat role
     FailedToRemoveAfloatRole,
+    // Offer not found
+    OfferNotFound,
+    // The offer can't be accepted in its current status
+    OfferNotAvailable,
+    // Users can't accept their own offers
+    CannotAcceptOwnOffer,
+    // The amount of tax credits must be greater than zero
+    InvalidTaxCreditAmount,
+    // The offer doesn't have enough tax credits left
+    NotEnoughTaxCreditsAvailable,
+    // Only the owner of the tax credit can sell it
+    NotOwnerOfTaxCredit,
+    // The price of the transaction overflowed
+    ArithmeticOverflow,
+    // Transaction already exists
+    TransactionAlreadyExists,
+    // Transaction not found
+    TransactionNotFound,
+    // The transfer form can't perform this step in its current status
+    InvalidTransactionStatus,
+    // The user already signed the transfer form
+    TransferFormAlreadySigned,
+    // The transaction is too small to be settled as a share of the tax credit
+    TaxCreditShareTooSmall,
+    // The offer was cancelled by its creator
+    OfferCancelled,
+    // The offer expired
+    OfferExpired,
+    // The offer is not of the expected type
+    InvalidOfferType,
+    // The user is not a CPA
+    NotACpa,
+    // Only the CPA assigned to the owner of the tax credit can attest it
+    CpaNotAssigned,
+    // Tax credit not found
+    TaxCreditNotFound,
+    // The tax credit hasn't been attested by a CPA
+    TaxCreditNotAttested,
+    // The amount must be greater than zero
+    InvalidAmount,
+    // The user doesn't have enough balance
+    InsufficientBalance,
+    // Deposit or withdrawal request not found
+    RampRequestNotFound,
+    // Deposit or withdrawal request already exists
+    RampRequestAlreadyExists,
+    // The deposit or withdrawal request was already confirmed or rejected
+    RampRequestAlreadyReviewed,
+    // Offer already exists
+    OfferAlreadyExists,
+    // The tax credit has too many open offers
+    OrderBookFull,
+    // The user doesn't have a pending sign up
+    SignUpRequestNotFound,
+    // The tax credit belongs to a jurisdiction the user can't trade in
+    JurisdictionNotAllowed,
+    // The fee tiers must be sorted by volume without duplicates
+    InvalidFeeSchedule,
+    // A storage removal started by kill_storage is still in progress
+    StoragePurgeInProgress,
+    // Afloat was already set up
+    AlreadyInitialized,
+    // The user or the tax credit has too many offers
+    TooManyOffers,
+    // The user or the tax credit has too many transactions
+    TooManyTransactions,
   }
 
   #[pallet::storage]
@@ -106,6 +211,12 @@ pub mod pallet {
     OptionQuery,
   >;
 
+  #[pallet::storage]
+  #[pallet::getter(fn sign_up_requests)]
+  /// Role requested by each user whose KYC application hasn't been reviewed yet.
+  pub(super) type SignUpRequests<T: Config> =
+    StorageMap<_, Blake2_128Concat, T::AccountId, AfloatRole, OptionQuery>;
+
   #[pallet::storage]
   #[pallet::getter(fn marketplace_id)]
   pub(super) type AfloatMarketPlaceId<T: Config> = StorageValue<
@@ -137,80 +248,333 @@ pub mod pallet {
   pub(super) type AfloatTransactions<T: Config> =
     StorageMap<_, Blake2_128Concat, StorageId, Transaction<T>, OptionQuery>;
 
+  #[pallet::storage]
+  #[pallet::getter(fn offer_ids_by_creator)]
+  /// Offers of each creator, at most `MaxOffersPerCreator`.
+  ///
+  /// Each entry of the offer and transaction indexes is the key of a single offer or transaction
+  /// id, so writing one never decodes or grows a value, and they are only read in pages of at
+  /// most `MAX_PAGE_SIZE` ids. The number of entries under each key is kept in the `*Count*`
+  /// maps next to them.
+  pub(super) type OfferIdsByCreator<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    T::AccountId,
+    Blake2_128Concat,
+    StorageId,
+    (),
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn offer_ids_by_tax_credit)]
+  /// Offers of each tax credit, at most `MaxOfferHistoryPerTaxCredit`.
+  pub(super) type OfferIdsByTaxCredit<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    <T as pallet_uniques::Config>::ItemId,
+    Blake2_128Concat,
+    StorageId,
+    (),
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn offer_ids_by_status)]
+  /// Offers in each status. Every offer is under a single status, so it holds one entry per
+  /// offer of `OfferIdsByCreator`.
+  pub(super) type OfferIdsByStatus<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    OfferStatus,
+    Blake2_128Concat,
+    StorageId,
+    (),
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn transaction_ids_by_user)]
+  /// Transactions of each user, as seller or buyer, at most `MaxTransactionsPerUser`.
+  pub(super) type TransactionIdsByUser<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    T::AccountId,
+    Blake2_128Concat,
+    StorageId,
+    (),
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn transaction_ids_by_tax_credit)]
+  /// Transactions of each tax credit, at most `MaxTransactionsPerTaxCredit`.
+  pub(super) type TransactionIdsByTaxCredit<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    <T as pallet_uniques::Config>::ItemId,
+    Blake2_128Concat,
+    StorageId,
+    (),
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn transaction_ids_by_status)]
+  /// Transactions in each status. Every transaction is under a single status, so it holds one
+  /// entry per transaction of `TransactionIdsByTaxCredit`.
+  pub(super) type TransactionIdsByStatus<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    OfferStatus,
+    Blake2_128Concat,
+    StorageId,
+    (),
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn offer_count_by_creator)]
+  /// Number of offers in `OfferIdsByCreator` for each creator.
+  pub(super) type OfferCountByCreator<T: Config> =
+    StorageMap<_, Blake2_128Concat, T::AccountId, u32, ValueQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn offer_count_by_tax_credit)]
+  /// Number of offers in `OfferIdsByTaxCredit` for each tax credit.
+  pub(super) type OfferCountByTaxCredit<T: Config> =
+    StorageMap<_, Blake2_128Concat, <T as pallet_uniques::Config>::ItemId, u32, ValueQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn transaction_count_by_user)]
+  /// Number of transactions in `TransactionIdsByUser` for each user.
+  pub(super) type TransactionCountByUser<T: Config> =
+    StorageMap<_, Blake2_128Concat, T::AccountId, u32, ValueQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn transaction_count_by_tax_credit)]
+  /// Number of transactions in `TransactionIdsByTaxCredit` for each tax credit.
+  pub(super) type TransactionCountByTaxCredit<T: Config> =
+    StorageMap<_, Blake2_128Concat, <T as pallet_uniques::Config>::ItemId, u32, ValueQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn order_book)]
+  /// Open offers of each tax credit, in the order they were created.
+  pub(super) type OrderBook<T: Config> = StorageMap<
+    _,
+    Blake2_128Concat,
+    <T as pallet_uniques::Config>::ItemId,
+    BoundedVec<StorageId, T::MaxOffersPerTaxCredit>,
+    ValueQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn tax_credit_jurisdiction)]
+  /// Jurisdiction each tax credit was issued in.
+  pub(super) type TaxCreditJurisdiction<T: Config> = StorageMap<
+    _,
+    Blake2_128Concat,
+    <T as pallet_uniques::Config>::ItemId,
+    Jurisdiction,
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn tax_credit_amount)]
+  /// Credits held by each tax credit, the credits sold from a tax credit are held by its children.
+  pub(super) type TaxCreditAmounts<T: Config> =
+    StorageMap<_, Blake2_128Concat, <T as pallet_uniques::Config>::ItemId, u32, ValueQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn cpa_of)]
+  /// CPA assigned to each seller to attest their tax credits.
+  pub(super) type CpaBySeller<T: Config> = StorageMap<
+    _,
+    Blake2_128Concat,
+    T::AccountId, // Seller
+    T::AccountId, // CPA
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn ramp_requests)]
+  /// Deposit and withdrawal requests of each user, their ledger of the Afloat asset.
+  pub(super) type RampRequests<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    T::AccountId,
+    Blake2_128Concat,
+    StorageId,
+    RampRequest<T>,
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn fee_schedule)]
+  /// Fee charged to the seller when a transaction is settled, no fee is charged without one.
+  pub(super) type AfloatFeeSchedule<T: Config> = StorageValue<_, FeeSchedule<T>, OptionQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn fee_account)]
+  /// Platform account the fees are paid to.
+  pub(super) type AfloatFeeAccount<T: Config> = StorageValue<_, T::AccountId, OptionQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn fee_revenue)]
+  /// Fees collected in each fee period, see `FeePeriod`.
+  pub(super) type FeeRevenue<T: Config> =
+    StorageMap<_, Blake2_128Concat, u64, T::Balance, ValueQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn traded_volume)]
+  /// Settled sales of each seller in each fee period, used to pick the fee tier.
+  pub(super) type TradedVolume<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    u64,
+    Blake2_128Concat,
+    T::AccountId,
+    T::Balance,
+    ValueQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn offers_sweep_cursor)]
+  /// Last offer checked for expiration by `on_idle`, the next sweep starts after it.
+  pub(super) type OffersSweepCursor<T: Config> = StorageValue<_, StorageId, OptionQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn storage_purge_progress)]
+  /// Progress of the storage removal started by `kill_storage`.
+  pub(super) type StoragePurgeProgress<T: Config> = StorageValue<_, PurgeProgress, OptionQuery>;
+
+  #[pallet::hooks]
+  impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+    fn on_idle(_n: T::BlockNumber, remaining_weight: Weight) -> Weight {
+      let (weight, complete) = Self::purge_on_idle(remaining_weight, T::DbWeight::get());
+      if complete {
+        Self::deposit_event(Event::StoragePurged);
+      }
+      // The offers are being removed, there's nothing to expire
+      if <StoragePurgeProgress<T>>::exists() {
+        return weight;
+      }
+      weight.saturating_add(Self::do_expire_offers(remaining_weight.saturating_sub(weight)))
+    }
+  }
+
+  #[pallet::genesis_config]
+  pub struct GenesisConfig<T: Config> {
+    /// Owner of the Afloat marketplace and collection, Afloat is set up at genesis when both the
+    /// owner and the admin are given
+    pub owner: Option<T::AccountId>,
+    pub admin: Option<T::AccountId>,
+    /// Asset used by the marketplace, created with the owner as its admin if it doesn't exist
+    pub asset_id: <T as pallet_mapped_assets::Config>::AssetId,
+    pub asset_name: Vec<u8>,
+    pub asset_symbol: Vec<u8>,
+    pub asset_decimals: u8,
+  }
+
+  #[cfg(feature = "std")]
+  impl<T: Config> Default for GenesisConfig<T> {
+    fn default() -> Self {
+      Self {
+        owner: None,
+        admin: None,
+        asset_id: Default::default(),
+        asset_name: b"Afloat".to_vec(),
+        asset_symbol: b"AFLOAT".to_vec(),
+        asset_decimals: 6,
+      }
+    }
+  }
+
+  #[pallet::genesis_build]
+  impl<T: Config> GenesisBuild<T> for GenesisConfig<T> {
+    fn build(&self) {
+      if let (Some(owner), Some(admin)) = (&self.owner, &self.admin) {
+        Pallet::<T>::do_create_afloat_asset(
+          owner.clone(),
+          self.asset_id,
+          self.asset_name.clone(),
+          self.asset_symbol.clone(),
+          self.asset_decimals,
+        )
+        .expect("Afloat genesis asset creation failed");
+        Pallet::<T>::do_initial_setup(owner.clone(), admin.clone(), self.asset_id)
+          .expect("Afloat genesis setup failed");
+      }
+    }
+  }
+
   #[pallet::call]
   impl<T: Config> Pallet<T>
   where
     T: pallet_uniques::Config<CollectionId = CollectionId>,
     <T as pallet_uniques::Config>::ItemId: From<u32>,
   {
+    /// Sets up Afloat on chains where it wasn't set up at genesis, see `GenesisConfig`.
     #[pallet::call_index(0)]
-    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,1))]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(2,10))]
     pub fn initial_setup(
       origin: OriginFor<T>,
-      creator: T::AccountId,
+      owner: T::AccountId,
       admin: T::AccountId,
+      asset_id: <T as pallet_mapped_assets::Config>::AssetId,
+      asset_name: Vec<u8>,
+      asset_symbol: Vec<u8>,
+      asset_decimals: u8,
     ) -> DispatchResult {
-      ensure_signed(origin.clone())?;
-      let asset_id: T::AssetId = Default::default();
-      AfloatAssetId::<T>::put(asset_id.clone());
-
-      let metadata: CollectionDescription<T> =
-        BoundedVec::try_from(b"Afloat".to_vec()).expect("Label too long");
-
-      pallet_fruniques::Pallet::<T>::do_initial_setup()?;
-
-      Self::create_afloat_collection(
-        RawOrigin::Signed(creator.clone()).into(),
-        metadata,
-        admin.clone(),
-      )?;
+      ensure_root(origin)?;
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      ensure!(!<AfloatMarketPlaceId<T>>::exists(), Error::<T>::AlreadyInitialized);
 
-      pallet_gated_marketplace::Pallet::<T>::do_initial_setup()?;
-
-      let label: BoundedVec<u8, T::LabelMaxLen> =
-        BoundedVec::try_from(b"Afloat".to_vec()).expect("Label too long");
-      let marketplace: Marketplace<T> = Marketplace {
-        label,
-        buy_fee: Permill::from_percent(2),
-        sell_fee: Permill::from_percent(4),
+      Self::do_create_afloat_asset(
+        owner.clone(),
         asset_id,
-        creator: creator.clone(),
-      };
-      let marketplace_id = marketplace.clone().using_encoded(blake2_256);
-
-      AfloatMarketPlaceId::<T>::put(marketplace_id);
-      Self::add_to_afloat_collection(admin.clone(), FruniqueRole::Admin)?;
-      pallet_gated_marketplace::Pallet::do_create_marketplace(
-        RawOrigin::Signed(creator.clone()).into(),
-        admin.clone(),
-        marketplace,
+        asset_name,
+        asset_symbol,
+        asset_decimals,
       )?;
-
-      Self::do_initial_setup(creator, admin)?;
-
-      Ok(())
+      Self::do_initial_setup(owner, admin, asset_id)
     }
 
     #[pallet::call_index(1)]
-    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,1))]
+    #[pallet::weight(
+      Weight::from_ref_time(10_000)
+        + T::DbWeight::get().reads_writes(3, 6 + u64::from(purge::KEYS_PER_CALL))
+    )]
     pub fn kill_storage(origin: OriginFor<T>) -> DispatchResult {
       let who = ensure_signed(origin.clone())?;
+      // Starting over would drop the progress of the purge in progress
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       // Self::do_delete_all_users()?;
+      ensure!(
+        <AfloatMarketPlaceId<T>>::exists() && Self::is_admin_or_owner(who),
+        Error::<T>::Unauthorized
+      );
 
-      let _ = <UserInfo<T>>::clear(1000, None);
       let _ = <AfloatMarketPlaceId<T>>::kill();
       let _ = <AfloatCollectionId<T>>::kill();
-      let _ = <AfloatOffers<T>>::clear(1000, None);
-      let _ = <AfloatTransactions<T>>::clear(1000, None);
+      let _ = <OffersSweepCursor<T>>::kill();
+      let _ = <AfloatFeeSchedule<T>>::kill();
+      let _ = <AfloatFeeAccount<T>>::kill();
 
       <T as Config>::Rbac::remove_pallet_storage(Self::pallet_id())?;
 
+      // The maps are cleared in multiple blocks, see `on_idle`
+      if Self::start_purge(purge::KEYS_PER_CALL).complete {
+        Self::deposit_event(Event::StoragePurged);
+      }
+
       Ok(())
     }
 
     #[pallet::call_index(2)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,1))]
-    pub fn sign_up(origin: OriginFor<T>, args: SignUpArgs) -> DispatchResult {
+    pub fn sign_up(origin: OriginFor<T>, args: SignUpArgs<T>) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
       Self::do_create_user(who.clone(), who, args)
     }
@@ -222,6 +586,7 @@ pub mod pallet {
       address: T::AccountId,
       args: UpdateUserArgs,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       ensure!(<UserInfo<T>>::contains_key(address.clone()), Error::<T>::UserNotFound);
@@ -247,9 +612,16 @@ pub mod pallet {
       Ok(())
     }
 
+    // Every offer in the order book can be matched, creating a transaction, and the credits a
+    // seller has on sale are read from its transactions
     #[pallet::call_index(4)]
-    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,1))]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(
+      5 + 7 * T::MaxOffersPerTaxCredit::get() as u64
+        + 2 * T::MaxTransactionsPerTaxCredit::get() as u64,
+      5 + 12 * T::MaxOffersPerTaxCredit::get() as u64,
+    ))]
     pub fn create_offer(origin: OriginFor<T>, args: CreateOfferArgs<T>) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
       match args {
         CreateOfferArgs::Sell {
@@ -284,56 +656,223 @@ pub mod pallet {
       Ok(())
     }
 
+    // Writes the offer, its order book and status index, the transaction, its four indexes and
+    // their counts
     #[pallet::call_index(5)]
-    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,1))]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(15, 12))]
     pub fn accept_offer(
       origin: OriginFor<T>,
       offer_id: [u8; 32],
-      amount: Option<u64>,
+      amount: Option<u32>,
     ) -> DispatchResult {
-      ensure_signed(origin.clone())?;
-      Ok(())
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      Self::do_accept_offer(who, offer_id, amount)
     }
 
+    // Same storage as `accept_offer`, reading the offer once more
     #[pallet::call_index(6)]
-    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,1))]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(16, 12))]
     pub fn take_sell_order(origin: OriginFor<T>, offer_id: [u8; 32]) -> DispatchResult {
-      ensure_signed(origin.clone())?;
-      Self::do_take_sell_order(origin, offer_id)
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      Self::do_take_sell_order(who, offer_id)
     }
 
     #[pallet::call_index(7)]
-    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,1))]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(16, 12))]
     pub fn take_buy_order(origin: OriginFor<T>, offer_id: [u8; 32]) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
       Self::do_take_buy_order(who, offer_id)
     }
 
     #[pallet::call_index(8)]
-    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(2,1))]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(2,2))]
     pub fn create_tax_credit(
       origin: OriginFor<T>,
       metadata: CollectionDescription<T>,
       attributes: Option<Attributes<T>>,
       parent_info: Option<ParentInfo<T>>,
+      jurisdiction: Jurisdiction,
+      tax_credit_amount: u32,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      Self::do_create_tax_credit(
+        who,
+        metadata,
+        attributes,
+        parent_info,
+        jurisdiction,
+        tax_credit_amount,
+      )
+    }
+
+    #[pallet::call_index(10)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,1))]
+    pub fn fill_transfer_form(
+      origin: OriginFor<T>,
+      transaction_id: StorageId,
+      cid: ShortString,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      Self::do_fill_transfer_form(who, transaction_id, cid)
+    }
+
+    #[pallet::call_index(11)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,1))]
+    pub fn sign_transfer_form(
+      origin: OriginFor<T>,
+      transaction_id: StorageId,
+      cid: ShortString,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
-      Self::do_create_tax_credit(who, metadata, attributes, parent_info)
+      Self::do_sign_transfer_form(who, transaction_id, cid)
     }
 
-    #[pallet::call_index(9)]
+    #[pallet::call_index(12)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,1))]
+    pub fn cpa_update_transfer_form(
+      origin: OriginFor<T>,
+      transaction_id: StorageId,
+      args: CpaTransferFormArgs,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      Self::do_cpa_update_transfer_form(who, transaction_id, args)
+    }
+
+    // The worst case settles part of a tax credit: the seller enlists a marketplace offer that
+    // the buyer takes, spawning the child tax credit, the price is paid in two asset transfers
+    // and the Afloat fee in a third one
+    #[pallet::call_index(13)]
+    #[pallet::weight(
+      Weight::from_ref_time(10_000)
+        // The transaction, its status index, `TaxCreditAmounts`, `TaxCreditJurisdiction`,
+        // `FeeRevenue` and `TradedVolume`
+        + T::DbWeight::get().reads_writes(11, 9)
+        // Enlisting and taking the marketplace offer, spawning the child
+        + T::DbWeight::get().reads_writes(27, 24)
+        + T::DbWeight::get().reads_writes(3, 3).saturating_mul(3)
+    )]
+    pub fn approve_transaction(origin: OriginFor<T>, transaction_id: StorageId) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      ensure!(Self::is_admin_or_owner(who.clone()), Error::<T>::Unauthorized);
+      Self::do_approve_transaction(who, transaction_id)
+    }
+
+    #[pallet::call_index(14)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,1))]
+    pub fn cancel_offer(origin: OriginFor<T>, offer_id: StorageId) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      Self::do_cancel_offer(who, offer_id)
+    }
+
+    #[pallet::call_index(15)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(2,1))]
-    pub fn set_afloat_balance(
+    pub fn assign_cpa(
+      origin: OriginFor<T>,
+      seller: T::AccountId,
+      cpa: T::AccountId,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      ensure!(Self::is_admin_or_owner(who), Error::<T>::Unauthorized);
+      Self::do_assign_cpa(seller, cpa)
+    }
+
+    #[pallet::call_index(16)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(3,3))]
+    pub fn attest_tax_credit(
+      origin: OriginFor<T>,
+      tax_credit_id: <T as pallet_uniques::Config>::ItemId,
+      evidence: Option<CID>,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      Self::do_attest_tax_credit(who, tax_credit_id, evidence)
+    }
+
+    #[pallet::call_index(17)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(2,1))]
+    pub fn request_deposit(
+      origin: OriginFor<T>,
+      amount: T::Balance,
+      bank_reference_cid: ShortString,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      Self::do_create_ramp_request(who, RampRequestType::Deposit, amount, bank_reference_cid)
+    }
+
+    #[pallet::call_index(18)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(3,1))]
+    pub fn request_withdrawal(
       origin: OriginFor<T>,
-      beneficiary: T::AccountId,
       amount: T::Balance,
+      bank_reference_cid: ShortString,
     ) -> DispatchResult {
-      ensure_signed(origin.clone())?;
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      Self::do_create_ramp_request(who, RampRequestType::Withdrawal, amount, bank_reference_cid)
+    }
 
-      // Only the owner can set afloat balance
-      ensure!(Self::is_owner(ensure_signed(origin.clone())?), Error::<T>::Unauthorized);
+    #[pallet::call_index(19)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(3,2))]
+    pub fn confirm_ramp_request(
+      origin: OriginFor<T>,
+      user: T::AccountId,
+      request_id: StorageId,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      ensure!(Self::is_admin_or_owner(who.clone()), Error::<T>::Unauthorized);
+      Self::do_review_ramp_request(who, user, request_id, true)
+    }
 
-      Self::do_set_afloat_balance(origin, beneficiary, amount)
+    #[pallet::call_index(20)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(2,1))]
+    pub fn reject_ramp_request(
+      origin: OriginFor<T>,
+      user: T::AccountId,
+      request_id: StorageId,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      ensure!(Self::is_admin_or_owner(who.clone()), Error::<T>::Unauthorized);
+      Self::do_review_ramp_request(who, user, request_id, false)
+    }
+
+    #[pallet::call_index(21)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(4,5))]
+    pub fn review_sign_up(
+      origin: OriginFor<T>,
+      user: T::AccountId,
+      approved: bool,
+      feedback: BoundedVec<u8, T::MaxFeedbackLen>,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      ensure!(Self::is_admin_or_owner(who.clone()), Error::<T>::Unauthorized);
+      Self::do_review_sign_up(who, user, approved, feedback)
+    }
+
+    #[pallet::call_index(22)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().reads_writes(1,2))]
+    pub fn set_fee_schedule(
+      origin: OriginFor<T>,
+      schedule: FeeSchedule<T>,
+      fee_account: T::AccountId,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+      ensure!(Self::is_owner(who.clone()), Error::<T>::Unauthorized);
+      Self::do_set_fee_schedule(who, schedule, fee_account)
     }
   }
 }
@@ -0,0 +1,295 @@
+//! Storage migrations of the afloat pallet.
+use super::*;
+
+const LOG_TARGET: &str = "\nAfloat pallet migration ";
+use crate::types::*;
+use frame_support::{
+  log,
+  pallet_prelude::*,
+  storage_alias,
+  traits::{tokens::nonfungibles::InspectEnumerable, OnRuntimeUpgrade, UnixTime},
+};
+use sp_runtime::sp_std::vec::Vec;
+use sp_runtime::Saturating;
+
+mod v1 {
+  use super::*;
+
+  #[derive(Decode, Encode)]
+  pub struct OldUser<T: Config> {
+    pub cid: ShortString,
+    pub cid_creator: ShortString,
+    pub group: ShortString,
+    pub created_by: Option<T::AccountId>,
+    pub created_date: Option<Date>,
+    pub last_modified_by: Option<T::AccountId>,
+    pub last_modified_date: Option<Date>,
+  }
+
+  #[storage_alias]
+  pub(super) type UserInfo<T: Config> =
+    StorageMap<Pallet<T>, Blake2_128Concat, <T as frame_system::Config>::AccountId, OldUser<T>>;
+
+  #[derive(Decode, Encode)]
+  pub struct OldTransaction<T: Config> {
+    pub tax_credit_amount: u32,
+    pub price_per_credit: u32,
+    pub total_price: u32,
+    pub fee: u32,
+    pub creation_date: Date,
+    pub cancellation_date: Option<Date>,
+    pub tax_credit_id: u32,
+    pub seller_id: T::AccountId,
+    pub buyer_id: T::AccountId,
+    pub offer_id: StorageId,
+    pub marketplace_offer_id: StorageId,
+    pub seller_confirmation_date: Option<Date>,
+    pub buyer_confirmation_date: Option<Date>,
+  }
+
+  #[storage_alias]
+  pub(super) type AfloatTransactions<T: Config> =
+    StorageMap<Pallet<T>, Blake2_128Concat, StorageId, OldTransaction<T>>;
+
+  #[storage_alias]
+  pub(super) type AfloatOffers<T: Config> =
+    StorageMap<Pallet<T>, Blake2_128Concat, StorageId, Offer<T>>;
+}
+
+pub mod v2 {
+  pub use super::v1::{OldTransaction, OldUser};
+  use super::*;
+
+  impl<T: Config> OldUser<T> {
+    // The free text groups can't be mapped to a jurisdiction, users start with federal tax
+    // credits only until an admin sets their jurisdiction
+    fn migrate_to_v2_user(self) -> User<T> {
+      User {
+        cid: self.cid,
+        cid_creator: self.cid_creator,
+        group: Jurisdiction::Federal,
+        created_by: self.created_by,
+        created_date: self.created_date,
+        last_modified_by: self.last_modified_by,
+        last_modified_date: self.last_modified_date,
+      }
+    }
+  }
+
+  impl<T: Config> OldTransaction<T>
+  where
+    <T as pallet_uniques::Config>::ItemId: From<u32>,
+  {
+    // Transactions of v1 were executed in the marketplace as soon as they were created
+    fn migrate_to_v2_transaction(self) -> Transaction<T> {
+      Transaction {
+        tax_credit_amount: self.tax_credit_amount,
+        price_per_credit: self.price_per_credit.into(),
+        total_price: self.total_price.into(),
+        fee: self.fee.into(),
+        creation_date: self.creation_date,
+        cancellation_date: self.cancellation_date,
+        tax_credit_id: self.tax_credit_id.into(),
+        seller_id: self.seller_id,
+        buyer_id: self.buyer_id,
+        offer_id: self.offer_id,
+        matched_offer_id: None,
+        marketplace_offer_id: Some(self.marketplace_offer_id),
+        seller_confirmation_date: self.seller_confirmation_date,
+        buyer_confirmation_date: self.buyer_confirmation_date,
+        status: OfferStatus::AFLOAT_APPROVED,
+        documents: TransferFormDocuments::default(),
+      }
+    }
+  }
+
+  /// Migrates the users to jurisdictions and the transactions to the transfer form workflow, and
+  /// fills the storage added in v2: the jurisdiction and credits of the tax credits, the order
+  /// books and the offer and transaction indexes. It also sets the storage version of runtimes
+  /// where the pallet is added in this upgrade, where there is nothing to migrate.
+  pub struct MigrateToV2<T>(sp_runtime::sp_std::marker::PhantomData<T>);
+  impl<T: Config> OnRuntimeUpgrade for MigrateToV2<T>
+  where
+    <T as pallet_uniques::Config>::ItemId: From<u32>,
+  {
+    fn on_runtime_upgrade() -> Weight {
+      let onchain_version = Pallet::<T>::on_chain_storage_version();
+      let current_version = Pallet::<T>::current_storage_version();
+
+      log::info!(
+        target: LOG_TARGET,
+        "Running migration with current storage version: {:?} / onchain version: {:?}",
+        current_version,
+        onchain_version
+      );
+
+      if onchain_version < 2 && current_version == 2 {
+        let mut translated_users = 0u64;
+
+        UserInfo::<T>::translate::<OldUser<T>, _>(|_key: T::AccountId, value: OldUser<T>| {
+          translated_users.saturating_inc();
+          Some(value.migrate_to_v2_user())
+        });
+
+        // The layout of the offers didn't change, translating them checks they still decode.
+        // Their indexes are built and the credits each tax credit holds are taken from its
+        // largest offer, v1 didn't store them.
+        let now = T::TimeProvider::now().as_secs();
+        let mut open_offers = Vec::new();
+        let mut translated_offers = 0u64;
+        AfloatOffers::<T>::translate::<Offer<T>, _>(|offer_id: StorageId, offer: Offer<T>| {
+          translated_offers.saturating_inc();
+          OfferIdsByCreator::<T>::insert(&offer.creator_id, offer_id, ());
+          OfferIdsByTaxCredit::<T>::insert(offer.tax_credit_id, offer_id, ());
+          OfferIdsByStatus::<T>::insert(offer.status, offer_id, ());
+          OfferCountByCreator::<T>::mutate(&offer.creator_id, |count| count.saturating_inc());
+          OfferCountByTaxCredit::<T>::mutate(offer.tax_credit_id, |count| count.saturating_inc());
+          TaxCreditAmounts::<T>::mutate(offer.tax_credit_id, |credits| {
+            *credits = (*credits).max(offer.tax_credit_amount)
+          });
+          if offer.is_open() && !offer.is_expired(now) {
+            open_offers.push((offer.creation_date, offer_id, offer.tax_credit_id));
+          }
+          Some(offer)
+        });
+
+        // The oldest offers go first in the order book
+        open_offers.sort_by_key(|(creation_date, _, _)| *creation_date);
+        for (_, offer_id, tax_credit_id) in open_offers {
+          if OrderBook::<T>::try_append(tax_credit_id, offer_id).is_err() {
+            log::warn!(target: LOG_TARGET, "Order book full, offer {:?} not added", offer_id);
+          }
+        }
+
+        let mut translated_transactions = 0u64;
+        AfloatTransactions::<T>::translate::<OldTransaction<T>, _>(
+          |transaction_id: StorageId, value: OldTransaction<T>| {
+            translated_transactions.saturating_inc();
+            let transaction = value.migrate_to_v2_transaction();
+            TransactionIdsByUser::<T>::insert(&transaction.seller_id, transaction_id, ());
+            TransactionIdsByUser::<T>::insert(&transaction.buyer_id, transaction_id, ());
+            TransactionIdsByTaxCredit::<T>::insert(transaction.tax_credit_id, transaction_id, ());
+            TransactionIdsByStatus::<T>::insert(transaction.status, transaction_id, ());
+            for party in [&transaction.seller_id, &transaction.buyer_id] {
+              TransactionCountByUser::<T>::mutate(party, |count| count.saturating_inc());
+            }
+            TransactionCountByTaxCredit::<T>::mutate(transaction.tax_credit_id, |count| {
+              count.saturating_inc()
+            });
+            Some(transaction)
+          },
+        );
+
+        // The tax credits of v1 had no jurisdiction, they are federal like their users
+        let mut migrated_tax_credits = 0u64;
+        let mut tax_credits_without_credits = 0u64;
+        if let Some(collection_id) = AfloatCollectionId::<T>::get() {
+          for tax_credit_id in pallet_uniques::Pallet::<T>::items(&collection_id) {
+            migrated_tax_credits.saturating_inc();
+            if !TaxCreditJurisdiction::<T>::contains_key(tax_credit_id) {
+              TaxCreditJurisdiction::<T>::insert(tax_credit_id, Jurisdiction::Federal);
+            }
+            if TaxCreditAmounts::<T>::get(tax_credit_id) == 0 {
+              tax_credits_without_credits.saturating_inc();
+            }
+          }
+
+          let policy_set = pallet_uniques::Pallet::<T>::collection_owner(collection_id)
+            .map(|owner| Pallet::<T>::set_afloat_verification_policy(owner).is_ok())
+            .unwrap_or(false);
+          if !policy_set {
+            log::warn!(target: LOG_TARGET, "The verification policy of Afloat wasn't set");
+          }
+        }
+        if tax_credits_without_credits > 0 {
+          log::warn!(
+            target: LOG_TARGET,
+            "{} tax credits were never offered, they hold no credits",
+            tax_credits_without_credits
+          );
+        }
+
+        current_version.put::<Pallet<T>>();
+
+        log::info!(
+          target: LOG_TARGET,
+          "Upgraded {} users, {} offers, {} transactions and {} tax credits, storage to version {:?}",
+          translated_users,
+          translated_offers,
+          translated_transactions,
+          migrated_tax_credits,
+          current_version
+        );
+
+        // Offers are written with their 3 indexes and 2 counts, their credits and their order
+        // book, and transactions with their 4 indexes and 3 counts
+        let reads = translated_users
+          + translated_offers * 5
+          + translated_transactions * 4
+          + migrated_tax_credits * 2
+          + 3;
+        let writes = translated_users
+          + translated_offers * 8
+          + translated_transactions * 8
+          + migrated_tax_credits
+          + 2;
+        T::DbWeight::get().reads_writes(reads, writes)
+      } else {
+        log::info!(
+          target: LOG_TARGET,
+          "Migration did not execute. This probably should be removed"
+        );
+        T::DbWeight::get().reads(1)
+      }
+    }
+
+    #[cfg(feature = "try-runtime")]
+    fn pre_upgrade() -> Result<Vec<u8>, &'static str> {
+      ensure!(Pallet::<T>::on_chain_storage_version() < 2, "must upgrade linearly");
+      ensure!(Pallet::<T>::current_storage_version() == 2, "migration to version 2");
+
+      let keys_users = v1::UserInfo::<T>::iter_keys().count() as u32;
+      let decodable_users = v1::UserInfo::<T>::iter_values().count() as u32;
+      ensure!(keys_users == decodable_users, "Not all user values are decodable.");
+
+      let keys_offers = v1::AfloatOffers::<T>::iter_keys().count() as u32;
+      let decodable_offers = v1::AfloatOffers::<T>::iter_values().count() as u32;
+      ensure!(keys_offers == decodable_offers, "Not all offer values are decodable.");
+
+      let keys_transactions = v1::AfloatTransactions::<T>::iter_keys().count() as u32;
+      let decodable_transactions = v1::AfloatTransactions::<T>::iter_values().count() as u32;
+      ensure!(
+        keys_transactions == decodable_transactions,
+        "Not all transaction values are decodable."
+      );
+
+      Ok((keys_users, keys_offers, keys_transactions).encode())
+    }
+
+    #[cfg(feature = "try-runtime")]
+    fn post_upgrade(prev_count: Vec<u8>) -> Result<(), &'static str> {
+      let (prev_users, prev_offers, prev_transactions): (u32, u32, u32) =
+        Decode::decode(&mut prev_count.as_slice()).expect("the state parameter should be counts");
+      let post_users = UserInfo::<T>::iter().count() as u32;
+      ensure!(prev_users == post_users, "the user count before and after the migration differs");
+      let post_offers = AfloatOffers::<T>::iter().count() as u32;
+      ensure!(prev_offers == post_offers, "the offer count before and after the migration differs");
+      let post_transactions = AfloatTransactions::<T>::iter().count() as u32;
+      ensure!(
+        prev_transactions == post_transactions,
+        "the transaction count before and after the migration differs"
+      );
+      if let Some(collection_id) = AfloatCollectionId::<T>::get() {
+        ensure!(
+          pallet_uniques::Pallet::<T>::items(&collection_id)
+            .all(|tax_credit_id| TaxCreditJurisdiction::<T>::contains_key(tax_credit_id)),
+          "a tax credit has no jurisdiction"
+        );
+      }
+      let indexed_offers = OfferIdsByStatus::<T>::iter_keys().count() as u32;
+      ensure!(prev_offers == indexed_offers, "not every offer is indexed");
+      ensure!(Pallet::<T>::on_chain_storage_version() == 2, "wrong storage version");
+      Ok(())
+    }
+  }
+}
@@ -1,7 +1,7 @@
 use crate as pallet_afloat;
 use frame_support::{
   parameter_types,
-  traits::{AsEnsureOriginWithArg, ConstU32, ConstU64, Currency},
+  traits::{AsEnsureOriginWithArg, ConstU32, ConstU64, GenesisBuild},
 };
 use frame_system as system;
 use sp_core::H256;
@@ -14,8 +14,6 @@ type Block = frame_system::mocking::MockBlock<Test>;
 use frame_system::EnsureRoot;
 use system::EnsureSigned;
 
-use frame_system::RawOrigin;
-
 type AssetId = u32;
 
 parameter_types! {
@@ -38,7 +36,7 @@ frame_support::construct_runtime!(
     Timestamp: pallet_timestamp::{Pallet, Call, Storage, Inherent},
     RBAC: pallet_rbac::{Pallet, Call, Storage, Event<T>},
     Assets: pallet_mapped_assets::{Pallet, Call, Storage, Event<T>},
-    Afloat: pallet_afloat::{Pallet, Call, Storage, Event<T>},
+    Afloat: pallet_afloat::{Pallet, Call, Storage, Config<T>, Event<T>},
   }
 );
 
@@ -76,13 +74,19 @@ impl pallet_afloat::Config for Test {
   type Currency = pallet_balances::Pallet<Self>;
   type Rbac = RBAC;
   type ItemId = u32;
+  type MaxOffersPerTaxCredit = ConstU32<100>;
+  type MaxOffersPerCreator = ConstU32<10>;
+  type MaxOfferHistoryPerTaxCredit = ConstU32<20>;
+  type MaxTransactionsPerUser = ConstU32<10>;
+  type MaxTransactionsPerTaxCredit = ConstU32<20>;
+  type FeePeriod = ConstU64<2_592_000>;
 }
 
 parameter_types! {
   pub const LabelMaxLen: u32 = 32;
   pub const MaxAuthsPerMarket: u32 = 3;
   pub const MaxRolesPerAuth : u32 = 1;
-  pub const MaxApplicants: u32 = 3;
+  pub const MaxApplicants: u32 = 10;
   pub const MaxBlockedUsersPerMarket: u32 = 100;
   pub const NotesMaxLen: u32 = 256;
   pub const MaxFeedbackLen: u32 = 256;
@@ -115,6 +119,12 @@ impl pallet_gated_marketplace::Config for Test {
 parameter_types! {
   pub const ChildMaxLen: u32 = 10;
   pub const MaxParentsInCollection: u32 = 10;
+  pub const MaxVerifiers: u32 = 3;
+  pub const MaxSchemaFields: u32 = 10;
+  pub const MaxHistoryLength: u32 = 10;
+  pub const MaxBatchSpawn: u32 = 5;
+  pub const MaxThawsPerBlock: u32 = 3;
+  pub const InvitationExpiration: u64 = 10;
 }
 
 impl pallet_fruniques::Config for Test {
@@ -123,6 +133,12 @@ impl pallet_fruniques::Config for Test {
   type ChildMaxLen = ChildMaxLen;
   type MaxParentsInCollection = MaxParentsInCollection;
   type Rbac = RBAC;
+  type MaxVerifiers = MaxVerifiers;
+  type MaxSchemaFields = MaxSchemaFields;
+  type MaxHistoryLength = MaxHistoryLength;
+  type MaxBatchSpawn = MaxBatchSpawn;
+  type MaxThawsPerBlock = MaxThawsPerBlock;
+  type InvitationExpiration = InvitationExpiration;
 }
 
 parameter_types! {
@@ -176,12 +192,12 @@ impl pallet_balances::Config for Test {
 
 parameter_types! {
   pub const MaxScopesPerPallet: u32 = 2;
-  pub const MaxRolesPerPallet: u32 = 6;
+  pub const MaxRolesPerPallet: u32 = 7;
   pub const RoleMaxLen: u32 = 25;
   pub const PermissionMaxLen: u32 = 25;
   pub const MaxPermissionsPerRole: u32 = 30;
   pub const MaxRolesPerUser: u32 = 2;
-  pub const MaxUsersPerRole: u32 = 2;
+  pub const MaxUsersPerRole: u32 = 10;
 }
 impl pallet_rbac::Config for Test {
   type RuntimeEvent = RuntimeEvent;
@@ -241,14 +257,24 @@ impl pallet_mapped_assets::Config for Test {
 
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
+  build_test_ext(true)
+}
+
+// Afloat is left to be set up through `initial_setup`
+pub fn new_test_ext_without_setup() -> sp_io::TestExternalities {
+  build_test_ext(false)
+}
+
+fn build_test_ext(setup: bool) -> sp_io::TestExternalities {
   // TODO: get initial conf?
-  let mut t: sp_io::TestExternalities =
-    frame_system::GenesisConfig::default().build_storage::<Test>().unwrap().into();
-  t.execute_with(|| {
-    Balances::make_free_balance_be(&1, 100);
-    Balances::make_free_balance_be(&2, 100);
-    Afloat::initial_setup(RawOrigin::Signed(1).into(), 1, 2)
-      .expect("Error on GatedMarketplace configuring initial setup");
-  });
-  t
+  let mut storage = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+  pallet_balances::GenesisConfig::<Test> { balances: vec![(1, 100), (2, 100)] }
+    .assimilate_storage(&mut storage)
+    .unwrap();
+  if setup {
+    pallet_afloat::GenesisConfig::<Test> { owner: Some(1), admin: Some(2), ..Default::default() }
+      .assimilate_storage(&mut storage)
+      .unwrap();
+  }
+  storage.into()
 }
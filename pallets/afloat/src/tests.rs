@@ -1,7 +1,15 @@
 use super::*;
 use crate::{mock::*, types::*, Error};
-use frame_support::{assert_noop, assert_ok, traits::Currency, BoundedVec};
+use frame_support::{
+  assert_noop, assert_ok,
+  traits::{Currency, Hooks},
+  weights::Weight,
+  BoundedVec,
+};
 use frame_system::RawOrigin;
+use pallet_gated_marketplace::types::{Cid, FieldName, Fields};
+use pallet_rbac::{purge::StoragePurge, types::RoleBasedAccessControl};
+use sp_runtime::{DispatchError, Permill};
 
 fn new_account(account_id: u64) -> <Test as frame_system::Config>::AccountId {
   account_id
@@ -11,53 +19,313 @@ fn dummy_description() -> BoundedVec<u8, StringLimit> {
   BoundedVec::<u8, StringLimit>::try_from(b"dummy description".to_vec()).unwrap()
 }
 
+fn short_string(value: &[u8]) -> ShortString {
+  ShortString::try_from(value.to_vec()).unwrap()
+}
+
+fn bank_reference() -> ShortString {
+  short_string(b"bank-reference")
+}
+
+fn kyc_fields() -> Fields<Test> {
+  Fields::<Test>::try_from(vec![(
+    FieldName::try_from(b"ID".to_vec()).unwrap(),
+    Cid::try_from(b"QmKycDocument".to_vec()).unwrap(),
+  )])
+  .unwrap()
+}
+
+fn feedback() -> BoundedVec<u8, MaxFeedbackLen> {
+  BoundedVec::<u8, MaxFeedbackLen>::try_from(b"feedback".to_vec()).unwrap()
+}
+
+fn roles_of(user: u64) -> Vec<[u8; 32]> {
+  RBAC::get_roles_by_user(user, Afloat::pallet_id(), &Afloat::marketplace_id().unwrap())
+}
+
+fn sign_up_args(group: Jurisdiction) -> SignUpArgs<Test> {
+  SignUpArgs::BuyerOrSeller {
+    cid: short_string(b"QmUserInfo"),
+    cid_creator: short_string(b"QmUserInfoCreator"),
+    group,
+    fields: kyc_fields(),
+  }
+}
+
+// Signs up a buyer or seller and has the admin approve its KYC application
+fn register(user: u64, group: Jurisdiction) {
+  Balances::make_free_balance_be(&user, 100);
+  assert_ok!(Afloat::sign_up(RawOrigin::Signed(user).into(), sign_up_args(group)));
+  assert_ok!(Afloat::review_sign_up(RawOrigin::Signed(2).into(), user, true, feedback()));
+}
+
+// Signs up a CPA and has the admin approve its KYC application
+fn register_cpa(user: u64) {
+  Balances::make_free_balance_be(&user, 100);
+  let args = SignUpArgs::CPA {
+    cid: short_string(b"QmCpaInfo"),
+    cid_creator: short_string(b"QmCpaInfoCreator"),
+    group: Jurisdiction::Federal,
+    fields: kyc_fields(),
+  };
+  assert_ok!(Afloat::sign_up(RawOrigin::Signed(user).into(), args));
+  assert_ok!(Afloat::review_sign_up(RawOrigin::Signed(2).into(), user, true, feedback()));
+}
+
+// Creates a federal tax credit with `credits` owned by `owner` and returns its id
+fn create_tax_credit(owner: u64, credits: u32) -> u32 {
+  let tax_credit_id = Fruniques::next_frunique(Afloat::collection_id().unwrap());
+  assert_ok!(Afloat::create_tax_credit(
+    RawOrigin::Signed(owner).into(),
+    dummy_description(),
+    None,
+    None,
+    Jurisdiction::Federal,
+    credits,
+  ));
+  tax_credit_id
+}
+
+// The CPA assigned to the owner of the tax credit attests it
+fn attest(cpa: u64, seller: u64, tax_credit_id: u32) {
+  assert_ok!(Afloat::assign_cpa(RawOrigin::Signed(2).into(), seller, cpa));
+  assert_ok!(Afloat::attest_tax_credit(RawOrigin::Signed(cpa).into(), tax_credit_id, None));
+}
+
+fn sell_args(tax_credit_id: u32, price_per_credit: u64, amount: u32) -> CreateOfferArgs<Test> {
+  CreateOfferArgs::Sell {
+    tax_credit_amount: amount,
+    price_per_credit,
+    tax_credit_id,
+    expiration_date: 1_000,
+  }
+}
+
+fn buy_args(tax_credit_id: u32, price_per_credit: u64, amount: u32) -> CreateOfferArgs<Test> {
+  CreateOfferArgs::Buy {
+    tax_credit_amount: amount,
+    price_per_credit,
+    tax_credit_id,
+    expiration_date: 1_000,
+  }
+}
+
+fn offer_ids_of(creator: u64) -> Vec<StorageId> {
+  Afloat::get_offers_by_creator(creator, None, MAX_PAGE_SIZE)
+    .into_iter()
+    .map(|(offer_id, _)| offer_id)
+    .collect()
+}
+
+fn transaction_ids_of(user: u64) -> Vec<StorageId> {
+  Afloat::get_transactions_by_user(user, None, MAX_PAGE_SIZE)
+    .into_iter()
+    .map(|(transaction_id, _)| transaction_id)
+    .collect()
+}
+
+// Creates an offer and returns its id
+fn create_offer(creator: u64, args: CreateOfferArgs<Test>) -> StorageId {
+  let previous_offers = offer_ids_of(creator);
+  assert_ok!(Afloat::create_offer(RawOrigin::Signed(creator).into(), args));
+  offer_ids_of(creator)
+    .into_iter()
+    .find(|offer_id| !previous_offers.contains(offer_id))
+    .unwrap()
+}
+
+// Accepts an offer and returns the id of the new transaction
+fn accept_offer(user: u64, offer_id: StorageId, amount: Option<u32>) -> StorageId {
+  let previous_transactions = transaction_ids_of(user);
+  assert_ok!(Afloat::accept_offer(RawOrigin::Signed(user).into(), offer_id, amount));
+  transaction_ids_of(user)
+    .into_iter()
+    .find(|transaction_id| !previous_transactions.contains(transaction_id))
+    .unwrap()
+}
+
+// Seller 3 with an attested federal tax credit of 10 credits, buyer 4 and CPA 5
+fn setup_trade() -> u32 {
+  register(3, Jurisdiction::Federal);
+  register(4, Jurisdiction::Federal);
+  register_cpa(5);
+  let tax_credit_id = create_tax_credit(3, 10);
+  attest(5, 3, tax_credit_id);
+  tax_credit_id
+}
+
+fn pending_request(user: u64) -> StorageId {
+  Afloat::get_ramp_ledger(user)
+    .into_iter()
+    .find(|(_, request)| request.status == RampRequestStatus::Pending)
+    .map(|(request_id, _)| request_id)
+    .unwrap()
+}
+
+// Mints the Afloat asset to a user through a confirmed deposit
+fn deposit(user: u64, amount: u64) {
+  assert_ok!(Afloat::request_deposit(RawOrigin::Signed(user).into(), amount, bank_reference()));
+  let request_id = pending_request(user);
+  assert_ok!(Afloat::confirm_ramp_request(RawOrigin::Signed(1).into(), user, request_id));
+}
+
 //owner_id = 1
 //admin_id = 2
-//buy_fee = 2%
-//sell_fee = 4%
+//fee = 4%, paid by the seller to the owner
+
+#[test]
+fn initial_setup_works() {
+  new_test_ext_without_setup().execute_with(|| {
+    assert_eq!(Afloat::marketplace_id(), None);
+    assert_noop!(
+      Afloat::initial_setup(
+        RawOrigin::Signed(1).into(),
+        1,
+        2,
+        0,
+        b"Afloat".to_vec(),
+        b"AFLOAT".to_vec(),
+        6
+      ),
+      DispatchError::BadOrigin
+    );
+
+    assert_ok!(Afloat::initial_setup(
+      RawOrigin::Root.into(),
+      1,
+      2,
+      0,
+      b"Afloat".to_vec(),
+      b"AFLOAT".to_vec(),
+      6
+    ));
+
+    assert!(Afloat::marketplace_id().is_some());
+    assert!(Afloat::collection_id().is_some());
+    assert_eq!(Afloat::asset_id(), Some(0));
+    assert_eq!(Afloat::fee_account(), Some(1));
+    assert!(UserInfo::<Test>::contains_key(1));
+    assert!(UserInfo::<Test>::contains_key(2));
+    register(3, Jurisdiction::Federal);
+
+    assert_noop!(
+      Afloat::initial_setup(
+        RawOrigin::Root.into(),
+        1,
+        2,
+        0,
+        b"Afloat".to_vec(),
+        b"AFLOAT".to_vec(),
+        6
+      ),
+      Error::<Test>::AlreadyInitialized
+    );
+  });
+}
 
 #[test]
 fn sign_up_works() {
   new_test_ext().execute_with(|| {
     let user = new_account(3);
     Balances::make_free_balance_be(&user, 100);
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 1,
-    };
 
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args));
+    assert_ok!(Afloat::sign_up(
+      RawOrigin::Signed(user.clone()).into(),
+      sign_up_args(Jurisdiction::Federal)
+    ));
 
     assert!(UserInfo::<Test>::contains_key(user));
+    // The role is granted once the KYC application is approved
+    assert_eq!(Afloat::sign_up_requests(user), Some(AfloatRole::BuyerOrSeller));
+    assert!(roles_of(user).is_empty());
+    assert_noop!(
+      Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), sign_up_args(Jurisdiction::Federal)),
+      Error::<Test>::UserAlreadyExists
+    );
   });
 }
 
 #[test]
-fn update_user_info_edit_works() {
+fn rejected_sign_up_can_apply_again() {
   new_test_ext().execute_with(|| {
     let user = new_account(3);
     Balances::make_free_balance_be(&user, 100);
+    assert_ok!(Afloat::sign_up(
+      RawOrigin::Signed(user).into(),
+      sign_up_args(Jurisdiction::Federal)
+    ));
 
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 1,
-    };
+    assert_noop!(
+      Afloat::review_sign_up(RawOrigin::Signed(user).into(), user, true, feedback()),
+      Error::<Test>::Unauthorized
+    );
+    assert_ok!(Afloat::review_sign_up(RawOrigin::Signed(2).into(), user, false, feedback()));
+
+    assert!(!UserInfo::<Test>::contains_key(user));
+    assert_eq!(Afloat::sign_up_requests(user), None);
+    assert!(roles_of(user).is_empty());
+
+    assert_ok!(Afloat::sign_up(
+      RawOrigin::Signed(user).into(),
+      sign_up_args(Jurisdiction::Federal)
+    ));
+    assert_ok!(Afloat::review_sign_up(RawOrigin::Signed(2).into(), user, true, feedback()));
+    assert_eq!(roles_of(user).len(), 1);
+  });
+}
+
+#[test]
+fn users_only_trade_federal_and_their_own_state_tax_credits() {
+  new_test_ext().execute_with(|| {
+    register(3, Jurisdiction::California);
+    register(4, Jurisdiction::Alaska);
+    register(6, Jurisdiction::California);
+    register_cpa(5);
+
+    assert_noop!(
+      Afloat::create_tax_credit(
+        RawOrigin::Signed(3).into(),
+        dummy_description(),
+        None,
+        None,
+        Jurisdiction::Alaska,
+        10,
+      ),
+      Error::<Test>::JurisdictionNotAllowed
+    );
+    let tax_credit_id = Fruniques::next_frunique(Afloat::collection_id().unwrap());
+    assert_ok!(Afloat::create_tax_credit(
+      RawOrigin::Signed(3).into(),
+      dummy_description(),
+      None,
+      None,
+      Jurisdiction::California,
+      10,
+    ));
+    attest(5, 3, tax_credit_id);
 
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args));
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+    assert_noop!(
+      Afloat::create_offer(RawOrigin::Signed(4).into(), buy_args(tax_credit_id, 10000, 1)),
+      Error::<Test>::JurisdictionNotAllowed
+    );
+    assert_noop!(
+      Afloat::accept_offer(RawOrigin::Signed(4).into(), offer_id, Some(1)),
+      Error::<Test>::JurisdictionNotAllowed
+    );
+    accept_offer(6, offer_id, Some(1));
+  });
+}
+
+#[test]
+fn update_user_info_edit_works() {
+  new_test_ext().execute_with(|| {
+    let user = new_account(3);
+    register(user, Jurisdiction::Federal);
 
     let update_args = UpdateUserArgs::Edit {
-      first_name: Some(ShortString::try_from(b"New".to_vec()).unwrap()),
-      last_name: Some(ShortString::try_from(b"User".to_vec()).unwrap()),
-      email: Some(LongString::try_from(b"Info".to_vec()).unwrap()),
-      lang_key: None,
-      phone: None,
-      credits_needed: None,
-      cpa_id: None,
-      state: None,
+      cid: short_string(b"QmNew"),
+      cid_creator: short_string(b"QmNewCreator"),
     };
 
     assert_ok!(Afloat::update_user_info(
@@ -67,9 +335,9 @@ fn update_user_info_edit_works() {
     ));
 
     let updated_user = UserInfo::<Test>::get(user).unwrap();
-    assert_eq!(updated_user.first_name, ShortString::try_from(b"New".to_vec()).unwrap());
-    assert_eq!(updated_user.last_name, ShortString::try_from(b"User".to_vec()).unwrap());
-    assert_eq!(updated_user.email, LongString::try_from(b"Info".to_vec()).unwrap());
+    assert_eq!(updated_user.cid, short_string(b"QmNew"));
+    assert_eq!(updated_user.cid_creator, short_string(b"QmNewCreator"));
+    assert_eq!(updated_user.last_modified_by, Some(user));
   });
 }
 
@@ -78,28 +346,12 @@ fn update_other_user_info_by_not_admin_fails() {
   new_test_ext().execute_with(|| {
     let user = new_account(3);
     let other_user = new_account(4);
-
-    Balances::make_free_balance_be(&user, 100);
-    Balances::make_free_balance_be(&other_user, 100);
-
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 1,
-    };
-
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args));
+    register(user, Jurisdiction::Federal);
+    register(other_user, Jurisdiction::Federal);
 
     let update_args = UpdateUserArgs::Edit {
-      first_name: Some(ShortString::try_from(b"New".to_vec()).unwrap()),
-      last_name: Some(ShortString::try_from(b"User".to_vec()).unwrap()),
-      email: Some(LongString::try_from(b"Info".to_vec()).unwrap()),
-      lang_key: None,
-      phone: None,
-      credits_needed: None,
-      cpa_id: None,
-      state: None,
+      cid: short_string(b"QmNew"),
+      cid_creator: short_string(b"QmNewCreator"),
     };
 
     assert_noop!(
@@ -116,34 +368,13 @@ fn update_other_user_info_by_not_admin_fails() {
 #[test]
 fn update_other_user_info_by_admin_works() {
   new_test_ext().execute_with(|| {
-    let owner = new_account(1);
     let admin = new_account(2);
     let user = new_account(3);
-    let other_user = new_account(4);
-
-    Balances::make_free_balance_be(&owner, 100);
-    Balances::make_free_balance_be(&admin, 100);
-    Balances::make_free_balance_be(&user, 100);
-    Balances::make_free_balance_be(&other_user, 100);
-
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 1,
-    };
-
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args));
+    register(user, Jurisdiction::Federal);
 
     let update_args = UpdateUserArgs::Edit {
-      first_name: Some(ShortString::try_from(b"New".to_vec()).unwrap()),
-      last_name: Some(ShortString::try_from(b"User".to_vec()).unwrap()),
-      email: Some(LongString::try_from(b"Info".to_vec()).unwrap()),
-      lang_key: None,
-      phone: None,
-      credits_needed: None,
-      cpa_id: None,
-      state: None,
+      cid: short_string(b"QmNew"),
+      cid_creator: short_string(b"QmNewCreator"),
     };
 
     assert_ok!(Afloat::update_user_info(
@@ -153,9 +384,8 @@ fn update_other_user_info_by_admin_works() {
     ));
 
     let updated_user = UserInfo::<Test>::get(user).unwrap();
-    assert_eq!(updated_user.first_name, ShortString::try_from(b"New".to_vec()).unwrap());
-    assert_eq!(updated_user.last_name, ShortString::try_from(b"User".to_vec()).unwrap());
-    assert_eq!(updated_user.email, LongString::try_from(b"Info".to_vec()).unwrap());
+    assert_eq!(updated_user.cid, short_string(b"QmNew"));
+    assert_eq!(updated_user.last_modified_by, Some(admin));
   });
 }
 
@@ -163,24 +393,25 @@ fn update_other_user_info_by_admin_works() {
 fn update_user_info_delete_works() {
   new_test_ext().execute_with(|| {
     let user = new_account(3);
-    Balances::make_free_balance_be(&user, 100);
-
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 1,
-    };
-
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args));
+    register(user, Jurisdiction::Federal);
 
+    // Only admins can delete users
+    assert_noop!(
+      Afloat::update_user_info(
+        RawOrigin::Signed(user.clone()).into(),
+        user.clone(),
+        UpdateUserArgs::Delete
+      ),
+      Error::<Test>::Unauthorized
+    );
     assert_ok!(Afloat::update_user_info(
-      RawOrigin::Signed(user.clone()).into(),
+      RawOrigin::Signed(2).into(),
       user.clone(),
       UpdateUserArgs::Delete
     ));
 
     assert!(!UserInfo::<Test>::contains_key(user));
+    assert!(roles_of(user).is_empty());
   });
 }
 
@@ -193,19 +424,9 @@ fn kill_storage_works() {
     let user1 = new_account(3);
     let user2 = new_account(4);
 
-    Balances::make_free_balance_be(&user1, 100);
-    Balances::make_free_balance_be(&user2, 100);
-
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 1,
-    };
-
     // Add users
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user1.clone()).into(), args.clone()));
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user2.clone()).into(), args.clone()));
+    register(user1, Jurisdiction::Federal);
+    register(user2, Jurisdiction::Federal);
 
     // Ensure users exist
     assert!(UserInfo::<Test>::contains_key(user1));
@@ -214,17 +435,45 @@ fn kill_storage_works() {
     // Kill storage with admin
     assert_ok!(Afloat::kill_storage(RawOrigin::Signed(admin.clone()).into()));
 
-    // Ensure users no longer exist
+    // Ensure no user exists, the owner and the admin included
     assert!(!UserInfo::<Test>::contains_key(user1));
     assert!(!UserInfo::<Test>::contains_key(user2));
+    assert!(!UserInfo::<Test>::contains_key(admin));
+    assert!(!UserInfo::<Test>::contains_key(owner));
 
-    // Ensure admin and owner still exists
-    assert!(UserInfo::<Test>::contains_key(admin));
-    assert!(UserInfo::<Test>::contains_key(owner));
+    // Nobody can sign up until Afloat is set up again
+    assert_noop!(
+      Afloat::sign_up(RawOrigin::Signed(user1.clone()).into(), sign_up_args(Jurisdiction::Federal)),
+      Error::<Test>::NotInitialized
+    );
+  });
+}
 
-    // Add users again
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user1.clone()).into(), args.clone()));
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user2.clone()).into(), args.clone()));
+#[test]
+fn kill_storage_purges_in_multiple_blocks() {
+  new_test_ext().execute_with(|| {
+    System::set_block_number(1);
+    assert!(UserInfo::<Test>::contains_key(1));
+    assert!(UserInfo::<Test>::contains_key(2));
+
+    // Only one of the genesis users can be removed right away
+    assert!(!Afloat::start_purge(1).complete);
+    assert!(Afloat::storage_purge_progress().is_some());
+    // Nothing can be written until the purge finishes
+    assert_noop!(
+      Afloat::cancel_offer(RawOrigin::Signed(1).into(), [0u8; 32]),
+      Error::<Test>::StoragePurgeInProgress
+    );
+    assert_noop!(
+      Afloat::kill_storage(RawOrigin::Signed(1).into()),
+      Error::<Test>::StoragePurgeInProgress
+    );
+
+    Afloat::on_idle(1, Weight::MAX);
+    assert!(Afloat::storage_purge_progress().is_none());
+    assert!(!UserInfo::<Test>::contains_key(1));
+    assert!(!UserInfo::<Test>::contains_key(2));
+    System::assert_last_event(Event::StoragePurged.into());
   });
 }
 
@@ -242,247 +491,998 @@ fn kill_storage_fails_for_non_admin() {
 }
 
 #[test]
-fn set_afloat_balance_works() {
+fn deposit_and_withdrawal_requests_work() {
   new_test_ext().execute_with(|| {
     let user = new_account(3);
-    let other_user = new_account(4);
-
-    Balances::make_free_balance_be(&user, 100);
-    Balances::make_free_balance_be(&other_user, 100);
+    register(user, Jurisdiction::Federal);
 
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 1,
-    };
-
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args.clone()));
+    deposit(user, 10000);
+    assert_eq!(Afloat::do_get_afloat_balance(user.clone()), 10000);
 
-    assert_ok!(Afloat::set_afloat_balance(RawOrigin::Signed(1).into(), user.clone(), 10000));
+    assert_noop!(
+      Afloat::request_withdrawal(RawOrigin::Signed(user).into(), 20000, bank_reference()),
+      Error::<Test>::InsufficientBalance
+    );
+    assert_ok!(Afloat::request_withdrawal(RawOrigin::Signed(user).into(), 9000, bank_reference()));
+    let request_id = pending_request(user);
+    // The balance is only burned once an admin confirms the withdrawal
     assert_eq!(Afloat::do_get_afloat_balance(user.clone()), 10000);
-    assert_ok!(Afloat::set_afloat_balance(RawOrigin::Signed(1).into(), user.clone(), 1000));
+    assert_ok!(Afloat::confirm_ramp_request(RawOrigin::Signed(2).into(), user, request_id));
     assert_eq!(Afloat::do_get_afloat_balance(user.clone()), 1000);
+    assert_noop!(
+      Afloat::confirm_ramp_request(RawOrigin::Signed(2).into(), user, request_id),
+      Error::<Test>::RampRequestAlreadyReviewed
+    );
+
+    let ledger = Afloat::get_ramp_ledger(user);
+    assert_eq!(ledger.len(), 2);
+    assert!(ledger.iter().all(|(_, request)| request.status == RampRequestStatus::Confirmed));
   });
 }
 
 #[test]
-fn set_balance_by_other_than_owner_fails() {
+fn ramp_requests_reviewed_by_other_than_admin_fail() {
   new_test_ext().execute_with(|| {
     let user = new_account(3);
     let other_user = new_account(4);
+    register(user, Jurisdiction::Federal);
+    register(other_user, Jurisdiction::Federal);
 
-    Balances::make_free_balance_be(&user, 100);
-    Balances::make_free_balance_be(&other_user, 100);
-
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 1,
-    };
-
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args.clone()));
+    assert_ok!(Afloat::request_deposit(RawOrigin::Signed(user).into(), 10000, bank_reference()));
+    let request_id = pending_request(user);
 
     assert_noop!(
-      Afloat::set_afloat_balance(RawOrigin::Signed(3).into(), other_user.clone(), 10000),
+      Afloat::confirm_ramp_request(RawOrigin::Signed(user).into(), user, request_id),
       Error::<Test>::Unauthorized
     );
     assert_noop!(
-      Afloat::set_afloat_balance(RawOrigin::Signed(2).into(), other_user.clone(), 10000),
+      Afloat::confirm_ramp_request(RawOrigin::Signed(other_user).into(), user, request_id),
       Error::<Test>::Unauthorized
     );
+    assert_ok!(Afloat::reject_ramp_request(RawOrigin::Signed(1).into(), user, request_id));
+    assert_eq!(Afloat::do_get_afloat_balance(user.clone()), 0);
   });
 }
 
 #[test]
-fn create_tax_credit_works() {
+fn invalid_ramp_requests_fail() {
   new_test_ext().execute_with(|| {
     let user = new_account(3);
-    let other_user = new_account(4);
 
-    Balances::make_free_balance_be(&user, 100);
-    Balances::make_free_balance_be(&other_user, 100);
+    assert_noop!(
+      Afloat::request_deposit(RawOrigin::Signed(user).into(), 10000, bank_reference()),
+      Error::<Test>::UserNotFound
+    );
+    register(user, Jurisdiction::Federal);
+    assert_noop!(
+      Afloat::request_deposit(RawOrigin::Signed(user).into(), 0, bank_reference()),
+      Error::<Test>::InvalidAmount
+    );
+    assert_ok!(Afloat::request_deposit(RawOrigin::Signed(user).into(), 10000, bank_reference()));
+    assert_noop!(
+      Afloat::request_deposit(RawOrigin::Signed(user).into(), 10000, bank_reference()),
+      Error::<Test>::RampRequestAlreadyExists
+    );
+    assert_noop!(
+      Afloat::confirm_ramp_request(RawOrigin::Signed(2).into(), user, [0; 32]),
+      Error::<Test>::RampRequestNotFound
+    );
+  });
+}
 
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 1,
-    };
+#[test]
+fn rejected_withdrawal_keeps_the_balance() {
+  new_test_ext().execute_with(|| {
+    let user = new_account(3);
+    register(user, Jurisdiction::Federal);
+    deposit(user, 10000);
 
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args.clone()));
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(other_user.clone()).into(), args.clone()));
+    assert_ok!(Afloat::request_withdrawal(RawOrigin::Signed(user).into(), 5000, bank_reference()));
+    let request_id = pending_request(user);
+    assert_ok!(Afloat::reject_ramp_request(RawOrigin::Signed(2).into(), user, request_id));
 
-    assert_ok!(Afloat::create_tax_credit(
-      RawOrigin::Signed(user.clone()).into(),
-      dummy_description(),
-      None,
-      None,
-    ));
+    assert_eq!(Afloat::do_get_afloat_balance(user.clone()), 10000);
+    let request = Afloat::ramp_requests(user, request_id).unwrap();
+    assert_eq!(request.status, RampRequestStatus::Rejected);
+    assert_eq!(request.reviewed_by, Some(2));
+    assert_noop!(
+      Afloat::confirm_ramp_request(RawOrigin::Signed(2).into(), user, request_id),
+      Error::<Test>::RampRequestAlreadyReviewed
+    );
   });
 }
 
 #[test]
-fn create_sell_order_works() {
+fn create_tax_credit_works() {
   new_test_ext().execute_with(|| {
     let user = new_account(3);
-    let other_user = new_account(4);
-    let item_id = 0;
+    register(user, Jurisdiction::Federal);
 
-    Balances::make_free_balance_be(&user, 100);
-    Balances::make_free_balance_be(&other_user, 100);
+    let tax_credit_id = create_tax_credit(user, 10);
 
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 0,
-    };
+    assert_eq!(Uniques::owner(Afloat::collection_id().unwrap(), tax_credit_id), Some(user));
+    assert_eq!(Afloat::tax_credit_jurisdiction(tax_credit_id), Some(Jurisdiction::Federal));
+    assert_eq!(Afloat::tax_credit_amount(tax_credit_id), 10);
+    assert_noop!(
+      Afloat::create_tax_credit(
+        RawOrigin::Signed(user).into(),
+        dummy_description(),
+        None,
+        None,
+        Jurisdiction::Federal,
+        0,
+      ),
+      Error::<Test>::InvalidTaxCreditAmount
+    );
+  });
+}
+
+#[test]
+fn create_sell_order_works() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
 
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args.clone()));
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(other_user.clone()).into(), args.clone()));
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
 
-    assert_ok!(Afloat::create_tax_credit(
+    let offer = Afloat::afloat_offers(offer_id).unwrap();
+    assert_eq!(offer.offer_type, OfferType::Sell);
+    assert_eq!(offer.status, OfferStatus::CREATED);
+    assert_eq!(offer.tax_credit_amount_remaining, 10);
+    assert_eq!(Afloat::order_book(tax_credit_id).into_inner(), vec![offer_id]);
+  });
+}
+
+#[test]
+fn create_buy_order_works() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+
+    let offer_id = create_offer(4, buy_args(tax_credit_id, 10000, 10));
+
+    let offer = Afloat::afloat_offers(offer_id).unwrap();
+    assert_eq!(offer.offer_type, OfferType::Buy);
+    assert_eq!(offer.status, OfferStatus::CREATED);
+    assert_eq!(Afloat::order_book(tax_credit_id).into_inner(), vec![offer_id]);
+  });
+}
+
+#[test]
+fn create_sell_order_of_a_tax_credit_not_owned_fails() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+
+    assert_noop!(
+      Afloat::create_offer(RawOrigin::Signed(4).into(), sell_args(tax_credit_id, 10000, 1)),
+      Error::<Test>::NotOwnerOfTaxCredit
+    );
+  });
+}
+
+#[test]
+fn create_sell_order_for_more_credits_than_held_fails() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+
+    assert_noop!(
+      Afloat::create_offer(RawOrigin::Signed(3).into(), sell_args(tax_credit_id, 10000, 0)),
+      Error::<Test>::InvalidTaxCreditAmount
+    );
+    assert_noop!(
+      Afloat::create_offer(RawOrigin::Signed(3).into(), sell_args(tax_credit_id, 10000, 11)),
+      Error::<Test>::NotEnoughTaxCreditsAvailable
+    );
+
+    // The credits of the open sell offers can't be offered again
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 6));
+    assert_noop!(
+      Afloat::create_offer(RawOrigin::Signed(3).into(), sell_args(tax_credit_id, 20000, 5)),
+      Error::<Test>::NotEnoughTaxCreditsAvailable
+    );
+    assert_ok!(Afloat::cancel_offer(RawOrigin::Signed(3).into(), offer_id));
+    create_offer(3, sell_args(tax_credit_id, 20000, 5));
+  });
+}
+
+#[test]
+fn credits_of_a_filled_offer_cant_be_listed_again_until_settled() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    deposit(4, 100000);
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 4));
+    let transaction_id = accept_offer(4, offer_id, None);
+    assert!(Afloat::order_book(tax_credit_id).is_empty());
+
+    // The credits of the filled offer are still committed to its transaction
+    assert_noop!(
+      Afloat::create_offer(RawOrigin::Signed(3).into(), sell_args(tax_credit_id, 20000, 7)),
+      Error::<Test>::NotEnoughTaxCreditsAvailable
+    );
+    let relisted_id = create_offer(3, sell_args(tax_credit_id, 20000, 6));
+
+    // Once settled, the tax credit holds the 6 credits left
+    approve_transfer_form(transaction_id);
+    assert_ok!(Afloat::approve_transaction(RawOrigin::Signed(2).into(), transaction_id));
+    assert_eq!(Afloat::tax_credit_amount(tax_credit_id), 6);
+    assert_ok!(Afloat::cancel_offer(RawOrigin::Signed(3).into(), relisted_id));
+    create_offer(3, sell_args(tax_credit_id, 15000, 6));
+  });
+}
+
+#[test]
+fn crossing_offers_are_matched_with_price_time_priority() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    register(6, Jurisdiction::Federal);
+    let low_bid_id = create_offer(4, buy_args(tax_credit_id, 10000, 3));
+    let first_bid_id = create_offer(6, buy_args(tax_credit_id, 12000, 3));
+    let second_bid_id = create_offer(4, buy_args(tax_credit_id, 12000, 2));
+
+    let ask_id = create_offer(3, sell_args(tax_credit_id, 11000, 4));
+
+    // The ask is filled by the best bids, oldest first, at their own price
+    let ask = Afloat::afloat_offers(ask_id).unwrap();
+    assert_eq!(ask.tax_credit_amount_remaining, 0);
+    assert_eq!(ask.status, OfferStatus::MATCHED);
+    assert_eq!(Afloat::afloat_offers(first_bid_id).unwrap().tax_credit_amount_remaining, 0);
+    assert_eq!(Afloat::afloat_offers(second_bid_id).unwrap().tax_credit_amount_remaining, 1);
+    assert_eq!(Afloat::afloat_offers(low_bid_id).unwrap().tax_credit_amount_remaining, 3);
+    assert_eq!(Afloat::order_book(tax_credit_id).into_inner(), vec![low_bid_id, second_bid_id]);
+
+    let mut transactions: Vec<_> = transaction_ids_of(3)
+      .into_iter()
+      .map(|transaction_id| Afloat::afloat_transactions(transaction_id).unwrap())
+      .map(|transaction| {
+        (transaction.buyer_id, transaction.tax_credit_amount, transaction.total_price)
+      })
+      .collect();
+    transactions.sort();
+    assert_eq!(transactions, vec![(4, 1, 12000), (6, 3, 36000)]);
+    assert!(transaction_ids_of(3).into_iter().all(|transaction_id| {
+      Afloat::afloat_transactions(transaction_id).unwrap().matched_offer_id == Some(ask_id)
+    }));
+  });
+}
+
+#[test]
+fn offers_that_dont_cross_rest_in_the_order_book() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let bid_id = create_offer(4, buy_args(tax_credit_id, 10000, 3));
+
+    let ask_id = create_offer(3, sell_args(tax_credit_id, 11000, 4));
+
+    assert!(transaction_ids_of(3).is_empty());
+    assert_eq!(Afloat::afloat_offers(ask_id).unwrap().status, OfferStatus::CREATED);
+    assert_eq!(Afloat::order_book(tax_credit_id).into_inner(), vec![bid_id, ask_id]);
+  });
+}
+
+#[test]
+fn accept_offer_for_part_of_the_credits_works() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+
+    let transaction_id = accept_offer(4, offer_id, Some(4));
+
+    let transaction = Afloat::afloat_transactions(transaction_id).unwrap();
+    assert_eq!(transaction.tax_credit_amount, 4);
+    assert_eq!(transaction.total_price, 40000);
+    assert_eq!(transaction.fee, 1600);
+    assert_eq!(transaction.seller_id, 3);
+    assert_eq!(transaction.buyer_id, 4);
+    assert_eq!(transaction.offer_id, offer_id);
+    assert_eq!(transaction.status, OfferStatus::MATCHED);
+
+    // The rest of the credits can still be traded
+    let offer = Afloat::afloat_offers(offer_id).unwrap();
+    assert_eq!(offer.tax_credit_amount_remaining, 6);
+    assert_eq!(offer.status, OfferStatus::MATCHED);
+    assert!(offer.is_open());
+    assert_eq!(Afloat::order_book(tax_credit_id).into_inner(), vec![offer_id]);
+  });
+}
+
+#[test]
+fn accept_offer_for_the_remaining_credits_closes_the_offer() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+
+    accept_offer(4, offer_id, Some(4));
+    let transaction_id = accept_offer(4, offer_id, None);
+
+    assert_eq!(Afloat::afloat_transactions(transaction_id).unwrap().tax_credit_amount, 6);
+    assert_eq!(Afloat::afloat_offers(offer_id).unwrap().tax_credit_amount_remaining, 0);
+    assert!(Afloat::order_book(tax_credit_id).is_empty());
+    assert_noop!(
+      Afloat::accept_offer(RawOrigin::Signed(4).into(), offer_id, None),
+      Error::<Test>::OfferNotAvailable
+    );
+  });
+}
+
+#[test]
+fn accept_offer_with_an_invalid_amount_fails() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+
+    assert_noop!(
+      Afloat::accept_offer(RawOrigin::Signed(4).into(), offer_id, Some(0)),
+      Error::<Test>::InvalidTaxCreditAmount
+    );
+    assert_noop!(
+      Afloat::accept_offer(RawOrigin::Signed(4).into(), offer_id, Some(11)),
+      Error::<Test>::NotEnoughTaxCreditsAvailable
+    );
+    assert_noop!(
+      Afloat::accept_offer(RawOrigin::Signed(3).into(), offer_id, None),
+      Error::<Test>::CannotAcceptOwnOffer
+    );
+    assert_noop!(
+      Afloat::accept_offer(RawOrigin::Signed(4).into(), [0u8; 32], None),
+      Error::<Test>::OfferNotFound
+    );
+  });
+}
+
+#[test]
+fn accept_buy_offer_by_other_than_the_owner_fails() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    register(6, Jurisdiction::Federal);
+    let offer_id = create_offer(4, buy_args(tax_credit_id, 10000, 10));
+
+    // Only the owner of the tax credit can sell it
+    assert_noop!(
+      Afloat::accept_offer(RawOrigin::Signed(6).into(), offer_id, None),
+      Error::<Test>::NotOwnerOfTaxCredit
+    );
+    let transaction_id = accept_offer(3, offer_id, Some(5));
+    let transaction = Afloat::afloat_transactions(transaction_id).unwrap();
+    assert_eq!(transaction.seller_id, 3);
+    assert_eq!(transaction.buyer_id, 4);
+  });
+}
+
+// Takes a matched transaction through the transfer form until the tax agency approves it, CPA 5
+// reviews the form
+fn approve_transfer_form(transaction_id: StorageId) {
+  let transaction = Afloat::afloat_transactions(transaction_id).unwrap();
+  assert_ok!(Afloat::fill_transfer_form(
+    RawOrigin::Signed(transaction.seller_id).into(),
+    transaction_id,
+    short_string(b"QmTransferForm")
+  ));
+  assert_ok!(Afloat::cpa_update_transfer_form(
+    RawOrigin::Signed(5).into(),
+    transaction_id,
+    CpaTransferFormArgs::RequestSignatures
+  ));
+  assert_ok!(Afloat::sign_transfer_form(
+    RawOrigin::Signed(transaction.seller_id).into(),
+    transaction_id,
+    short_string(b"QmSellerSignature")
+  ));
+  assert_ok!(Afloat::sign_transfer_form(
+    RawOrigin::Signed(transaction.buyer_id).into(),
+    transaction_id,
+    short_string(b"QmBuyerSignature")
+  ));
+  assert_ok!(Afloat::cpa_update_transfer_form(
+    RawOrigin::Signed(5).into(),
+    transaction_id,
+    CpaTransferFormArgs::SubmitToAgency { cid: short_string(b"QmAgencySubmission") }
+  ));
+  assert_ok!(Afloat::cpa_update_transfer_form(
+    RawOrigin::Signed(5).into(),
+    transaction_id,
+    CpaTransferFormArgs::AgencyApproved { cid: short_string(b"QmAgencyApproval") }
+  ));
+}
+
+#[test]
+fn transfer_form_workflow_works() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+    let transaction_id = accept_offer(4, offer_id, Some(4));
+
+    assert_ok!(Afloat::fill_transfer_form(
       RawOrigin::Signed(3).into(),
-      dummy_description(),
-      None,
-      None,
+      transaction_id,
+      short_string(b"QmTransferForm")
     ));
+    assert_eq!(Afloat::afloat_transactions(transaction_id).unwrap().status, OfferStatus::TF_FILLED);
 
-    assert_ok!(Afloat::create_sell_order(
-      RawOrigin::Signed(user.clone()).into(),
-      item_id,
-      10000,
-      10,
+    assert_ok!(Afloat::cpa_update_transfer_form(
+      RawOrigin::Signed(5).into(),
+      transaction_id,
+      CpaTransferFormArgs::RequestSignatures
+    ));
+    assert_ok!(Afloat::sign_transfer_form(
+      RawOrigin::Signed(4).into(),
+      transaction_id,
+      short_string(b"QmBuyerSignature")
+    ));
+    // The form is signed once both parties signed it
+    assert_eq!(
+      Afloat::afloat_transactions(transaction_id).unwrap().status,
+      OfferStatus::TF_PENDING_SIGNATURE
+    );
+    assert_ok!(Afloat::sign_transfer_form(
+      RawOrigin::Signed(3).into(),
+      transaction_id,
+      short_string(b"QmSellerSignature")
+    ));
+    assert_eq!(Afloat::afloat_transactions(transaction_id).unwrap().status, OfferStatus::TF_SIGNED);
+
+    assert_ok!(Afloat::cpa_update_transfer_form(
+      RawOrigin::Signed(5).into(),
+      transaction_id,
+      CpaTransferFormArgs::SubmitToAgency { cid: short_string(b"QmAgencySubmission") }
     ));
+    assert_ok!(Afloat::cpa_update_transfer_form(
+      RawOrigin::Signed(5).into(),
+      transaction_id,
+      CpaTransferFormArgs::AgencyApproved { cid: short_string(b"QmAgencyApproval") }
+    ));
+
+    let transaction = Afloat::afloat_transactions(transaction_id).unwrap();
+    assert_eq!(transaction.status, OfferStatus::TF_AGENCY_APPROVED);
+    assert!(transaction.seller_confirmation_date.is_some());
+    assert!(transaction.buyer_confirmation_date.is_some());
+    assert_eq!(
+      transaction.documents,
+      TransferFormDocuments {
+        transfer_form_cid: Some(short_string(b"QmTransferForm")),
+        seller_signature_cid: Some(short_string(b"QmSellerSignature")),
+        buyer_signature_cid: Some(short_string(b"QmBuyerSignature")),
+        agency_submission_cid: Some(short_string(b"QmAgencySubmission")),
+        agency_approval_cid: Some(short_string(b"QmAgencyApproval")),
+      }
+    );
   });
 }
 
 #[test]
-fn take_sell_order_works() {
+fn transfer_form_steps_out_of_order_fail() {
   new_test_ext().execute_with(|| {
-    let user = new_account(3);
-    let other_user = new_account(4);
-    let item_id = 0;
+    let tax_credit_id = setup_trade();
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+    let transaction_id = accept_offer(4, offer_id, Some(4));
 
-    Balances::make_free_balance_be(&user, 100);
-    Balances::make_free_balance_be(&other_user, 100);
+    // Only the seller fills the form
+    assert_noop!(
+      Afloat::fill_transfer_form(
+        RawOrigin::Signed(4).into(),
+        transaction_id,
+        short_string(b"QmTransferForm")
+      ),
+      Error::<Test>::Unauthorized
+    );
+    assert_noop!(
+      Afloat::sign_transfer_form(
+        RawOrigin::Signed(3).into(),
+        transaction_id,
+        short_string(b"QmSellerSignature")
+      ),
+      Error::<Test>::InvalidTransactionStatus
+    );
+    assert_noop!(
+      Afloat::approve_transaction(RawOrigin::Signed(2).into(), transaction_id),
+      Error::<Test>::InvalidTransactionStatus
+    );
 
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 0,
-    };
+    assert_ok!(Afloat::fill_transfer_form(
+      RawOrigin::Signed(3).into(),
+      transaction_id,
+      short_string(b"QmTransferForm")
+    ));
+    // Only CPAs review the form
+    assert_noop!(
+      Afloat::cpa_update_transfer_form(
+        RawOrigin::Signed(3).into(),
+        transaction_id,
+        CpaTransferFormArgs::RequestSignatures
+      ),
+      Error::<Test>::Unauthorized
+    );
+    assert_ok!(Afloat::cpa_update_transfer_form(
+      RawOrigin::Signed(5).into(),
+      transaction_id,
+      CpaTransferFormArgs::RequestSignatures
+    ));
+    assert_ok!(Afloat::sign_transfer_form(
+      RawOrigin::Signed(3).into(),
+      transaction_id,
+      short_string(b"QmSellerSignature")
+    ));
+    assert_noop!(
+      Afloat::sign_transfer_form(
+        RawOrigin::Signed(3).into(),
+        transaction_id,
+        short_string(b"QmSellerSignature")
+      ),
+      Error::<Test>::TransferFormAlreadySigned
+    );
+    assert_noop!(
+      Afloat::sign_transfer_form(
+        RawOrigin::Signed(5).into(),
+        transaction_id,
+        short_string(b"QmCpaSignature")
+      ),
+      Error::<Test>::Unauthorized
+    );
+  });
+}
 
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args.clone()));
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(other_user.clone()).into(), args.clone()));
+#[test]
+fn approve_transaction_settles_a_share_of_the_tax_credit() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let collection_id = Afloat::collection_id().unwrap();
+    deposit(4, 100000);
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+    let transaction_id = accept_offer(4, offer_id, Some(4));
+    approve_transfer_form(transaction_id);
+
+    let child_id = Fruniques::next_frunique(collection_id);
+    assert_ok!(Afloat::approve_transaction(RawOrigin::Signed(2).into(), transaction_id));
+
+    let transaction = Afloat::afloat_transactions(transaction_id).unwrap();
+    assert_eq!(transaction.status, OfferStatus::AFLOAT_APPROVED);
+    assert!(transaction.marketplace_offer_id.is_some());
+    // The buyer gets a child with the traded credits, 4 of the 10 the tax credit held
+    assert_eq!(Uniques::owner(collection_id, tax_credit_id), Some(3));
+    assert_eq!(Uniques::owner(collection_id, child_id), Some(4));
+    assert_eq!(Afloat::tax_credit_amount(tax_credit_id), 6);
+    assert_eq!(Afloat::tax_credit_amount(child_id), 4);
+    // The child keeps the jurisdiction and the attestation of its parent, so it can be resold
+    assert_eq!(Afloat::tax_credit_jurisdiction(child_id), Some(Jurisdiction::Federal));
+    assert_eq!(Fruniques::frunique_verified(collection_id, child_id), Some(true));
+    create_offer(4, sell_args(child_id, 10000, 4));
+    assert_eq!(
+      Fruniques::frunique_info(collection_id, child_id)
+        .unwrap()
+        .parent
+        .unwrap()
+        .parent_weight,
+      Permill::from_percent(40)
+    );
+    // 40000 paid, 4% of it to the owner
+    assert_eq!(Afloat::do_get_afloat_balance(4), 60000);
+    assert_eq!(Afloat::do_get_afloat_balance(3), 38400);
+    assert_eq!(Afloat::do_get_afloat_balance(1), 1600);
+  });
+}
 
-    assert_ok!(Afloat::set_afloat_balance(RuntimeOrigin::signed(1), 4, 100000));
+#[test]
+fn approve_transaction_selling_every_credit_transfers_the_tax_credit() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let collection_id = Afloat::collection_id().unwrap();
+    deposit(4, 100000);
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+    let first_transaction_id = accept_offer(4, offer_id, Some(4));
+    let last_transaction_id = accept_offer(4, offer_id, None);
+    approve_transfer_form(first_transaction_id);
+    approve_transfer_form(last_transaction_id);
+    assert_ok!(Afloat::approve_transaction(RawOrigin::Signed(2).into(), first_transaction_id));
+
+    // The 6 credits left are all the tax credit holds
+    let next_frunique = Fruniques::next_frunique(collection_id);
+    assert_ok!(Afloat::approve_transaction(RawOrigin::Signed(2).into(), last_transaction_id));
+
+    assert_eq!(
+      Afloat::afloat_transactions(last_transaction_id).unwrap().marketplace_offer_id,
+      None
+    );
+    assert_eq!(Uniques::owner(collection_id, tax_credit_id), Some(4));
+    assert_eq!(Fruniques::next_frunique(collection_id), next_frunique);
+    assert_eq!(Afloat::tax_credit_amount(tax_credit_id), 6);
+    assert_eq!(Afloat::do_get_afloat_balance(4), 0);
+    assert_eq!(Afloat::do_get_afloat_balance(3), 96000);
+    assert_eq!(Afloat::do_get_afloat_balance(1), 4000);
+  });
+}
 
-    assert_ok!(Afloat::create_tax_credit(
-      RawOrigin::Signed(user.clone()).into(),
-      dummy_description(),
-      None,
-      None,
-    ));
+#[test]
+fn approve_transaction_without_the_balance_to_pay_settles_nothing() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let collection_id = Afloat::collection_id().unwrap();
+    deposit(4, 10000);
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+    let transaction_id = accept_offer(4, offer_id, None);
+    approve_transfer_form(transaction_id);
+
+    assert!(Afloat::approve_transaction(RawOrigin::Signed(2).into(), transaction_id).is_err());
+
+    assert_eq!(
+      Afloat::afloat_transactions(transaction_id).unwrap().status,
+      OfferStatus::TF_AGENCY_APPROVED
+    );
+    assert_eq!(Uniques::owner(collection_id, tax_credit_id), Some(3));
+    assert_eq!(Afloat::do_get_afloat_balance(4), 10000);
+  });
+}
 
-    assert_ok!(Afloat::create_sell_order(
-      RawOrigin::Signed(user.clone()).into(),
-      item_id,
-      10000,
-      10,
-    ));
+#[test]
+fn approve_transaction_for_a_share_below_one_percent_fails() {
+  new_test_ext().execute_with(|| {
+    register(3, Jurisdiction::Federal);
+    register(4, Jurisdiction::Federal);
+    register_cpa(5);
+    let tax_credit_id = create_tax_credit(3, 200);
+    attest(5, 3, tax_credit_id);
+    deposit(4, 100000);
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+    let transaction_id = accept_offer(4, offer_id, Some(1));
+    approve_transfer_form(transaction_id);
 
-    let offer_id = GatedMarketplace::offers_by_item(0, 0).iter().next().unwrap().clone();
+    assert_noop!(
+      Afloat::approve_transaction(RawOrigin::Signed(2).into(), transaction_id),
+      Error::<Test>::TaxCreditShareTooSmall
+    );
+  });
+}
 
-    assert_ok!(Afloat::take_sell_order(RawOrigin::Signed(other_user.clone()).into(), offer_id,));
+#[test]
+fn cancel_offer_works() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+    let transaction_id = accept_offer(4, offer_id, Some(4));
 
-    assert_eq!(Afloat::do_get_afloat_balance(user.clone()), 9600); // 10000 - 400 (sell fee)
-    assert_eq!(Afloat::do_get_afloat_balance(1), 400); // 400 (sell fee)
+    assert_noop!(
+      Afloat::cancel_offer(RawOrigin::Signed(4).into(), offer_id),
+      Error::<Test>::Unauthorized
+    );
+    assert_ok!(Afloat::cancel_offer(RawOrigin::Signed(3).into(), offer_id));
+
+    let offer = Afloat::afloat_offers(offer_id).unwrap();
+    assert_eq!(offer.status, OfferStatus::CANCELLED);
+    assert!(offer.cancellation_date.is_some());
+    assert!(Afloat::order_book(tax_credit_id).is_empty());
+    // The transactions already created are kept
+    assert_eq!(Afloat::afloat_transactions(transaction_id).unwrap().status, OfferStatus::MATCHED);
+    assert_noop!(
+      Afloat::accept_offer(RawOrigin::Signed(4).into(), offer_id, None),
+      Error::<Test>::OfferCancelled
+    );
+    assert_noop!(
+      Afloat::cancel_offer(RawOrigin::Signed(3).into(), offer_id),
+      Error::<Test>::OfferCancelled
+    );
   });
 }
 
 #[test]
-fn create_buy_order_works() {
+fn expired_offers_are_swept_on_idle() {
   new_test_ext().execute_with(|| {
-    let user = new_account(3);
-    let other_user = new_account(4);
-    let item_id = 0;
+    let tax_credit_id = setup_trade();
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 5));
+    let other_offer_id = create_offer(
+      3,
+      CreateOfferArgs::Sell {
+        tax_credit_amount: 5,
+        price_per_credit: 20000,
+        tax_credit_id,
+        expiration_date: 5_000,
+      },
+    );
 
-    Balances::make_free_balance_be(&user, 100);
-    Balances::make_free_balance_be(&other_user, 100);
+    // Timestamps are in milliseconds, expiration dates in seconds
+    Timestamp::set_timestamp(2_000_000);
+    assert_noop!(
+      Afloat::accept_offer(RawOrigin::Signed(4).into(), offer_id, None),
+      Error::<Test>::OfferExpired
+    );
 
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 0,
-    };
+    Afloat::on_idle(1, Weight::MAX);
 
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args.clone()));
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(other_user.clone()).into(), args.clone()));
+    assert_eq!(Afloat::afloat_offers(offer_id).unwrap().status, OfferStatus::EXPIRED);
+    assert_eq!(Afloat::afloat_offers(other_offer_id).unwrap().status, OfferStatus::CREATED);
+    assert_eq!(Afloat::order_book(tax_credit_id).into_inner(), vec![other_offer_id]);
+    assert_noop!(
+      Afloat::cancel_offer(RawOrigin::Signed(3).into(), offer_id),
+      Error::<Test>::OfferExpired
+    );
+  });
+}
 
-    assert_ok!(Afloat::set_afloat_balance(RuntimeOrigin::signed(1), 4, 100000));
+#[test]
+fn offer_sweep_stops_when_out_of_weight() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    create_offer(3, sell_args(tax_credit_id, 10000, 10));
+    Timestamp::set_timestamp(2_000_000);
+
+    // Not enough weight to check a single offer
+    assert_eq!(Afloat::on_idle(1, Weight::zero()), Weight::zero());
+    assert_eq!(Afloat::offers_sweep_cursor(), None);
+    assert_eq!(Afloat::get_offers_by_status(OfferStatus::EXPIRED, None, MAX_PAGE_SIZE).len(), 0);
+  });
+}
 
-    assert_ok!(Afloat::create_tax_credit(
-      RawOrigin::Signed(user.clone()).into(),
-      dummy_description(),
-      None,
-      None,
-    ));
+#[test]
+fn cpa_attestation_works() {
+  new_test_ext().execute_with(|| {
+    register(3, Jurisdiction::Federal);
+    register_cpa(5);
+    let tax_credit_id = create_tax_credit(3, 10);
+    let collection_id = Afloat::collection_id().unwrap();
+    assert_eq!(Fruniques::frunique_verified(collection_id, tax_credit_id), None);
 
-    assert_ok!(Afloat::create_buy_order(
-      RawOrigin::Signed(other_user.clone()).into(),
-      item_id,
-      10000,
-      10,
-    ));
+    attest(5, 3, tax_credit_id);
+
+    assert_eq!(Afloat::cpa_of(3), Some(5));
+    assert_eq!(Fruniques::frunique_verified(collection_id, tax_credit_id), Some(true));
   });
 }
 
 #[test]
-fn take_buy_order_works() {
+fn assign_cpa_fails_for_non_admins_and_non_cpas() {
   new_test_ext().execute_with(|| {
-    let user = new_account(3);
-    let other_user = new_account(4);
-    let item_id = 0;
+    register(3, Jurisdiction::Federal);
+    register(4, Jurisdiction::Federal);
+    register_cpa(5);
 
-    Balances::make_free_balance_be(&user, 100);
-    Balances::make_free_balance_be(&other_user, 100);
+    assert_noop!(
+      Afloat::assign_cpa(RawOrigin::Signed(3).into(), 3, 5),
+      Error::<Test>::Unauthorized
+    );
+    assert_noop!(Afloat::assign_cpa(RawOrigin::Signed(2).into(), 3, 4), Error::<Test>::NotACpa);
+    assert_noop!(
+      Afloat::assign_cpa(RawOrigin::Signed(2).into(), 6, 5),
+      Error::<Test>::UserNotFound
+    );
+  });
+}
+
+#[test]
+fn attest_tax_credit_by_other_than_the_assigned_cpa_fails() {
+  new_test_ext().execute_with(|| {
+    register(3, Jurisdiction::Federal);
+    register_cpa(5);
+    register_cpa(6);
+    let tax_credit_id = create_tax_credit(3, 10);
+
+    assert_noop!(
+      Afloat::attest_tax_credit(RawOrigin::Signed(5).into(), tax_credit_id, None),
+      Error::<Test>::CpaNotAssigned
+    );
+    assert_ok!(Afloat::assign_cpa(RawOrigin::Signed(2).into(), 3, 5));
+    assert_noop!(
+      Afloat::attest_tax_credit(RawOrigin::Signed(6).into(), tax_credit_id, None),
+      Error::<Test>::CpaNotAssigned
+    );
+  });
+}
+
+#[test]
+fn selling_an_unattested_tax_credit_fails() {
+  new_test_ext().execute_with(|| {
+    register(3, Jurisdiction::Federal);
+    let tax_credit_id = create_tax_credit(3, 10);
 
-    let args = SignUpArgs::BuyerOrSeller {
-      first_name: ShortString::try_from(b"Afloat".to_vec()).unwrap(),
-      last_name: ShortString::try_from(b"User".to_vec()).unwrap(),
-      email: LongString::try_from(b"Afloatuser@gmail.com".to_vec()).unwrap(),
-      state: 0,
+    assert_noop!(
+      Afloat::create_offer(RawOrigin::Signed(3).into(), sell_args(tax_credit_id, 10000, 10)),
+      Error::<Test>::TaxCreditNotAttested
+    );
+  });
+}
+
+#[test]
+fn offer_indexes_follow_the_offer_status() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let cancelled_id = create_offer(3, sell_args(tax_credit_id, 10000, 2));
+    let expired_id = create_offer(3, sell_args(tax_credit_id, 20000, 2));
+    let bid_id = create_offer(4, buy_args(tax_credit_id, 5000, 2));
+    let ask_id = create_offer(
+      3,
+      CreateOfferArgs::Sell {
+        tax_credit_amount: 2,
+        price_per_credit: 5000,
+        tax_credit_id,
+        expiration_date: 5_000,
+      },
+    );
+
+    for offer_id in [cancelled_id, expired_id, ask_id] {
+      assert_eq!(Afloat::offer_ids_by_creator(3, offer_id), Some(()));
+    }
+    assert_eq!(Afloat::offer_ids_by_creator(4, bid_id), Some(()));
+    assert_eq!(Afloat::get_offers_by_tax_credit(tax_credit_id, None, MAX_PAGE_SIZE).len(), 4);
+    assert_eq!(Afloat::offer_ids_by_status(OfferStatus::MATCHED, bid_id), Some(()));
+    assert_eq!(Afloat::offer_ids_by_status(OfferStatus::MATCHED, ask_id), Some(()));
+    assert_eq!(Afloat::offer_ids_by_status(OfferStatus::CREATED, bid_id), None);
+
+    assert_ok!(Afloat::cancel_offer(RawOrigin::Signed(3).into(), cancelled_id));
+    Timestamp::set_timestamp(2_000_000);
+    Afloat::on_idle(1, Weight::MAX);
+
+    let ids_in = |status| -> Vec<StorageId> {
+      Afloat::get_offers_by_status(status, None, MAX_PAGE_SIZE)
+        .into_iter()
+        .map(|(offer_id, _)| offer_id)
+        .collect()
     };
+    assert_eq!(ids_in(OfferStatus::CANCELLED), vec![cancelled_id]);
+    assert_eq!(ids_in(OfferStatus::EXPIRED), vec![expired_id]);
+    assert!(ids_in(OfferStatus::CREATED).is_empty());
+    assert_eq!(ids_in(OfferStatus::MATCHED).len(), 2);
+    // The creator and tax credit indexes keep every offer
+    assert_eq!(Afloat::get_offers_by_creator(3, None, MAX_PAGE_SIZE).len(), 3);
+    assert_eq!(Afloat::get_offers_by_tax_credit(tax_credit_id, None, MAX_PAGE_SIZE).len(), 4);
+    assert!(Afloat::order_book(tax_credit_id).is_empty());
+  });
+}
 
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(user.clone()).into(), args.clone()));
-    assert_ok!(Afloat::sign_up(RawOrigin::Signed(other_user.clone()).into(), args.clone()));
+#[test]
+fn transaction_indexes_follow_the_transaction_status() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    deposit(4, 100000);
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+    let transaction_id = accept_offer(4, offer_id, Some(4));
+
+    assert_eq!(Afloat::transaction_ids_by_user(3, transaction_id), Some(()));
+    assert_eq!(Afloat::transaction_ids_by_user(4, transaction_id), Some(()));
+    assert_eq!(Afloat::transaction_ids_by_tax_credit(tax_credit_id, transaction_id), Some(()));
+    assert_eq!(Afloat::transaction_ids_by_status(OfferStatus::MATCHED, transaction_id), Some(()));
+
+    approve_transfer_form(transaction_id);
+    assert_eq!(Afloat::transaction_ids_by_status(OfferStatus::MATCHED, transaction_id), None);
+    assert_eq!(
+      Afloat::transaction_ids_by_status(OfferStatus::TF_AGENCY_APPROVED, transaction_id),
+      Some(())
+    );
 
-    assert_ok!(Afloat::set_afloat_balance(RuntimeOrigin::signed(1), 4, 100000));
+    assert_ok!(Afloat::approve_transaction(RawOrigin::Signed(2).into(), transaction_id));
+    assert_eq!(
+      Afloat::transaction_ids_by_status(OfferStatus::TF_AGENCY_APPROVED, transaction_id),
+      None
+    );
+    let settled = Afloat::get_transactions_by_status(OfferStatus::AFLOAT_APPROVED, None, 10);
+    assert_eq!(settled.len(), 1);
+    assert_eq!(settled[0].0, transaction_id);
+    assert_eq!(Afloat::get_transactions_by_tax_credit(tax_credit_id, None, 10).len(), 1);
+  });
+}
 
-    assert_ok!(Afloat::create_tax_credit(
-      RawOrigin::Signed(user.clone()).into(),
-      dummy_description(),
-      None,
-      None,
-    ));
+#[test]
+fn index_pages_are_bounded() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let offer_ids: Vec<StorageId> = (1..=3)
+      .map(|price| create_offer(3, sell_args(tax_credit_id, 10000 * price, 1)))
+      .collect();
+
+    let first_page = Afloat::get_offers_by_creator(3, None, 2);
+    assert_eq!(first_page.len(), 2);
+    let second_page = Afloat::get_offers_by_creator(3, Some(first_page[1].0), 2);
+    assert_eq!(second_page.len(), 1);
+    let mut paged: Vec<StorageId> = first_page
+      .iter()
+      .chain(second_page.iter())
+      .map(|(offer_id, _)| *offer_id)
+      .collect();
+    paged.sort();
+    let mut expected = offer_ids;
+    expected.sort();
+    assert_eq!(paged, expected);
+  });
+}
 
-    assert_ok!(Afloat::create_buy_order(
-      RawOrigin::Signed(other_user.clone()).into(),
-      item_id,
-      10000,
-      10,
-    ));
+#[test]
+fn create_offer_beyond_the_offers_per_creator_fails() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let offer_id = create_offer(4, buy_args(tax_credit_id, 1, 1));
+    for price in 2..=10 {
+      create_offer(4, buy_args(tax_credit_id, price, 1));
+    }
+    assert_eq!(Afloat::offer_count_by_creator(4), 10);
+    assert_eq!(Afloat::offer_count_by_tax_credit(tax_credit_id), 10);
 
-    let offer_id = GatedMarketplace::offers_by_item(0, 0).iter().next().unwrap().clone();
+    assert_noop!(
+      Afloat::create_offer(RawOrigin::Signed(4).into(), buy_args(tax_credit_id, 11, 1)),
+      Error::<Test>::TooManyOffers
+    );
+    // Cancelled offers stay in the indexes
+    assert_ok!(Afloat::cancel_offer(RawOrigin::Signed(4).into(), offer_id));
+    assert_noop!(
+      Afloat::create_offer(RawOrigin::Signed(4).into(), buy_args(tax_credit_id, 11, 1)),
+      Error::<Test>::TooManyOffers
+    );
+  });
+}
 
-    assert_ok!(Afloat::take_buy_order(RawOrigin::Signed(user.clone()).into(), offer_id,));
+#[test]
+fn accept_offer_beyond_the_transactions_per_user_fails() {
+  new_test_ext().execute_with(|| {
+    register(3, Jurisdiction::Federal);
+    register(4, Jurisdiction::Federal);
+    register_cpa(5);
+    let tax_credit_id = create_tax_credit(3, 20);
+    attest(5, 3, tax_credit_id);
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 20));
+    for _ in 0..10 {
+      accept_offer(4, offer_id, Some(1));
+    }
+    assert_eq!(Afloat::transaction_count_by_user(3), 10);
+    assert_eq!(Afloat::transaction_count_by_user(4), 10);
+    assert_eq!(Afloat::transaction_count_by_tax_credit(tax_credit_id), 10);
 
-    assert_eq!(Afloat::do_get_afloat_balance(user.clone()), 9800); // 10000 - 200 (buy fee)
-    assert_eq!(Afloat::do_get_afloat_balance(1), 200); // 200 (buy fee)
+    assert_noop!(
+      Afloat::accept_offer(RawOrigin::Signed(4).into(), offer_id, Some(1)),
+      Error::<Test>::TooManyTransactions
+    );
+    assert_eq!(Afloat::afloat_offers(offer_id).unwrap().tax_credit_amount_remaining, 10);
+  });
+}
+
+#[test]
+fn take_sell_order_works() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let collection_id = Afloat::collection_id().unwrap();
+    deposit(4, 100000);
+    let offer_id = create_offer(3, sell_args(tax_credit_id, 10000, 10));
+
+    assert_noop!(
+      Afloat::take_buy_order(RawOrigin::Signed(4).into(), offer_id),
+      Error::<Test>::InvalidOfferType
+    );
+    assert_ok!(Afloat::take_sell_order(RawOrigin::Signed(4).into(), offer_id));
+    let transaction_id = transaction_ids_of(4)[0];
+    assert_eq!(Afloat::afloat_transactions(transaction_id).unwrap().tax_credit_amount, 10);
+    assert_eq!(Afloat::afloat_offers(offer_id).unwrap().tax_credit_amount_remaining, 0);
+
+    approve_transfer_form(transaction_id);
+    assert_ok!(Afloat::approve_transaction(RawOrigin::Signed(2).into(), transaction_id));
+
+    assert_eq!(Uniques::owner(collection_id, tax_credit_id), Some(4));
+    let period = Afloat::fee_period();
+    assert_eq!(Afloat::fee_revenue(period), 4000);
+    assert_eq!(Afloat::traded_volume(period, 3), 100000);
+    assert_eq!(Afloat::traded_volume(period, 4), 0);
+    assert_eq!(Afloat::do_get_afloat_balance(1), 4000);
+  });
+}
+
+#[test]
+fn take_buy_order_works() {
+  new_test_ext().execute_with(|| {
+    let tax_credit_id = setup_trade();
+    let collection_id = Afloat::collection_id().unwrap();
+    deposit(4, 100000);
+    let offer_id = create_offer(4, buy_args(tax_credit_id, 5000, 5));
+
+    assert_noop!(
+      Afloat::take_sell_order(RawOrigin::Signed(3).into(), offer_id),
+      Error::<Test>::InvalidOfferType
+    );
+    assert_ok!(Afloat::take_buy_order(RawOrigin::Signed(3).into(), offer_id));
+    let transaction_id = transaction_ids_of(3)[0];
+    let transaction = Afloat::afloat_transactions(transaction_id).unwrap();
+    assert_eq!((transaction.seller_id, transaction.buyer_id), (3, 4));
+    assert_eq!(transaction.total_price, 25000);
+
+    approve_transfer_form(transaction_id);
+    let child_id = Fruniques::next_frunique(collection_id);
+    assert_ok!(Afloat::approve_transaction(RawOrigin::Signed(2).into(), transaction_id));
+
+    assert_eq!(Uniques::owner(collection_id, child_id), Some(4));
+    assert_eq!(Afloat::tax_credit_amount(child_id), 5);
+    let period = Afloat::fee_period();
+    assert_eq!(Afloat::fee_revenue(period), 1000);
+    assert_eq!(Afloat::traded_volume(period, 3), 25000);
+    assert_eq!(Afloat::do_get_afloat_balance(3), 24000);
+    assert_eq!(Afloat::do_get_afloat_balance(4), 75000);
   });
 }
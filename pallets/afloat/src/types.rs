@@ -1,6 +1,6 @@
 use super::*;
 use frame_support::{pallet_prelude::*, sp_io::hashing::blake2_256};
-use sp_runtime::sp_std::vec::Vec;
+use sp_runtime::{sp_std::vec::Vec, traits::Zero, PerThing, Permill};
 
 pub type ShortString = BoundedVec<u8, ConstU32<55>>;
 pub type LongString = BoundedVec<u8, ConstU32<255>>;
@@ -8,6 +8,9 @@ pub type Date = u64;
 pub type CollectionId = u32;
 pub type StorageId = [u8; 32];
 
+/// Maximum number of offers or transactions returned by a single page of the runtime API
+pub const MAX_PAGE_SIZE: u32 = 100;
+
 // ! User structures
 
 #[derive(CloneNoBound, Encode, Decode, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen, PartialEq)]
@@ -16,7 +19,7 @@ pub type StorageId = [u8; 32];
 pub struct User<T: Config> {
   pub cid: ShortString,
   pub cid_creator: ShortString,
-  pub group: ShortString, // only can be modified when the user is registered (can not be modified)
+  pub group: Jurisdiction, // only can be modified by an admin once the user is registered
   pub created_by: Option<T::AccountId>,
   pub created_date: Option<Date>,
   pub last_modified_by: Option<T::AccountId>,
@@ -27,7 +30,7 @@ impl<T: Config> User<T> {
   pub fn new(
     cid: ShortString,
     cid_creator: ShortString,
-    group: ShortString,
+    group: Jurisdiction,
     created_by: Option<T::AccountId>,
     created_date: Option<Date>,
     last_modified_by: Option<T::AccountId>,
@@ -42,16 +45,99 @@ impl<T: Config> User<T> {
 #[codec(mel_bound())]
 pub enum UpdateUserArgs {
   Edit { cid: ShortString, cid_creator: ShortString },
-  AdminEdit { cid: ShortString, cid_creator: ShortString, group: ShortString },
+  AdminEdit { cid: ShortString, cid_creator: ShortString, group: Jurisdiction },
   Delete,
 }
 
 #[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, TypeInfo)]
 #[scale_info(skip_type_params(T))]
 #[codec(mel_bound())]
-pub enum SignUpArgs {
-  BuyerOrSeller { cid: ShortString, cid_creator: ShortString, group: ShortString },
-  CPA { cid: ShortString, cid_creator: ShortString, group: ShortString },
+pub enum SignUpArgs<T: Config> {
+  // `fields` are the CIDs of the KYC documents reviewed by an admin before the user is enrolled
+  BuyerOrSeller {
+    cid: ShortString,
+    cid_creator: ShortString,
+    group: Jurisdiction,
+    fields: pallet_gated_marketplace::types::Fields<T>,
+  },
+  CPA {
+    cid: ShortString,
+    cid_creator: ShortString,
+    group: Jurisdiction,
+    fields: pallet_gated_marketplace::types::Fields<T>,
+  },
+}
+
+/// Jurisdiction of a user or a tax credit. Federal tax credits can be traded by every user, state
+/// tax credits only by the users of that state.
+#[derive(
+  Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, MaxEncodedLen, TypeInfo, Copy,
+)]
+pub enum Jurisdiction {
+  Federal,
+  Alabama,
+  Alaska,
+  Arizona,
+  Arkansas,
+  California,
+  Colorado,
+  Connecticut,
+  Delaware,
+  DistrictOfColumbia,
+  Florida,
+  Georgia,
+  Hawaii,
+  Idaho,
+  Illinois,
+  Indiana,
+  Iowa,
+  Kansas,
+  Kentucky,
+  Louisiana,
+  Maine,
+  Maryland,
+  Massachusetts,
+  Michigan,
+  Minnesota,
+  Mississippi,
+  Missouri,
+  Montana,
+  Nebraska,
+  Nevada,
+  NewHampshire,
+  NewJersey,
+  NewMexico,
+  NewYork,
+  NorthCarolina,
+  NorthDakota,
+  Ohio,
+  Oklahoma,
+  Oregon,
+  Pennsylvania,
+  RhodeIsland,
+  SouthCarolina,
+  SouthDakota,
+  Tennessee,
+  Texas,
+  Utah,
+  Vermont,
+  Virginia,
+  Washington,
+  WestVirginia,
+  Wisconsin,
+  Wyoming,
+}
+
+impl Default for Jurisdiction {
+  fn default() -> Self {
+    Jurisdiction::Federal
+  }
+}
+
+impl Jurisdiction {
+  pub fn can_trade(&self, tax_credit: Jurisdiction) -> bool {
+    tax_credit == Jurisdiction::Federal || tax_credit == *self
+  }
 }
 
 // ! Offer structures
@@ -67,11 +153,15 @@ pub enum OfferStatus {
   TF_AGENCY_SUBMITTED,
   TF_AGENCY_APPROVED,
   AFLOAT_APPROVED,
+  // New statuses go at the end, the index of the variants is part of the stored offers
+  CREATED,
+  CANCELLED,
+  EXPIRED,
 }
 
 impl Default for OfferStatus {
   fn default() -> Self {
-    OfferStatus::TF_PENDING_SIGNATURE
+    OfferStatus::CREATED
   }
 }
 
@@ -134,6 +224,16 @@ impl<T: Config> Offer<T> {
       offer_type,
     }
   }
+
+  /// Whether the offer still has tax credits that can be traded
+  pub fn is_open(&self) -> bool {
+    matches!(self.status, OfferStatus::CREATED | OfferStatus::MATCHED)
+      && self.tax_credit_amount_remaining > 0
+  }
+
+  pub fn is_expired(&self, now: Date) -> bool {
+    self.status == OfferStatus::EXPIRED || self.expiration_date <= now
+  }
 }
 
 #[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, TypeInfo)]
@@ -161,18 +261,117 @@ pub enum CreateOfferArgs<T: Config> {
 #[codec(mel_bound())]
 pub struct Transaction<T: Config> {
   pub tax_credit_amount: u32,
-  pub price_per_credit: u32,
-  pub total_price: u32,
-  pub fee: u32,
+  pub price_per_credit: T::Balance,
+  pub total_price: T::Balance,
+  pub fee: T::Balance,
   pub creation_date: Date,
   pub cancellation_date: Option<Date>,
-  pub tax_credit_id: u32,
+  pub tax_credit_id: <T as pallet_uniques::Config>::ItemId,
   pub seller_id: T::AccountId,
   pub buyer_id: T::AccountId,
   pub offer_id: StorageId,
-  pub marketplace_offer_id: StorageId,
+  // Offer that crossed `offer_id` in the order book, if the transaction was matched
+  pub matched_offer_id: Option<StorageId>,
+  pub marketplace_offer_id: Option<StorageId>,
   pub seller_confirmation_date: Option<Date>,
   pub buyer_confirmation_date: Option<Date>,
+  pub status: OfferStatus,
+  pub documents: TransferFormDocuments,
+}
+
+#[derive(
+  Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, MaxEncodedLen, TypeInfo, Default,
+)]
+pub struct TransferFormDocuments {
+  pub transfer_form_cid: Option<ShortString>,
+  pub seller_signature_cid: Option<ShortString>,
+  pub buyer_signature_cid: Option<ShortString>,
+  pub agency_submission_cid: Option<ShortString>,
+  pub agency_approval_cid: Option<ShortString>,
+}
+
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, TypeInfo)]
+pub enum CpaTransferFormArgs {
+  RequestSignatures,
+  SubmitToAgency { cid: ShortString },
+  AgencyApproved { cid: ShortString },
+}
+
+// ! Fiat ramp structures
+
+#[derive(
+  Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, MaxEncodedLen, TypeInfo, Copy,
+)]
+pub enum RampRequestType {
+  Deposit,
+  Withdrawal,
+}
+
+#[derive(
+  Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, MaxEncodedLen, TypeInfo, Copy,
+)]
+pub enum RampRequestStatus {
+  Pending,
+  Confirmed,
+  Rejected,
+}
+
+#[derive(CloneNoBound, Encode, Decode, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen, PartialEq)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct RampRequest<T: Config> {
+  pub request_type: RampRequestType,
+  pub amount: T::Balance,
+  pub bank_reference_cid: ShortString,
+  pub status: RampRequestStatus,
+  pub creation_date: Date,
+  pub reviewed_by: Option<T::AccountId>,
+  pub review_date: Option<Date>,
+}
+
+// ! Fee structures
+
+/// Maximum number of volume tiers in the fee schedule
+pub const MAX_FEE_TIERS: u32 = 10;
+
+#[derive(CloneNoBound, Encode, Decode, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen, PartialEq)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct FeeTier<T: Config> {
+  // Volume the seller must have traded in the current fee period for the tier to apply
+  pub volume: T::Balance,
+  pub percentage: Permill,
+}
+
+#[derive(CloneNoBound, Encode, Decode, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen, PartialEq)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct FeeSchedule<T: Config> {
+  pub percentage: Permill,
+  pub minimum: T::Balance,
+  // Sorted by volume, the highest tier reached replaces the base percentage
+  pub tiers: BoundedVec<FeeTier<T>, ConstU32<MAX_FEE_TIERS>>,
+}
+
+impl<T: Config> FeeSchedule<T> {
+  pub fn is_valid(&self) -> bool {
+    self.tiers.windows(2).all(|pair| pair[0].volume < pair[1].volume)
+  }
+
+  /// Fee of a sale of `amount` by a seller that already traded `volume` in the current period,
+  /// never more than the sale itself.
+  pub fn fee(&self, volume: T::Balance, amount: T::Balance) -> T::Balance {
+    if amount.is_zero() {
+      return Zero::zero();
+    }
+    let percentage = self
+      .tiers
+      .iter()
+      .rev()
+      .find(|tier| tier.volume <= volume)
+      .map_or(self.percentage, |tier| tier.percentage);
+    percentage.mul_floor(amount).max(self.minimum).min(amount)
+  }
 }
 
 // ! Roles structures
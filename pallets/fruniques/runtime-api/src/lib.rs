@@ -0,0 +1,26 @@
+//! Runtime API definition for the fruniques pallet
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use pallet_fruniques::types::{CollectionId, HistoryEntry, ItemId, Provenance};
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+  pub trait FruniquesApi<AccountId, BlockNumber>
+  where
+    AccountId: Codec,
+    BlockNumber: Codec,
+  {
+    /// Ancestor chain, descendant subtree and history of a frunique, `None` if it doesn't exist
+    fn provenance(
+      collection_id: CollectionId,
+      item_id: ItemId,
+    ) -> Option<Provenance<AccountId, CollectionId, ItemId, BlockNumber>>;
+
+    /// Recorded history of a frunique, oldest entries first
+    fn history(
+      collection_id: CollectionId,
+      item_id: ItemId,
+    ) -> Vec<HistoryEntry<AccountId, BlockNumber>>;
+  }
+}
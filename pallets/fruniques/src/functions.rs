@@ -1,15 +1,26 @@
 use super::*;
 
 use crate::types::*;
-use frame_support::{sp_io::hashing::blake2_256, traits::tokens::nonfungibles::Inspect};
+use frame_support::{
+  sp_io::{hashing::blake2_256, MultiRemovalResults},
+  storage::with_storage_layer,
+  traits::tokens::{fungibles, nonfungibles::Inspect},
+};
 use frame_system::pallet_prelude::*;
-use scale_info::prelude::string::String;
+use scale_info::prelude::{string::String, vec};
 
-use pallet_rbac::types::*;
+use pallet_rbac::{
+  purge::{PurgeProgress, StoragePurge},
+  types::*,
+};
 
 use frame_support::{pallet_prelude::*, traits::EnsureOriginWithArg, PalletId};
 // use frame_support::traits::OriginTrait;
-use sp_runtime::{sp_std::vec::Vec, traits::AccountIdConversion, Permill};
+use sp_runtime::{
+  sp_std::vec::Vec,
+  traits::{AccountIdConversion, Saturating, Zero},
+  Permill,
+};
 // use sp_runtime::traits::StaticLookup;
 
 impl<T: Config> Pallet<T> {
@@ -93,62 +104,41 @@ impl<T: Config> Pallet<T> {
   }
 
   // helper to initialize the roles for the RBAC module
+  // Creates the pallet roles and permissions. It can be called again after an upgrade, only the
+  // missing roles and permissions are added.
   pub fn do_initial_setup() -> DispatchResult {
     let pallet: IdOrVec = Self::pallet_id();
 
-    let owner_role_ids =
-      T::Rbac::create_and_set_roles(pallet.clone(), FruniqueRole::get_owner_roles())?;
-
-    for owner_role in owner_role_ids {
-      T::Rbac::create_and_set_permissions(
-        pallet.clone(),
-        owner_role,
-        Permission::owner_permissions(),
-      )?;
-    }
-
-    let admin_role_ids =
-      T::Rbac::create_and_set_roles(pallet.clone(), FruniqueRole::get_admin_roles())?;
-
-    for admin_role in admin_role_ids {
-      T::Rbac::create_and_set_permissions(
-        pallet.clone(),
-        admin_role,
-        Permission::admin_permissions(),
-      )?;
+    let roles = [
+      (FruniqueRole::get_owner_roles(), Permission::owner_permissions()),
+      (FruniqueRole::get_admin_roles(), Permission::admin_permissions()),
+      (FruniqueRole::get_collaborator_roles(), Permission::collaborator_permissions()),
+      (FruniqueRole::get_collector_roles(), Permission::collector_permissions()),
+      (FruniqueRole::get_holder_roles(), Permission::holder_permissions()),
+      (FruniqueRole::get_freezer_roles(), Permission::freezer_permissions()),
+      (FruniqueRole::get_verifier_roles(), Permission::verifier_permissions()),
+    ];
+
+    for (role_names, permissions) in roles {
+      for role in role_names {
+        Self::upsert_role(pallet.clone(), role, permissions.clone())?;
+      }
     }
 
-    let collaborator_role_ids =
-      T::Rbac::create_and_set_roles(pallet.clone(), FruniqueRole::get_collaborator_roles())?;
+    Ok(())
+  }
 
-    for collaborator_role in collaborator_role_ids {
-      T::Rbac::create_and_set_permissions(
-        pallet.clone(),
-        collaborator_role,
-        Permission::collaborator_permissions(),
-      )?;
+  fn upsert_role(pallet: IdOrVec, role: Vec<u8>, permissions: Vec<Vec<u8>>) -> DispatchResult {
+    let role_id = T::Rbac::create_role(role)?;
+    if T::Rbac::is_role_linked_to_pallet(pallet.clone(), &role_id).is_err() {
+      T::Rbac::set_role_to_pallet(pallet.clone(), role_id)?;
     }
 
-    let collector_role_ids =
-      T::Rbac::create_and_set_roles(pallet.clone(), FruniqueRole::get_collector_roles())?;
-
-    for collector_role in collector_role_ids {
-      T::Rbac::create_and_set_permissions(
-        pallet.clone(),
-        collector_role,
-        Permission::collector_permissions(),
-      )?;
-    }
-
-    let holder_role_ids =
-      T::Rbac::create_and_set_roles(pallet.clone(), FruniqueRole::get_holder_roles())?;
-
-    for holder_role in holder_role_ids {
-      T::Rbac::create_and_set_permissions(
-        pallet.clone(),
-        holder_role,
-        Permission::holder_permissions(),
-      )?;
+    for permission in permissions {
+      let permission_id = T::Rbac::create_permission(pallet.clone(), permission)?;
+      if T::Rbac::is_permission_linked_to_role(pallet.clone(), &role_id, &permission_id).is_err() {
+        T::Rbac::set_permission_to_role(pallet.clone(), role_id, permission_id)?;
+      }
     }
 
     Ok(())
@@ -206,31 +196,144 @@ impl<T: Config> Pallet<T> {
   }
 
   pub fn do_freeze(class_id: &T::CollectionId, instance_id: T::ItemId) -> DispatchResult {
-    <FruniqueInfo<T>>::try_mutate::<_, _, _, DispatchError, _>(
-      class_id,
-      instance_id,
-      |frunique_data| -> DispatchResult {
-        let frunique = frunique_data.as_mut().ok_or(Error::<T>::FruniqueNotFound)?;
-        frunique.frozen = true;
-        Ok(())
-      },
-    )?;
-    Ok(())
+    Self::set_frozen(*class_id, instance_id, true, None)
   }
 
   pub fn do_thaw(class_id: &T::CollectionId, instance_id: T::ItemId) -> DispatchResult {
+    Self::set_frozen(*class_id, instance_id, false, None)
+  }
+
+  fn set_frozen(
+    collection: T::CollectionId,
+    item: T::ItemId,
+    frozen: bool,
+    who: Option<T::AccountId>,
+  ) -> DispatchResult {
     <FruniqueInfo<T>>::try_mutate::<_, _, _, DispatchError, _>(
-      class_id,
-      instance_id,
+      collection,
+      item,
       |frunique_data| -> DispatchResult {
         let frunique = frunique_data.as_mut().ok_or(Error::<T>::FruniqueNotFound)?;
-        frunique.frozen = false;
+        frunique.frozen = frozen;
         Ok(())
       },
     )?;
+    if !frozen {
+      <FruniqueFreezes<T>>::remove(collection, item);
+    }
+    let action = if frozen { FruniqueAction::Frozen } else { FruniqueAction::Thawed };
+    Self::record_history(collection, item, action, who);
+    Ok(())
+  }
+
+  pub fn get_freeze_policy(collection: T::CollectionId) -> FreezePolicy<T> {
+    <FreezePolicies<T>>::get(collection).unwrap_or_default()
+  }
+
+  // Freezers are the global freezer account and the users with the freeze permission
+  pub fn is_freezer(user: T::AccountId, collection: T::CollectionId) -> bool {
+    Self::freezer() == Some(user.clone())
+      || Self::is_authorized(user, collection, Permission::Freeze).is_ok()
+  }
+
+  pub fn do_set_freeze_policy(
+    owner: T::AccountId,
+    collection: T::CollectionId,
+    policy: FreezePolicy<T>,
+  ) -> DispatchResult {
+    ensure!(Self::collection_exists(&collection), Error::<T>::CollectionNotFound);
+    ensure!(
+      pallet_uniques::Pallet::<T>::collection_owner(collection) == Some(owner),
+      Error::<T>::NotOwner
+    );
+    if let Some(max_lock_period) = policy.max_lock_period {
+      ensure!(!max_lock_period.is_zero(), Error::<T>::InvalidTimeLock);
+    }
+
+    <FreezePolicies<T>>::insert(collection, policy);
+
     Ok(())
   }
 
+  pub fn do_freeze_frunique(
+    who: T::AccountId,
+    collection: T::CollectionId,
+    item: T::ItemId,
+    thaw_at: Option<T::BlockNumber>,
+  ) -> DispatchResult {
+    ensure!(Self::instance_exists(&collection, &item), Error::<T>::FruniqueNotFound);
+
+    let frunique: FruniqueData<T> =
+      <FruniqueInfo<T>>::try_get(collection, item).map_err(|_| Error::<T>::NotAFrunique)?;
+    ensure!(!frunique.frozen, Error::<T>::FruniqueFrozen);
+    ensure!(!frunique.redeemed, Error::<T>::FruniqueAlreadyRedeemed);
+
+    let policy = Self::get_freeze_policy(collection);
+    ensure!(
+      Self::is_freezer(who.clone(), collection)
+        || (policy.owners_can_freeze && Self::admin_of(&collection, &item) == Some(who.clone())),
+      Error::<T>::NotAuthorized
+    );
+
+    let now = <frame_system::Pallet<T>>::block_number();
+    if let Some(thaw_at) = thaw_at {
+      ensure!(thaw_at > now, Error::<T>::InvalidTimeLock);
+    }
+    if let Some(max_lock_period) = policy.max_lock_period {
+      let thaw_at = thaw_at.ok_or(Error::<T>::InvalidTimeLock)?;
+      ensure!(thaw_at <= now.saturating_add(max_lock_period), Error::<T>::InvalidTimeLock);
+    }
+
+    if let Some(thaw_at) = thaw_at {
+      <ScheduledThaws<T>>::try_mutate(thaw_at, |scheduled| {
+        scheduled
+          .try_push((collection, item))
+          .map_err(|_| Error::<T>::TooManyThawsScheduled)
+      })?;
+    }
+
+    Self::set_frozen(collection, item, true, Some(who.clone()))?;
+    <FruniqueFreezes<T>>::insert(collection, item, FreezeInfo { frozen_by: who, thaw_at });
+
+    Ok(())
+  }
+
+  pub fn do_thaw_frunique(
+    who: T::AccountId,
+    collection: T::CollectionId,
+    item: T::ItemId,
+  ) -> DispatchResult {
+    let freeze_info =
+      <FruniqueFreezes<T>>::get(collection, item).ok_or(Error::<T>::FruniqueNotFrozen)?;
+
+    if !Self::is_freezer(who.clone(), collection) {
+      ensure!(freeze_info.frozen_by == who, Error::<T>::NotAuthorized);
+      ensure!(freeze_info.thaw_at.is_none(), Error::<T>::FruniqueTimeLocked);
+    }
+
+    if let Some(thaw_at) = freeze_info.thaw_at {
+      <ScheduledThaws<T>>::mutate(thaw_at, |scheduled| {
+        scheduled.retain(|frunique| *frunique != (collection, item))
+      });
+    }
+
+    Self::set_frozen(collection, item, false, Some(who))
+  }
+
+  // Thaws the fruniques whose time lock expires at the given block, returns how many were thawed
+  pub fn do_thaw_expired(now: T::BlockNumber) -> u32 {
+    let mut thawed = 0;
+    for (collection, item) in <ScheduledThaws<T>>::take(now) {
+      let expired = <FruniqueFreezes<T>>::get(collection, item)
+        .map_or(false, |freeze_info| freeze_info.thaw_at == Some(now));
+      if expired && Self::set_frozen(collection, item, false, None).is_ok() {
+        Self::deposit_event(Event::FruniqueAutoThawed(collection, item));
+        thawed += 1;
+      }
+    }
+    thawed
+  }
+
   pub fn burn(
     origin: OriginFor<T>,
     class_id: &T::CollectionId,
@@ -261,7 +364,8 @@ impl<T: Config> Pallet<T> {
     let next_collection: u32 = Self::next_collection();
     let class_id = Self::u32_to_class_id(next_collection);
 
-    let owner = T::CreateOrigin::ensure_origin(origin.clone(), &class_id)?;
+    let owner =
+      <T as pallet_uniques::Config>::CreateOrigin::ensure_origin(origin.clone(), &class_id)?;
 
     let scope_id = class_id.using_encoded(blake2_256);
     T::Rbac::create_scope(Self::pallet_id(), scope_id)?;
@@ -302,6 +406,8 @@ impl<T: Config> Pallet<T> {
   {
     ensure!(Self::collection_exists(&collection), Error::<T>::CollectionNotFound);
 
+    let attributes = Self::apply_attribute_schema(collection, attributes, parent_info.as_ref())?;
+
     let nex_item: ItemId = <NextFrunique<T>>::try_get(collection).unwrap_or(0);
     let item = Self::u32_to_instance_id(nex_item);
 
@@ -325,10 +431,41 @@ impl<T: Config> Pallet<T> {
 
     <FruniqueInfo<T>>::insert(collection, item, frunique_data);
     <FruniqueRoots<T>>::insert(collection, item, true);
+    Self::record_history(collection, item, FruniqueAction::Spawned, Some(owner));
 
     Ok(())
   }
 
+  // Spawns root fruniques with consecutive ids, either all of them are spawned or none is
+  pub fn do_spawn_batch(
+    collection: T::CollectionId,
+    owner: T::AccountId,
+    metadata: CollectionDescription<T>,
+    items_attributes: Vec<Option<Attributes<T>>>,
+  ) -> Result<T::ItemId, DispatchError>
+  where
+    <T as pallet_uniques::Config>::ItemId: From<u32>,
+  {
+    ensure!(Self::collection_exists(&collection), Error::<T>::CollectionNotFound);
+    ensure!(!items_attributes.is_empty(), Error::<T>::EmptyBatch);
+    ensure!(items_attributes.len() as u32 <= T::MaxBatchSpawn::get(), Error::<T>::BatchTooLarge);
+
+    let first_item: ItemId = <NextFrunique<T>>::try_get(collection).unwrap_or(0);
+    let last_item = first_item
+      .checked_add(items_attributes.len() as u32)
+      .ok_or(Error::<T>::FruniqueCntOverflow)?;
+
+    with_storage_layer(|| -> DispatchResult {
+      for attributes in items_attributes {
+        Self::do_spawn(collection, owner.clone(), metadata.clone(), attributes, None)?;
+      }
+      ensure!(<NextFrunique<T>>::get(collection) == last_item, Error::<T>::FruniqueCntOverflow);
+      Ok(())
+    })?;
+
+    Ok(Self::u32_to_instance_id(first_item))
+  }
+
   // Takes cares of the division of the NFT
   pub fn do_nft_division(
     collection: T::CollectionId,
@@ -361,19 +498,26 @@ impl<T: Config> Pallet<T> {
       is_hierarchical: parent_info.is_hierarchical,
     };
 
+    // Children of a verified parent inherit its verification when the policy allows it
+    let inherits_verification = frunique_parent.verified
+      && Self::get_verification_policy(parent_info.collection_id).propagate_to_children;
+
     let frunique_data: FruniqueData<T> = FruniqueData {
       metadata,
       weight: Self::percent_to_permill(100),
       parent: Some(parent_data),
       children: None,
-      verified: false,
+      verified: inherits_verification,
       frozen: false,
       redeemed: false,
       spawned_by: Some(user.clone()),
-      verified_by: None,
+      verified_by: if inherits_verification { frunique_parent.verified_by.clone() } else { None },
     };
 
     <FruniqueInfo<T>>::insert(collection, item, frunique_data);
+    if inherits_verification {
+      <FruniqueVerified<T>>::insert(collection, item, true);
+    }
 
     let frunique_child: ChildInfo<T> = ChildInfo {
       collection_id: collection,
@@ -403,6 +547,520 @@ impl<T: Config> Pallet<T> {
       },
     )?;
 
+    Self::record_history(collection, item, FruniqueAction::Spawned, Some(user.clone()));
+    Self::record_history(
+      parent_info.collection_id,
+      parent_info.parent_id,
+      FruniqueAction::Divided,
+      Some(user),
+    );
+
+    Ok(())
+  }
+
+  // Takes care of recombining children fruniques back into their parent
+  pub fn do_merge(
+    owner: T::AccountId,
+    collection: T::CollectionId,
+    item: T::ItemId,
+    children: Option<Vec<(T::CollectionId, T::ItemId)>>,
+  ) -> DispatchResult {
+    ensure!(Self::collection_exists(&collection), Error::<T>::CollectionNotFound);
+    ensure!(Self::instance_exists(&collection, &item), Error::<T>::FruniqueNotFound);
+    ensure!(Self::admin_of(&collection, &item) == Some(owner.clone()), Error::<T>::NotOwner);
+
+    let frunique_parent: FruniqueData<T> =
+      <FruniqueInfo<T>>::try_get(collection, item).map_err(|_| Error::<T>::NotAFrunique)?;
+
+    ensure!(!frunique_parent.frozen, Error::<T>::FruniqueFrozen);
+    ensure!(!frunique_parent.redeemed, Error::<T>::FruniqueAlreadyRedeemed);
+
+    let current_children = frunique_parent.children.unwrap_or_default();
+    ensure!(!current_children.is_empty(), Error::<T>::NoChildrenToMerge);
+
+    // When no children are given, all of them are merged back
+    let children_to_merge: Vec<(T::CollectionId, T::ItemId)> = match children {
+      Some(children) => children,
+      None => current_children
+        .iter()
+        .map(|child| (child.collection_id, child.child_id))
+        .collect(),
+    };
+    ensure!(!children_to_merge.is_empty(), Error::<T>::NoChildrenToMerge);
+
+    let mut restored_weight = Self::percent_to_permill(0);
+    for (child_collection, child_item) in children_to_merge.iter() {
+      let child_info = current_children
+        .iter()
+        .find(|child| child.collection_id == *child_collection && child.child_id == *child_item)
+        .ok_or(Error::<T>::ChildNotFound)?;
+      ensure!(child_info.is_hierarchical, Error::<T>::ChildNotHierarchical);
+
+      ensure!(
+        Self::admin_of(child_collection, child_item) == Some(owner.clone()),
+        Error::<T>::NotOwner
+      );
+
+      let frunique_child: FruniqueData<T> =
+        <FruniqueInfo<T>>::try_get(child_collection, child_item)
+          .map_err(|_| Error::<T>::FruniqueNotFound)?;
+
+      ensure!(!frunique_child.frozen, Error::<T>::FruniqueFrozen);
+      ensure!(!frunique_child.redeemed, Error::<T>::FruniqueAlreadyRedeemed);
+      // Burning a leased child would end the lease of its user
+      ensure!(
+        Self::get_rental(*child_collection, *child_item).is_none(),
+        Error::<T>::FruniqueRented
+      );
+      // A child that was divided must be merged first
+      ensure!(
+        frunique_child.children.map_or(true, |children| children.is_empty()),
+        Error::<T>::ChildAlreadyDivided
+      );
+      ensure!(
+        frunique_child.weight == Self::percent_to_permill(100),
+        Error::<T>::ChildAlreadyDivided
+      );
+
+      let parent_data = frunique_child.parent.ok_or(Error::<T>::ChildNotFound)?;
+      ensure!(
+        parent_data.collection_id == collection
+          && parent_data.parent_id == item
+          && parent_data.parent_weight == child_info.weight_inherited,
+        Error::<T>::ChildNotFound
+      );
+
+      restored_weight = restored_weight.saturating_add(child_info.weight_inherited);
+    }
+
+    for (child_collection, child_item) in children_to_merge.iter() {
+      pallet_uniques::Pallet::<T>::do_burn(*child_collection, *child_item, |_, _| Ok(()))?;
+      <FruniqueInfo<T>>::remove(child_collection, child_item);
+      <FruniqueRoots<T>>::remove(child_collection, child_item);
+      <FruniqueVerified<T>>::remove(child_collection, child_item);
+      <FruniqueRedeemed<T>>::remove(child_collection, child_item);
+      <FruniqueAttestations<T>>::remove(child_collection, child_item);
+      <FruniqueHistory<T>>::remove(child_collection, child_item);
+      <FruniqueFreezes<T>>::remove(child_collection, child_item);
+      <FruniqueRentals<T>>::remove(child_collection, child_item);
+    }
+
+    <FruniqueInfo<T>>::try_mutate::<_, _, _, DispatchError, _>(
+      collection,
+      item,
+      |frunique_data| -> DispatchResult {
+        let frunique = frunique_data.as_mut().ok_or(Error::<T>::FruniqueNotFound)?;
+        if let Some(children) = frunique.children.as_mut() {
+          children
+            .retain(|child| !children_to_merge.contains(&(child.collection_id, child.child_id)));
+        }
+        if frunique.children.as_ref().map_or(false, |children| children.is_empty()) {
+          frunique.children = None;
+        }
+        frunique.weight = frunique.weight.saturating_add(restored_weight);
+        Ok(())
+      },
+    )?;
+
+    Self::record_history(collection, item, FruniqueAction::Merged, Some(owner));
+
+    Ok(())
+  }
+
+  // Locks a frunique in the pallet account and mints its shares as a fungible asset
+  pub fn do_fractionalize(
+    owner: T::AccountId,
+    collection: T::CollectionId,
+    item: T::ItemId,
+    asset_id: FractionAssetId<T>,
+    supply: FractionBalance<T>,
+  ) -> DispatchResult {
+    ensure!(Self::collection_exists(&collection), Error::<T>::CollectionNotFound);
+    ensure!(Self::instance_exists(&collection, &item), Error::<T>::FruniqueNotFound);
+    ensure!(Self::admin_of(&collection, &item) == Some(owner.clone()), Error::<T>::NotOwner);
+    ensure!(
+      !<FungibleFractions<T>>::contains_key(collection, item),
+      Error::<T>::FruniqueAlreadyFractionalized
+    );
+    ensure!(!supply.is_zero(), Error::<T>::InvalidFractionSupply);
+
+    let frunique: FruniqueData<T> =
+      <FruniqueInfo<T>>::try_get(collection, item).map_err(|_| Error::<T>::NotAFrunique)?;
+
+    ensure!(!frunique.frozen, Error::<T>::FruniqueFrozen);
+    ensure!(!frunique.redeemed, Error::<T>::FruniqueAlreadyRedeemed);
+    ensure!(
+      frunique.children.map_or(true, |children| children.is_empty()),
+      Error::<T>::FruniqueHasChildren
+    );
+
+    let pallet_account = Self::pallet_account();
+    pallet_uniques::Pallet::<T>::do_transfer(collection, item, pallet_account.clone(), |_, _| {
+      Ok(())
+    })?;
+    Self::do_freeze(&collection, item)?;
+
+    <pallet_mapped_assets::Pallet<T> as fungibles::Create<T::AccountId>>::create(
+      asset_id,
+      pallet_account,
+      false,
+      <FractionBalance<T>>::from(1u32),
+    )?;
+    <pallet_mapped_assets::Pallet<T> as fungibles::Mutate<T::AccountId>>::mint_into(
+      asset_id, &owner, supply,
+    )?;
+
+    <FungibleFractions<T>>::insert(
+      collection,
+      item,
+      FungibleFractionData { asset_id, total_supply: supply, locked_by: owner.clone() },
+    );
+    Self::record_history(collection, item, FruniqueAction::Fractionalized, Some(owner));
+
+    Ok(())
+  }
+
+  // Burns the full supply of fungible shares and gives the frunique back to the redeemer
+  pub fn do_redeem_fractions(
+    who: T::AccountId,
+    collection: T::CollectionId,
+    item: T::ItemId,
+  ) -> Result<FractionAssetId<T>, DispatchError> {
+    let fractions: FungibleFractionData<T> =
+      <FungibleFractions<T>>::get(collection, item).ok_or(Error::<T>::FruniqueNotFractionalized)?;
+
+    let balance = <pallet_mapped_assets::Pallet<T> as fungibles::Inspect<T::AccountId>>::balance(
+      fractions.asset_id,
+      &who,
+    );
+    let total_issuance =
+      <pallet_mapped_assets::Pallet<T> as fungibles::Inspect<T::AccountId>>::total_issuance(
+        fractions.asset_id,
+      );
+    ensure!(
+      balance == fractions.total_supply && total_issuance == fractions.total_supply,
+      Error::<T>::IncompleteFractionSupply
+    );
+
+    <pallet_mapped_assets::Pallet<T> as fungibles::Mutate<T::AccountId>>::burn_from(
+      fractions.asset_id,
+      &who,
+      balance,
+    )?;
+    Self::destroy_fraction_asset(fractions.asset_id)?;
+
+    Self::do_thaw(&collection, item)?;
+    pallet_uniques::Pallet::<T>::do_transfer(collection, item, who.clone(), |_, _| Ok(()))?;
+
+    <FungibleFractions<T>>::remove(collection, item);
+    Self::record_history(collection, item, FruniqueAction::FractionsRedeemed, Some(who));
+
+    Ok(fractions.asset_id)
+  }
+
+  // The whole supply is burned by now, so only accounts that were left with a zero balance and
+  // stale approvals can remain. If there are more than `FRACTION_DESTROY_LIMIT` of them the asset
+  // stays in the destroying state and can be finished through the mapped-assets extrinsics.
+  fn destroy_fraction_asset(asset_id: FractionAssetId<T>) -> DispatchResult {
+    type Assets<T> = pallet_mapped_assets::Pallet<T>;
+    <Assets<T> as fungibles::Destroy<T::AccountId>>::start_destroy(asset_id, None)?;
+    <Assets<T> as fungibles::Destroy<T::AccountId>>::destroy_accounts(
+      asset_id,
+      FRACTION_DESTROY_LIMIT,
+    )?;
+    <Assets<T> as fungibles::Destroy<T::AccountId>>::destroy_approvals(
+      asset_id,
+      FRACTION_DESTROY_LIMIT,
+    )?;
+    let _ = <Assets<T> as fungibles::Destroy<T::AccountId>>::finish_destroy(asset_id);
+    Ok(())
+  }
+
+  pub fn get_verification_policy(collection: T::CollectionId) -> VerificationPolicy<T> {
+    <VerificationPolicies<T>>::get(collection).unwrap_or_default()
+  }
+
+  // Checks if a user can attest or revoke fruniques of a collection
+  pub fn is_verifier(user: T::AccountId, collection: T::CollectionId) -> bool {
+    let policy = Self::get_verification_policy(collection);
+    if policy.verifiers.is_empty() {
+      return Self::is_authorized(user, collection, Permission::Verify).is_ok();
+    }
+    policy.verifiers.contains(&user)
+  }
+
+  pub fn do_set_verification_policy(
+    owner: T::AccountId,
+    collection: T::CollectionId,
+    policy: VerificationPolicy<T>,
+  ) -> DispatchResult {
+    ensure!(Self::collection_exists(&collection), Error::<T>::CollectionNotFound);
+    ensure!(
+      pallet_uniques::Pallet::<T>::collection_owner(collection) == Some(owner),
+      Error::<T>::NotOwner
+    );
+
+    ensure!(policy.required_approvals > 0, Error::<T>::InvalidVerificationPolicy);
+    ensure!(
+      policy.required_approvals <= T::MaxVerifiers::get(),
+      Error::<T>::InvalidVerificationPolicy
+    );
+    if !policy.verifiers.is_empty() {
+      ensure!(
+        policy.required_approvals as usize <= policy.verifiers.len(),
+        Error::<T>::InvalidVerificationPolicy
+      );
+      // Verifiers must be allowed to verify in the collection
+      for verifier in policy.verifiers.iter() {
+        ensure!(
+          Self::is_authorized(verifier.clone(), collection, Permission::Verify).is_ok(),
+          Error::<T>::UserNotInCollection
+        );
+      }
+    }
+
+    <VerificationPolicies<T>>::insert(collection, policy);
+
+    Ok(())
+  }
+
+  // Records an attestation and verifies the frunique once the policy threshold is reached
+  pub fn do_attest(
+    verifier: T::AccountId,
+    collection: T::CollectionId,
+    item: T::ItemId,
+    evidence: Option<CID>,
+  ) -> DispatchResult {
+    ensure!(Self::instance_exists(&collection, &item), Error::<T>::FruniqueNotFound);
+    ensure!(Self::is_verifier(verifier.clone(), collection), Error::<T>::NotAuthorized);
+
+    let frunique: FruniqueData<T> =
+      <FruniqueInfo<T>>::try_get(collection, item).map_err(|_| Error::<T>::FruniqueNotFound)?;
+    ensure!(!frunique.verified, Error::<T>::FruniqueAlreadyVerified);
+
+    let attestations = <FruniqueAttestations<T>>::try_mutate::<_, _, _, DispatchError, _>(
+      collection,
+      item,
+      |attestations| {
+        ensure!(
+          !attestations.iter().any(|attestation| attestation.verifier == verifier),
+          Error::<T>::AlreadyAttested
+        );
+        attestations
+          .try_push(Attestation {
+            verifier: verifier.clone(),
+            evidence,
+            attested_at: <frame_system::Pallet<T>>::block_number(),
+          })
+          .map_err(|_| Error::<T>::MaxVerifiersReached)?;
+        Ok(attestations.len() as u32)
+      },
+    )?;
+
+    Self::record_history(collection, item, FruniqueAction::Attested, Some(verifier.clone()));
+    Self::deposit_event(Event::FruniqueAttested(verifier.clone(), collection, item));
+
+    if attestations < Self::get_verification_policy(collection).required_approvals {
+      return Ok(());
+    }
+
+    <FruniqueInfo<T>>::try_mutate::<_, _, _, DispatchError, _>(
+      collection,
+      item,
+      |frunique_data| -> DispatchResult {
+        let frunique = frunique_data.as_mut().ok_or(Error::<T>::FruniqueNotFound)?;
+        frunique.verified = true;
+        frunique.verified_by = Some(verifier.clone());
+        Ok(())
+      },
+    )?;
+
+    <FruniqueVerified<T>>::insert(collection, item, true);
+
+    Self::record_history(collection, item, FruniqueAction::Verified, Some(verifier.clone()));
+    Self::deposit_event(Event::FruniqueVerified(verifier, collection, item));
+
+    Ok(())
+  }
+
+  // Clears the verification of a frunique and of the descendants that inherited it, failing if
+  // more than `max_descendants` of them would be revoked
+  pub fn do_revoke_verification(
+    who: T::AccountId,
+    collection: T::CollectionId,
+    item: T::ItemId,
+    max_descendants: u32,
+  ) -> DispatchResult {
+    let has_attestations = !<FruniqueAttestations<T>>::get(collection, item).is_empty();
+    let frunique: FruniqueData<T> =
+      <FruniqueInfo<T>>::try_get(collection, item).map_err(|_| Error::<T>::FruniqueNotFound)?;
+    ensure!(frunique.verified || has_attestations, Error::<T>::FruniqueNotVerified);
+
+    let mut pending = vec![(collection, item)];
+    let mut revoked_descendants: u32 = 0;
+    while let Some((revoked_collection, revoked_item)) = pending.pop() {
+      let children = Self::clear_verification(&who, revoked_collection, revoked_item)?;
+
+      // Children without attestations of their own got their verification from the parent
+      for (child_collection, child_item) in children {
+        let inherited = <FruniqueInfo<T>>::get(child_collection, child_item)
+          .map_or(false, |child| child.verified)
+          && <FruniqueAttestations<T>>::get(child_collection, child_item).is_empty();
+        if inherited {
+          revoked_descendants = revoked_descendants.saturating_add(1);
+          ensure!(revoked_descendants <= max_descendants, Error::<T>::TooManyDescendants);
+          pending.push((child_collection, child_item));
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  // Removes the verification data of a single frunique and returns its children
+  fn clear_verification(
+    who: &T::AccountId,
+    collection: T::CollectionId,
+    item: T::ItemId,
+  ) -> Result<Vec<(T::CollectionId, T::ItemId)>, DispatchError> {
+    let children =
+      <FruniqueInfo<T>>::try_mutate::<_, _, Vec<(T::CollectionId, T::ItemId)>, DispatchError, _>(
+        collection,
+        item,
+        |frunique_data| {
+          let frunique = frunique_data.as_mut().ok_or(Error::<T>::FruniqueNotFound)?;
+          frunique.verified = false;
+          frunique.verified_by = None;
+          Ok(
+            frunique
+              .children
+              .as_ref()
+              .map(|children| {
+                children.iter().map(|child| (child.collection_id, child.child_id)).collect()
+              })
+              .unwrap_or_default(),
+          )
+        },
+      )?;
+
+    <FruniqueAttestations<T>>::remove(collection, item);
+    <FruniqueVerified<T>>::remove(collection, item);
+
+    Self::record_history(collection, item, FruniqueAction::VerificationRevoked, Some(who.clone()));
+    Self::deposit_event(Event::FruniqueVerificationRevoked(who.clone(), collection, item));
+
+    Ok(children)
+  }
+
+  pub fn do_set_attribute_schema(
+    owner: T::AccountId,
+    collection: T::CollectionId,
+    schema: AttributeSchema<T>,
+  ) -> DispatchResult {
+    ensure!(Self::collection_exists(&collection), Error::<T>::CollectionNotFound);
+    ensure!(
+      pallet_uniques::Pallet::<T>::collection_owner(collection) == Some(owner),
+      Error::<T>::NotOwner
+    );
+
+    for (index, field) in schema.iter().enumerate() {
+      ensure!(
+        !schema.iter().skip(index + 1).any(|other| other.key == field.key),
+        Error::<T>::InvalidAttributeSchema
+      );
+      if let AttributeType::Enum(options) = &field.value_type {
+        ensure!(!options.is_empty(), Error::<T>::InvalidAttributeSchema);
+      }
+    }
+
+    <AttributeSchemas<T>>::insert(collection, schema);
+
+    Ok(())
+  }
+
+  // Schema a frunique follows: the one of its collection or, if missing, the one of its parent
+  pub fn get_attribute_schema(
+    collection: T::CollectionId,
+    parent_info: Option<ParentInfo<T>>,
+  ) -> Option<AttributeSchema<T>> {
+    if let Some(schema) = <AttributeSchemas<T>>::get(collection) {
+      return Some(schema);
+    }
+    let parent_info = parent_info?;
+    let grandparent_info = <FruniqueInfo<T>>::get(parent_info.collection_id, parent_info.parent_id)
+      .and_then(|parent| parent.parent);
+    Self::get_attribute_schema(parent_info.collection_id, grandparent_info)
+  }
+
+  // Validates the attributes of a new frunique, inheriting the missing values from its parent
+  pub fn apply_attribute_schema(
+    collection: T::CollectionId,
+    attributes: Option<Attributes<T>>,
+    parent_info: Option<&ParentInfo<T>>,
+  ) -> Result<Option<Attributes<T>>, DispatchError> {
+    let schema = match Self::get_attribute_schema(collection, parent_info.cloned()) {
+      Some(schema) => schema,
+      None => return Ok(attributes),
+    };
+
+    let mut attributes = attributes.unwrap_or_default();
+    for field in schema.iter() {
+      if attributes.iter().any(|(key, _)| *key == field.key) {
+        continue;
+      }
+      let inherited = parent_info.and_then(|parent_info| {
+        pallet_uniques::Pallet::<T>::attribute(
+          &parent_info.collection_id,
+          &parent_info.parent_id,
+          &field.key,
+        )
+      });
+      match inherited {
+        Some(value) => attributes.push((
+          field.key.clone(),
+          AttributeValue::<T>::try_from(value).map_err(|_| Error::<T>::ValueTooLong)?,
+        )),
+        None => ensure!(!field.required, Error::<T>::MissingRequiredAttribute),
+      }
+    }
+
+    for (key, value) in attributes.iter() {
+      let field = schema
+        .iter()
+        .find(|field| field.key == *key)
+        .ok_or(Error::<T>::AttributeNotInSchema)?;
+      ensure!(field.value_type.is_valid(value), Error::<T>::InvalidAttributeValue);
+    }
+
+    Ok(if attributes.is_empty() { None } else { Some(attributes) })
+  }
+
+  // Validates new values for the attributes of an existing frunique
+  pub fn validate_attributes_update(
+    collection: T::CollectionId,
+    item: T::ItemId,
+    attributes: &Attributes<T>,
+  ) -> DispatchResult {
+    let parent_info = <FruniqueInfo<T>>::get(collection, item).and_then(|frunique| frunique.parent);
+    let schema = match Self::get_attribute_schema(collection, parent_info) {
+      Some(schema) => schema,
+      None => return Ok(()),
+    };
+
+    for (key, value) in attributes.iter() {
+      let field = schema
+        .iter()
+        .find(|field| field.key == *key)
+        .ok_or(Error::<T>::AttributeNotInSchema)?;
+      ensure!(field.value_type.is_valid(value), Error::<T>::InvalidAttributeValue);
+      if !field.mutable {
+        ensure!(
+          pallet_uniques::Pallet::<T>::attribute(&collection, &item, key).is_none(),
+          Error::<T>::ImmutableAttribute
+        );
+      }
+    }
+
     Ok(())
   }
 
@@ -431,10 +1089,93 @@ impl<T: Config> Pallet<T> {
     )?;
 
     <FruniqueRedeemed<T>>::insert(collection, item, true);
+    Self::record_history(collection, item, FruniqueAction::Redeemed, None);
 
     Ok(())
   }
 
+  // Appends an entry to the history of a frunique, dropping the oldest one when it's full
+  pub fn record_history(
+    collection: T::CollectionId,
+    item: T::ItemId,
+    action: FruniqueAction,
+    who: Option<T::AccountId>,
+  ) {
+    <FruniqueHistory<T>>::mutate(collection, item, |history| {
+      if history.is_full() && !history.is_empty() {
+        history.remove(0);
+      }
+      let _ = history.try_push(HistoryEntry {
+        action,
+        who,
+        block_number: <frame_system::Pallet<T>>::block_number(),
+      });
+    });
+  }
+
+  fn provenance_node(collection: T::CollectionId, item: T::ItemId) -> Option<ProvenanceNodeOf<T>> {
+    let frunique = <FruniqueInfo<T>>::get(collection, item)?;
+    Some(ProvenanceNode {
+      collection_id: collection,
+      item_id: item,
+      parent: frunique.parent.as_ref().map(|parent| (parent.collection_id, parent.parent_id)),
+      weight: frunique.weight,
+      weight_inherited: frunique.parent.as_ref().map(|parent| parent.parent_weight),
+      owner: Self::admin_of(&collection, &item),
+      verified: frunique.verified,
+      verified_by: frunique.verified_by,
+      frozen: frunique.frozen,
+      redeemed: frunique.redeemed,
+    })
+  }
+
+  /// Builds the ancestor chain and the descendant subtree of a frunique, used by the runtime API
+  pub fn get_provenance(collection: T::CollectionId, item: T::ItemId) -> Option<ProvenanceOf<T>> {
+    let frunique = Self::provenance_node(collection, item)?;
+
+    let mut ancestors = Vec::new();
+    let mut next = frunique.parent;
+    while let Some((parent_collection, parent_item)) = next {
+      match Self::provenance_node(parent_collection, parent_item) {
+        Some(parent) => {
+          next = parent.parent;
+          ancestors.push(parent);
+        },
+        None => break,
+      }
+    }
+
+    // Depth first, so every parent is listed before its children
+    let mut descendants = Vec::new();
+    let mut pending = Self::children_of(collection, item);
+    pending.reverse();
+    while let Some((child_collection, child_item)) = pending.pop() {
+      if let Some(child) = Self::provenance_node(child_collection, child_item) {
+        let mut grandchildren = Self::children_of(child_collection, child_item);
+        grandchildren.reverse();
+        pending.append(&mut grandchildren);
+        descendants.push(child);
+      }
+    }
+
+    Some(Provenance {
+      frunique,
+      ancestors,
+      descendants,
+      history: <FruniqueHistory<T>>::get(collection, item).into_inner(),
+    })
+  }
+
+  fn children_of(
+    collection: T::CollectionId,
+    item: T::ItemId,
+  ) -> Vec<(T::CollectionId, T::ItemId)> {
+    <FruniqueInfo<T>>::get(collection, item)
+      .and_then(|frunique| frunique.children)
+      .map(|children| children.iter().map(|child| (child.collection_id, child.child_id)).collect())
+      .unwrap_or_default()
+  }
+
   pub fn get_nft_metadata(
     collection: T::CollectionId,
     item: T::ItemId,
@@ -488,6 +1229,183 @@ impl<T: Config> Pallet<T> {
     Ok(())
   }
 
+  // Only the collection owner can handle admins, and owners are only set on collection creation
+  fn ensure_can_manage_role(
+    who: T::AccountId,
+    collection: T::CollectionId,
+    role: FruniqueRole,
+  ) -> DispatchResult {
+    ensure!(Self::collection_exists(&collection), Error::<T>::CollectionNotFound);
+    ensure!(role != FruniqueRole::Owner, Error::<T>::InvalidRole);
+    if role == FruniqueRole::Admin {
+      ensure!(
+        pallet_uniques::Pallet::<T>::collection_owner(collection) == Some(who),
+        Error::<T>::NotOwner
+      );
+      return Ok(());
+    }
+    ensure!(
+      Self::is_authorized(who, collection, Permission::InviteCollaborator).is_ok(),
+      Error::<T>::NoPermission
+    );
+    Ok(())
+  }
+
+  pub fn do_invite(
+    who: T::AccountId,
+    collection: T::CollectionId,
+    invitee: T::AccountId,
+    role: FruniqueRole,
+  ) -> DispatchResult {
+    Self::ensure_can_manage_role(who.clone(), collection, role)?;
+
+    let now = <frame_system::Pallet<T>>::block_number();
+    if let Some(invitation) = <Invitations<T>>::get(collection, &invitee) {
+      ensure!(
+        invitation.status_at(now) != InvitationStatus::Pending,
+        Error::<T>::InvitationAlreadyPending
+      );
+    }
+
+    <Invitations<T>>::insert(
+      collection,
+      invitee,
+      Invitation {
+        role,
+        invited_by: who,
+        expires_at: now.saturating_add(T::InvitationExpiration::get()),
+        status: InvitationStatus::Pending,
+      },
+    );
+
+    Ok(())
+  }
+
+  pub fn do_accept_invitation(
+    invitee: T::AccountId,
+    collection: T::CollectionId,
+  ) -> Result<FruniqueRole, DispatchError> {
+    let now = <frame_system::Pallet<T>>::block_number();
+    let role = <Invitations<T>>::try_mutate::<_, _, _, DispatchError, _>(
+      collection,
+      &invitee,
+      |invitation| {
+        let invitation = invitation.as_mut().ok_or(Error::<T>::InvitationNotFound)?;
+        match invitation.status_at(now) {
+          InvitationStatus::Pending => {},
+          InvitationStatus::Expired => return Err(Error::<T>::InvitationExpired.into()),
+          _ => return Err(Error::<T>::InvitationNotFound.into()),
+        }
+        invitation.status = InvitationStatus::Accepted;
+        Ok(invitation.role)
+      },
+    )?;
+
+    Self::insert_auth_in_frunique_collection(invitee, collection, role)?;
+
+    Ok(role)
+  }
+
+  pub fn do_decline_invitation(
+    invitee: T::AccountId,
+    collection: T::CollectionId,
+  ) -> DispatchResult {
+    let now = <frame_system::Pallet<T>>::block_number();
+    <Invitations<T>>::try_mutate::<_, _, _, DispatchError, _>(collection, &invitee, |invitation| {
+      let invitation = invitation.as_mut().ok_or(Error::<T>::InvitationNotFound)?;
+      match invitation.status_at(now) {
+        InvitationStatus::Pending => {},
+        InvitationStatus::Expired => return Err(Error::<T>::InvitationExpired.into()),
+        _ => return Err(Error::<T>::InvitationNotFound.into()),
+      }
+      invitation.status = InvitationStatus::Declined;
+      Ok(())
+    })
+  }
+
+  // Cancels a pending invitation for the role, or removes the role from the user
+  pub fn do_revoke(
+    who: T::AccountId,
+    collection: T::CollectionId,
+    user: T::AccountId,
+    role: FruniqueRole,
+  ) -> DispatchResult {
+    Self::ensure_can_manage_role(who, collection, role)?;
+
+    let now = <frame_system::Pallet<T>>::block_number();
+    if let Some(invitation) = <Invitations<T>>::get(collection, &user) {
+      if invitation.role == role && invitation.status_at(now) == InvitationStatus::Pending {
+        <Invitations<T>>::remove(collection, &user);
+        return Ok(());
+      }
+      if invitation.role == role && invitation.status == InvitationStatus::Accepted {
+        <Invitations<T>>::remove(collection, &user);
+      }
+    }
+
+    Self::remove_auth_from_frunique_collection(user, collection, role)
+  }
+
+  // Active lease of a frunique, expired leases are ignored
+  pub fn get_rental(collection: T::CollectionId, item: T::ItemId) -> Option<RentalInfo<T>> {
+    let now = <frame_system::Pallet<T>>::block_number();
+    <FruniqueRentals<T>>::get(collection, item).filter(|rental| rental.is_active(now))
+  }
+
+  // Account holding the usage rights of a frunique, the owner if it isn't leased
+  pub fn user_of(collection: T::CollectionId, item: T::ItemId) -> Option<T::AccountId> {
+    Self::get_rental(collection, item)
+      .map(|rental| rental.user)
+      .or_else(|| Self::admin_of(&collection, &item))
+  }
+
+  // Fruniques with an active lease can only be sold when the lease allows it
+  pub fn ensure_sellable(collection: T::CollectionId, item: T::ItemId) -> DispatchResult {
+    if let Some(rental) = Self::get_rental(collection, item) {
+      ensure!(rental.allow_sale, Error::<T>::FruniqueRented);
+    }
+    Ok(())
+  }
+
+  pub fn do_set_user(
+    owner: T::AccountId,
+    collection: T::CollectionId,
+    item: T::ItemId,
+    user: T::AccountId,
+    expires_at: T::BlockNumber,
+    allow_sale: bool,
+  ) -> DispatchResult {
+    ensure!(Self::instance_exists(&collection, &item), Error::<T>::FruniqueNotFound);
+    ensure!(Self::admin_of(&collection, &item) == Some(owner.clone()), Error::<T>::NotOwner);
+
+    let frunique: FruniqueData<T> =
+      <FruniqueInfo<T>>::try_get(collection, item).map_err(|_| Error::<T>::NotAFrunique)?;
+    ensure!(!frunique.redeemed, Error::<T>::FruniqueAlreadyRedeemed);
+
+    let now = <frame_system::Pallet<T>>::block_number();
+    ensure!(expires_at > now, Error::<T>::InvalidRentalPeriod);
+    ensure!(Self::get_rental(collection, item).is_none(), Error::<T>::RentalActive);
+
+    <FruniqueRentals<T>>::insert(collection, item, RentalInfo { user, expires_at, allow_sale });
+    Self::record_history(collection, item, FruniqueAction::Rented, Some(owner));
+
+    Ok(())
+  }
+
+  pub fn do_end_rental(
+    user: T::AccountId,
+    collection: T::CollectionId,
+    item: T::ItemId,
+  ) -> DispatchResult {
+    let rental = Self::get_rental(collection, item).ok_or(Error::<T>::NoActiveRental)?;
+    ensure!(rental.user == user, Error::<T>::NotAuthorized);
+
+    <FruniqueRentals<T>>::remove(collection, item);
+    Self::record_history(collection, item, FruniqueAction::RentalEnded, Some(user));
+
+    Ok(())
+  }
+
   // Helper function to check if a user has a specific role in a collection
   pub fn is_authorized(
     user: T::AccountId,
@@ -498,3 +1416,36 @@ impl<T: Config> Pallet<T> {
     <T as pallet::Config>::Rbac::is_authorized(user, Self::pallet_id(), &scope_id, &permission.id())
   }
 }
+
+impl<T: Config> StoragePurge for Pallet<T> {
+  const STAGES: u32 = 15;
+
+  fn clear_stage(stage: u32, limit: u32, cursor: Option<&[u8]>) -> MultiRemovalResults {
+    match stage {
+      0 => <NextFrunique<T>>::clear(limit, cursor),
+      1 => <FruniqueVerified<T>>::clear(limit, cursor),
+      2 => <FruniqueRoots<T>>::clear(limit, cursor),
+      3 => <FruniqueRedeemed<T>>::clear(limit, cursor),
+      4 => <FruniqueInfo<T>>::clear(limit, cursor),
+      5 => <FungibleFractions<T>>::clear(limit, cursor),
+      6 => <VerificationPolicies<T>>::clear(limit, cursor),
+      7 => <FruniqueAttestations<T>>::clear(limit, cursor),
+      8 => <AttributeSchemas<T>>::clear(limit, cursor),
+      9 => <FruniqueHistory<T>>::clear(limit, cursor),
+      10 => <FreezePolicies<T>>::clear(limit, cursor),
+      11 => <FruniqueFreezes<T>>::clear(limit, cursor),
+      12 => <ScheduledThaws<T>>::clear(limit, cursor),
+      13 => <Invitations<T>>::clear(limit, cursor),
+      14 => <FruniqueRentals<T>>::clear(limit, cursor),
+      _ => MultiRemovalResults { maybe_cursor: None, backend: 0, unique: 0, loops: 0 },
+    }
+  }
+
+  fn purge_progress() -> Option<PurgeProgress> {
+    <StoragePurgeProgress<T>>::get()
+  }
+
+  fn set_purge_progress(progress: Option<PurgeProgress>) {
+    <StoragePurgeProgress<T>>::set(progress);
+  }
+}
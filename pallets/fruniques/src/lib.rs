@@ -20,16 +20,21 @@ pub mod pallet {
   use crate::types::*;
   use frame_support::pallet_prelude::*;
   use frame_system::pallet_prelude::*;
-  use sp_runtime::Permill;
+  use sp_runtime::{sp_std::vec::Vec, Permill};
 
   // use frame_support::PalletId;
 
   const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
 
-  use pallet_rbac::types::RoleBasedAccessControl;
+  use pallet_rbac::{
+    purge::{self, PurgeProgress, StoragePurge},
+    types::RoleBasedAccessControl,
+  };
   /// Configure the pallet by specifying the parameters and types on which it depends.
   #[pallet::config]
-  pub trait Config: frame_system::Config + pallet_uniques::Config {
+  pub trait Config:
+    frame_system::Config + pallet_uniques::Config + pallet_mapped_assets::Config
+  {
     type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
 
     type RemoveOrigin: EnsureOrigin<Self::RuntimeOrigin>;
@@ -41,6 +46,30 @@ pub mod pallet {
     #[pallet::constant]
     type MaxParentsInCollection: Get<u32>;
 
+    /// Maximum number of verifiers a collection policy can have
+    #[pallet::constant]
+    type MaxVerifiers: Get<u32>;
+
+    /// Maximum number of fields in a collection attribute schema
+    #[pallet::constant]
+    type MaxSchemaFields: Get<u32>;
+
+    /// Maximum number of history entries kept for each frunique
+    #[pallet::constant]
+    type MaxHistoryLength: Get<u32>;
+
+    /// Maximum number of fruniques that can be spawned in a single batch
+    #[pallet::constant]
+    type MaxBatchSpawn: Get<u32>;
+
+    /// Maximum number of time-locked fruniques that can be thawed in the same block
+    #[pallet::constant]
+    type MaxThawsPerBlock: Get<u32>;
+
+    /// Number of blocks an invitation to a collection stays valid
+    #[pallet::constant]
+    type InvitationExpiration: Get<Self::BlockNumber>;
+
     /// The fruniques pallet id, used for deriving its sovereign account ID.
     // #[pallet::constant]
     // type PalletId: Get<PalletId>;
@@ -62,11 +91,51 @@ pub mod pallet {
     // A frunique/unique was successfully divided!
     FruniqueDivided(T::AccountId, T::AccountId, T::CollectionId, T::ItemId),
     // A frunique has been verified.
-    FruniqueVerified(T::AccountId, CollectionId, ItemId),
-    // A user has been invited to collaborate on a collection.
-    InvitedToCollaborate(T::AccountId, T::AccountId, T::CollectionId),
+    FruniqueVerified(T::AccountId, T::CollectionId, T::ItemId),
+    // A user has been invited to collaborate on a collection with the given role.
+    InvitedToCollaborate(T::AccountId, T::AccountId, T::CollectionId, FruniqueRole),
     // Counter should work?
     NextFrunique(u32),
+    // Children fruniques were merged back into their parent.
+    FruniqueMerged(T::AccountId, T::CollectionId, T::ItemId),
+    // A frunique was locked and its shares were minted as a fungible asset.
+    FruniqueFractionalized(T::AccountId, T::CollectionId, T::ItemId, FractionAssetId<T>),
+    // The full supply of fungible shares was redeemed and the frunique was unlocked.
+    FractionsRedeemed(T::AccountId, T::CollectionId, T::ItemId, FractionAssetId<T>),
+    // A verification policy was set for a collection.
+    VerificationPolicySet(T::AccountId, T::CollectionId),
+    // A verifier attested a frunique.
+    FruniqueAttested(T::AccountId, T::CollectionId, T::ItemId),
+    // The verification of a frunique was revoked.
+    FruniqueVerificationRevoked(T::AccountId, T::CollectionId, T::ItemId),
+    // An attribute schema was set for a collection.
+    AttributeSchemaSet(T::AccountId, T::CollectionId),
+    // A batch of fruniques was spawned, starting at the given item id.
+    FruniquesBatchSpawned(T::AccountId, T::CollectionId, T::ItemId, u32),
+    // A freeze policy was set for a collection.
+    FreezePolicySet(T::AccountId, T::CollectionId),
+    // A user was given the freezer role in a collection.
+    FreezerAdded(T::AccountId, T::AccountId, T::CollectionId),
+    // A user was removed from the freezer role of a collection.
+    FreezerRemoved(T::AccountId, T::AccountId, T::CollectionId),
+    // A frunique was frozen, with the block at which it will be thawed, if any.
+    FruniqueFrozen(T::AccountId, T::CollectionId, T::ItemId, Option<T::BlockNumber>),
+    // A frunique was thawed.
+    FruniqueThawed(T::AccountId, T::CollectionId, T::ItemId),
+    // The time lock of a frunique expired and it was thawed.
+    FruniqueAutoThawed(T::CollectionId, T::ItemId),
+    // An invitation to a collection was accepted.
+    InvitationAccepted(T::AccountId, T::CollectionId, FruniqueRole),
+    // An invitation to a collection was declined.
+    InvitationDeclined(T::AccountId, T::CollectionId),
+    // A role or a pending invitation was revoked from a user.
+    RoleRevoked(T::AccountId, T::AccountId, T::CollectionId, FruniqueRole),
+    // The usage rights of a frunique were leased to a user until the given block.
+    FruniqueUserSet(T::AccountId, T::CollectionId, T::ItemId, T::AccountId, T::BlockNumber),
+    // The user of a frunique gave the usage rights back before the lease expired.
+    FruniqueRentalEnded(T::AccountId, T::CollectionId, T::ItemId),
+    // The storage removal started by kill_storage finished.
+    StoragePurged,
   }
 
   #[pallet::error]
@@ -81,6 +150,34 @@ pub mod pallet {
     NotYetImplemented,
     // Too many fruniques were minted
     FruniqueCntOverflow,
+    // A batch must spawn at least one frunique
+    EmptyBatch,
+    // The batch exceeds the maximum number of fruniques that can be spawned at once
+    BatchTooLarge,
+    // The thaw block is in the past or exceeds the time lock allowed by the freeze policy
+    InvalidTimeLock,
+    // The frunique wasn't frozen through the freeze extrinsic
+    FruniqueNotFrozen,
+    // The frunique is time-locked and only a freezer can thaw it before the lock expires
+    FruniqueTimeLocked,
+    // Too many fruniques are already scheduled to be thawed in that block
+    TooManyThawsScheduled,
+    // The user already has a pending invitation to the collection
+    InvitationAlreadyPending,
+    // There's no pending invitation for the user
+    InvitationNotFound,
+    // The invitation expired
+    InvitationExpired,
+    // The role can't be given or revoked this way
+    InvalidRole,
+    // The frunique is leased to a user and the lease doesn't allow to sell it
+    FruniqueRented,
+    // The frunique is already leased to a user
+    RentalActive,
+    // The frunique isn't leased to anyone
+    NoActiveRental,
+    // The lease must expire in a future block
+    InvalidRentalPeriod,
     // The asset_id is not linked to a frunique or it doesn't exists
     NotAFrunique,
     // The key of an attribute it's too long
@@ -119,6 +216,48 @@ pub mod pallet {
     UserNotInCollection,
     //User is not authorized to perform this action
     NotAuthorized,
+    // Only the owner of the frunique can perform this action
+    NotOwner,
+    // The frunique doesn't have children to merge
+    NoChildrenToMerge,
+    // The given frunique is not a child of the parent
+    ChildNotFound,
+    // Only hierarchical children can be merged back into their parent
+    ChildNotHierarchical,
+    // The child has been divided, its own children must be merged first
+    ChildAlreadyDivided,
+    // The frunique is already locked as fungible fractions
+    FruniqueAlreadyFractionalized,
+    // The frunique is not locked as fungible fractions
+    FruniqueNotFractionalized,
+    // The supply of fungible fractions must be greater than zero
+    InvalidFractionSupply,
+    // The full supply of fungible fractions is needed to unlock the frunique
+    IncompleteFractionSupply,
+    // The frunique has children, it can't be locked as fungible fractions
+    FruniqueHasChildren,
+    // The verifier already attested this frunique
+    AlreadyAttested,
+    // Too many attestations or verifiers
+    MaxVerifiersReached,
+    // The verification policy is not valid
+    InvalidVerificationPolicy,
+    // The frunique is not verified nor has pending attestations
+    FruniqueNotVerified,
+    // More descendants inherited the verification than the given max
+    TooManyDescendants,
+    // The attribute schema is not valid
+    InvalidAttributeSchema,
+    // The attribute key is not declared in the collection schema
+    AttributeNotInSchema,
+    // The attribute value doesn't match the type declared in the schema
+    InvalidAttributeValue,
+    // A required attribute of the schema is missing
+    MissingRequiredAttribute,
+    // The attribute is immutable and it's already set
+    ImmutableAttribute,
+    // A storage removal started by kill_storage is still in progress
+    StoragePurgeInProgress,
   }
 
   #[pallet::storage]
@@ -197,14 +336,148 @@ pub mod pallet {
     OptionQuery,
   >;
 
+  #[pallet::storage]
+  #[pallet::getter(fn fungible_fractions)]
+  /// Fruniques locked in the pallet account whose shares are a mapped-assets asset.
+  pub(super) type FungibleFractions<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    T::CollectionId,
+    Blake2_128Concat,
+    T::ItemId,
+    FungibleFractionData<T>,
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn verification_policies)]
+  /// Verification policy of each collection, the default policy is used when missing.
+  pub(super) type VerificationPolicies<T: Config> =
+    StorageMap<_, Blake2_128Concat, T::CollectionId, VerificationPolicy<T>, OptionQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn frunique_attestations)]
+  /// Attestations recorded for each frunique.
+  pub(super) type FruniqueAttestations<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    T::CollectionId,
+    Blake2_128Concat,
+    T::ItemId,
+    Attestations<T>,
+    ValueQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn attribute_schemas)]
+  /// Attribute schema each frunique of a collection must follow.
+  pub(super) type AttributeSchemas<T: Config> =
+    StorageMap<_, Blake2_128Concat, T::CollectionId, AttributeSchema<T>, OptionQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn frunique_history)]
+  /// Most recent actions performed on each frunique, oldest entries are dropped first.
+  pub(super) type FruniqueHistory<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    T::CollectionId,
+    Blake2_128Concat,
+    T::ItemId,
+    History<T>,
+    ValueQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn freeze_policies)]
+  /// Freeze policy of each collection, the default policy is used when missing.
+  pub(super) type FreezePolicies<T: Config> =
+    StorageMap<_, Blake2_128Concat, T::CollectionId, FreezePolicy<T>, OptionQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn frunique_freezes)]
+  /// Fruniques frozen through the freeze extrinsic.
+  pub(super) type FruniqueFreezes<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    T::CollectionId,
+    Blake2_128Concat,
+    T::ItemId,
+    FreezeInfo<T>,
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn scheduled_thaws)]
+  /// Time-locked fruniques to be thawed at the start of each block.
+  pub(super) type ScheduledThaws<T: Config> =
+    StorageMap<_, Blake2_128Concat, T::BlockNumber, ScheduledThawsOf<T>, ValueQuery>;
+
+  #[pallet::storage]
+  #[pallet::getter(fn invitations)]
+  /// Last invitation of each user to each collection.
+  pub(super) type Invitations<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    T::CollectionId,
+    Blake2_128Concat,
+    T::AccountId,
+    Invitation<T>,
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn frunique_rentals)]
+  /// Users the fruniques are leased to, the entries are ignored once the lease expires.
+  pub(super) type FruniqueRentals<T: Config> = StorageDoubleMap<
+    _,
+    Blake2_128Concat,
+    T::CollectionId,
+    Blake2_128Concat,
+    T::ItemId,
+    RentalInfo<T>,
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn storage_purge_progress)]
+  /// Progress of the storage removal started by `kill_storage`.
+  pub(super) type StoragePurgeProgress<T: Config> = StorageValue<_, PurgeProgress, OptionQuery>;
+
+  #[pallet::hooks]
+  impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+    fn on_initialize(now: T::BlockNumber) -> Weight {
+      let thawed = Self::do_thaw_expired(now);
+      T::DbWeight::get().reads_writes(1 + thawed as u64, 1 + 2 * thawed as u64)
+    }
+
+    fn on_idle(_n: T::BlockNumber, remaining_weight: Weight) -> Weight {
+      let (weight, complete) = Self::purge_on_idle(remaining_weight, T::DbWeight::get());
+      if complete {
+        Self::deposit_event(Event::StoragePurged);
+      }
+      weight
+    }
+  }
+
   #[pallet::call]
   impl<T: Config> Pallet<T>
   where
     T: pallet_uniques::Config<CollectionId = CollectionId, ItemId = ItemId>,
   {
+    /// # Initial setup of the pallet
+    /// Sets the freezer account and creates the roles and permissions of the pallet.
+    ///
+    /// ## Parameters
+    /// - `origin`: The root origin.
+    /// - `freezer`: The account allowed to freeze any frunique.
+    ///
+    /// ## Considerations
+    /// It can be called again after a runtime upgrade adds roles or permissions, only the
+    /// missing ones are created.
     #[pallet::call_index(1)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(10))]
     pub fn initial_setup(origin: OriginFor<T>, freezer: T::AccountId) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       //Transfer the balance
       T::RemoveOrigin::ensure_origin(origin.clone())?;
 
@@ -227,6 +500,7 @@ pub mod pallet {
       origin: OriginFor<T>,
       metadata: CollectionDescription<T>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let admin: T::AccountId = ensure_signed(origin.clone())?;
       // let admin: T::AccountId = frame_system::RawOrigin::Root.into();
 
@@ -251,6 +525,7 @@ pub mod pallet {
       instance_id: T::ItemId,
       attributes: Attributes<T>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       ensure!(Self::instance_exists(&class_id, &instance_id), Error::<T>::FruniqueNotFound);
 
       // ! Ensure the admin is the one who can add attributes to the frunique.
@@ -260,6 +535,7 @@ pub mod pallet {
       ensure!(signer == admin, Error::<T>::NotAdmin);
 
       ensure!(!attributes.is_empty(), Error::<T>::AttributesEmpty);
+      Self::validate_attributes_update(class_id, instance_id, &attributes)?;
       for attribute in &attributes {
         Self::set_attribute(
           origin.clone(),
@@ -269,6 +545,7 @@ pub mod pallet {
           attribute.1.clone(),
         )?;
       }
+      Self::record_history(class_id, instance_id, FruniqueAction::AttributesSet, signer);
       Ok(())
     }
 
@@ -288,6 +565,7 @@ pub mod pallet {
       attributes: Option<Attributes<T>>,
       parent_info_call: Option<ParentInfoCall<T>>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       //Ensure the collection exists
       ensure!(Self::collection_exists(&class_id), Error::<T>::CollectionNotFound);
       // Ensure the user is in the collection
@@ -341,9 +619,11 @@ pub mod pallet {
 
     /// ## Verification of the NFT
     /// ### Parameters:
-    /// - `origin` must be signed by the owner of the frunique.
+    /// - `origin` must be signed by a verifier of the collection.
     /// - `class_id` must be a valid class of the asset class.
     /// - `instance_id` must be a valid instance of the asset class.
+    /// ### Considerations:
+    /// This records an attestation without evidence, see `attest`.
     #[pallet::call_index(5)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
     pub fn verify(
@@ -351,61 +631,36 @@ pub mod pallet {
       class_id: CollectionId,
       instance_id: ItemId,
     ) -> DispatchResult {
-      // Ensure the frunique exists.
-      ensure!(Self::instance_exists(&class_id, &instance_id), Error::<T>::FruniqueNotFound);
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let caller: T::AccountId = ensure_signed(origin)?;
 
-      // Ensure the caller has the permission to verify the frunique.
-      let caller: T::AccountId = ensure_signed(origin.clone())?;
-      ensure!(
-        Self::is_authorized(caller.clone(), class_id, Permission::Verify).is_ok(),
-        Error::<T>::NotAuthorized
-      );
-
-      <FruniqueInfo<T>>::try_mutate::<_, _, _, DispatchError, _>(
-        class_id,
-        instance_id,
-        |frunique_data| -> DispatchResult {
-          let frunique = frunique_data.as_mut().ok_or(Error::<T>::FruniqueNotFound)?;
-          if frunique.verified == true || frunique.verified_by.is_some() {
-            return Err(Error::<T>::FruniqueAlreadyVerified.into());
-          }
-          frunique.verified = true;
-          frunique.verified_by = Some(caller.clone());
-          Ok(())
-        },
-      )?;
-
-      <FruniqueVerified<T>>::insert(class_id, instance_id, true);
-
-      Self::deposit_event(Event::FruniqueVerified(caller, class_id, instance_id));
-
-      Ok(())
+      Self::do_attest(caller, class_id, instance_id, None)
     }
 
-    /// ## Invite a user to become a collaborator in a collection.
+    /// ## Invite a user to join a collection with a given role.
     /// ### Parameters:
-    /// - `origin` must be signed by the owner of the frunique.
+    /// - `origin` must be signed by a user with the `InviteCollaborator` permission.
     /// - `class_id` must be a valid class of the asset class.
     /// - `invitee` must be a valid user.
+    /// - `role` the role the invitee gets once the invitation is accepted.
     /// ### Considerations:
-    /// This functions enables the owner of a collection to invite a user to become a collaborator.
-    /// The user will be able to create NFTs in the collection.
-    /// The user will be able to add attributes to the NFTs in the collection.
+    /// The invitation stays pending until the invitee accepts or declines it, and expires after
+    /// `InvitationExpiration` blocks. Only the owner of the collection can invite admins, and
+    /// the `Owner` role can't be given through an invitation.
     #[pallet::call_index(6)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
     pub fn invite(
       origin: OriginFor<T>,
       class_id: CollectionId,
       invitee: T::AccountId,
+      role: FruniqueRole,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let owner: T::AccountId = ensure_signed(origin.clone())?;
-      Self::insert_auth_in_frunique_collection(
-        invitee.clone(),
-        class_id,
-        FruniqueRole::Collaborator,
-      )?;
 
-      Self::deposit_event(Event::InvitedToCollaborate(owner, invitee, class_id));
+      Self::do_invite(owner.clone(), class_id, invitee.clone(), role)?;
+
+      Self::deposit_event(Event::InvitedToCollaborate(owner, invitee, class_id, role));
       Ok(())
     }
 
@@ -426,6 +681,7 @@ pub mod pallet {
       class_id: T::CollectionId,
       instance_id: Option<T::ItemId>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       T::RemoveOrigin::ensure_origin(origin)?;
 
       if let Some(instance_id) = instance_id {
@@ -459,6 +715,7 @@ pub mod pallet {
       witness: pallet_uniques::DestroyWitness,
       maybe_check_owner: Option<T::AccountId>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       T::RemoveOrigin::ensure_origin(origin)?;
 
       ensure!(Self::collection_exists(&class_id), Error::<T>::CollectionNotFound);
@@ -476,33 +733,504 @@ pub mod pallet {
     ///
     /// ### Considerations:
     /// - This function is only available to the `admin` with sudo access.
+    /// - The storage maps that can't be cleared in this call are cleared in the next blocks,
+    ///   `StoragePurged` is emitted once they are empty.
     #[pallet::call_index(9)]
-    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
+    #[pallet::weight(
+      Weight::from_ref_time(10_000)
+        + T::DbWeight::get().reads_writes(2, 3 + u64::from(purge::KEYS_PER_CALL))
+    )]
     pub fn kill_storage(origin: OriginFor<T>) -> DispatchResult {
       T::RemoveOrigin::ensure_origin(origin.clone())?;
+      // Starting over would drop the progress of the purge in progress
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       <Freezer<T>>::kill();
       <NextCollection<T>>::put(0);
-      let _ = <NextFrunique<T>>::clear(1000, None);
-      let _ = <FruniqueVerified<T>>::clear(1000, None);
-      let _ = <FruniqueRoots<T>>::clear(1000, None);
-      let _ = <FruniqueRedeemed<T>>::clear(1000, None);
-      let _ = <FruniqueInfo<T>>::clear(1000, None);
 
       T::Rbac::remove_pallet_storage(Self::pallet_id())?;
+
+      // The maps are cleared in multiple blocks, see `on_idle`
+      if Self::start_purge(purge::KEYS_PER_CALL).complete {
+        Self::deposit_event(Event::StoragePurged);
+      }
       Ok(())
     }
 
+    /// ## Batch NFT creation
+    /// ### Parameters:
+    /// - `origin` must be signed by a user with the mint permission in the collection.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `metadata` Title shared by all the nfts.
+    /// - `items_attributes` Attributes of each nft, one nft is spawned per entry.
+    /// ### Considerations:
+    /// At most `MaxBatchSpawn` nfts can be spawned at once. They get consecutive ids and none
+    /// of them is spawned if any of them fails.
     #[pallet::call_index(10)]
+    #[pallet::weight(
+      Weight::from_ref_time(10_000).saturating_mul(items_attributes.len() as u64)
+        + T::DbWeight::get().reads_writes(2, 5).saturating_mul(items_attributes.len() as u64)
+    )]
+    pub fn spawn_batch(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      metadata: CollectionDescription<T>,
+      items_attributes: BoundedVec<Option<Attributes<T>>, T::MaxBatchSpawn>,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      ensure!(Self::collection_exists(&class_id), Error::<T>::CollectionNotFound);
+      let user: T::AccountId = ensure_signed(origin)?;
+
+      ensure!(
+        Self::is_authorized(user.clone(), class_id, Permission::Mint).is_ok(),
+        Error::<T>::UserNotInCollection
+      );
+
+      let count = items_attributes.len() as u32;
+      let first_item =
+        Self::do_spawn_batch(class_id, user.clone(), metadata, items_attributes.into_inner())?;
+
+      Self::deposit_event(Event::FruniquesBatchSpawned(user, class_id, first_item, count));
+      Ok(())
+    }
+
+    /// ## Merge children fruniques back into their parent
+    /// ### Parameters:
+    /// - `origin` must be signed by the owner of the parent and all of the children.
+    /// - `class_id` must be the collection of the parent frunique.
+    /// - `instance_id` must be the parent frunique.
+    /// - `children` Optional list of `(collection_id, child_id)` to merge, if `None` all the
+    ///   children of the parent are merged.
+    /// ### Considerations:
+    /// Only hierarchical children which have not been divided themselves nor leased can be
+    /// merged. Each merged child is burned and its `weight_inherited` is restored to the parent.
+    #[pallet::call_index(11)]
+    #[pallet::weight(
+      Weight::from_ref_time(10_000)
+        + T::DbWeight::get().reads_writes(2, 2)
+        + T::DbWeight::get().reads_writes(5, 11).saturating_mul(
+          children.as_ref().map_or(T::ChildMaxLen::get(), |children| children.len() as u32).into()
+        )
+    )]
+    pub fn merge(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      instance_id: ItemId,
+      children: Option<BoundedVec<(CollectionId, ItemId), T::ChildMaxLen>>,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let owner: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_merge(
+        owner.clone(),
+        class_id,
+        instance_id,
+        children.map(|children| children.into_inner()),
+      )?;
+
+      Self::deposit_event(Event::FruniqueMerged(owner, class_id, instance_id));
+      Ok(())
+    }
+
+    /// ## Lock a frunique as fungible fractions
+    /// ### Parameters:
+    /// - `origin` must be signed by the owner of the frunique.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `instance_id` must be a valid instance of the asset class.
+    /// - `asset_id` the id of the new mapped-assets asset representing the shares. It must not
+    ///   exist yet.
+    /// - `supply` the amount of shares minted to the owner.
+    /// ### Considerations:
+    /// The frunique is transferred to the pallet account and frozen until the full supply is
+    /// redeemed with `redeem_fractions`.
+    #[pallet::call_index(12)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(6))]
+    pub fn fractionalize(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      instance_id: ItemId,
+      asset_id: FractionAssetId<T>,
+      supply: FractionBalance<T>,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let owner: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_fractionalize(owner.clone(), class_id, instance_id, asset_id, supply)?;
+
+      Self::deposit_event(Event::FruniqueFractionalized(owner, class_id, instance_id, asset_id));
+      Ok(())
+    }
+
+    /// ## Redeem the fungible fractions of a frunique
+    /// ### Parameters:
+    /// - `origin` must be signed by the holder of the full supply of shares.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `instance_id` must be a fractionalized frunique.
+    /// ### Considerations:
+    /// The shares are burned and the frunique is thawed and transferred to the caller.
+    #[pallet::call_index(13)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(6))]
+    pub fn redeem_fractions(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      instance_id: ItemId,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who: T::AccountId = ensure_signed(origin)?;
+
+      let asset_id = Self::do_redeem_fractions(who.clone(), class_id, instance_id)?;
+
+      Self::deposit_event(Event::FractionsRedeemed(who, class_id, instance_id, asset_id));
+      Ok(())
+    }
+
+    /// ## Set the verification policy of a collection
+    /// ### Parameters:
+    /// - `origin` must be signed by the owner of the collection.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `policy` the number of attestations required, the accounts allowed to attest and
+    ///   whether children inherit the verification of their parent.
+    /// ### Considerations:
+    /// When `verifiers` is empty any account with the `Verify` permission can attest, otherwise
+    /// only the listed accounts can, and `required_approvals` can't exceed their number. The listed
+    /// accounts must have the `Verify` permission in the collection.
+    #[pallet::call_index(14)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
-    pub fn spam_spawning(
+    pub fn set_verification_policy(
       origin: OriginFor<T>,
-      class_id: T::CollectionId,
-      instances: u32,
+      class_id: CollectionId,
+      policy: VerificationPolicy<T>,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let owner: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_set_verification_policy(owner.clone(), class_id, policy)?;
+
+      Self::deposit_event(Event::VerificationPolicySet(owner, class_id));
+      Ok(())
+    }
+
+    /// ## Attest a frunique
+    /// ### Parameters:
+    /// - `origin` must be signed by a verifier of the collection.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `instance_id` must be a valid instance of the asset class.
+    /// - `evidence` CID of the documents backing the attestation.
+    /// ### Considerations:
+    /// The frunique is verified once it gathers the attestations required by the collection
+    /// policy.
+    #[pallet::call_index(15)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(2))]
+    pub fn attest(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      instance_id: ItemId,
+      evidence: CID,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let caller: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_attest(caller, class_id, instance_id, Some(evidence))
+    }
+
+    /// ## Revoke the verification of a frunique
+    /// ### Parameters:
+    /// - `origin` must be signed by a verifier of the collection.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `instance_id` must be a valid instance of the asset class.
+    /// - `max_descendants` is the max number of descendants that inherited the verification and
+    ///   are revoked along with this frunique, the call is weighted by it.
+    /// ### Considerations:
+    /// All the attestations are removed. Descendants that inherited the verification from this
+    /// frunique are revoked as well, the call fails if there are more than `max_descendants`.
+    #[pallet::call_index(16)]
+    #[pallet::weight(
+      Weight::from_ref_time(10_000)
+        + T::DbWeight::get().reads_writes(3, 4).saturating_mul(u64::from(*max_descendants) + 1)
+    )]
+    pub fn revoke_verification(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      instance_id: ItemId,
+      max_descendants: u32,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let caller: T::AccountId = ensure_signed(origin)?;
+
+      ensure!(Self::instance_exists(&class_id, &instance_id), Error::<T>::FruniqueNotFound);
+      ensure!(Self::is_verifier(caller.clone(), class_id), Error::<T>::NotAuthorized);
+
+      Self::do_revoke_verification(caller, class_id, instance_id, max_descendants)
+    }
+
+    /// ## Set the attribute schema of a collection
+    /// ### Parameters:
+    /// - `origin` must be signed by the owner of the collection.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `schema` the keys fruniques of the collection can have, with the type of their values,
+    ///   whether they are required and whether they can be changed once set.
+    /// ### Considerations:
+    /// The schema is enforced on `spawn` and `set_attributes`. Children spawned in a collection
+    /// without schema follow the schema of their parent's collection and inherit the values of
+    /// their parent for the keys they don't set.
+    #[pallet::call_index(17)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
+    pub fn set_attribute_schema(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      schema: AttributeSchema<T>,
     ) -> DispatchResult {
-      let _ = ensure_signed(origin)?;
-      // for instance in instances {
-      // 	let _ = Self::mint(instance, class_id, None, None, None, None, None, None, None)?;
-      // }
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let owner: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_set_attribute_schema(owner.clone(), class_id, schema)?;
+
+      Self::deposit_event(Event::AttributeSchemaSet(owner, class_id));
+      Ok(())
+    }
+
+    /// ## Set the freeze policy of a collection
+    /// ### Parameters:
+    /// - `origin` must be signed by the owner of the collection.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `policy` whether holders can freeze their own fruniques and the longest time lock a
+    ///   freeze can have.
+    #[pallet::call_index(18)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
+    pub fn set_freeze_policy(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      policy: FreezePolicy<T>,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let owner: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_set_freeze_policy(owner.clone(), class_id, policy)?;
+
+      Self::deposit_event(Event::FreezePolicySet(owner, class_id));
+      Ok(())
+    }
+
+    /// ## Give the freezer role to a user
+    /// ### Parameters:
+    /// - `origin` must be signed by the owner of the collection.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `freezer` the account that will be able to freeze and thaw fruniques of the collection.
+    #[pallet::call_index(19)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
+    pub fn add_freezer(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      freezer: T::AccountId,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let owner: T::AccountId = ensure_signed(origin)?;
+      ensure!(Self::collection_exists(&class_id), Error::<T>::CollectionNotFound);
+      ensure!(
+        pallet_uniques::Pallet::<T>::collection_owner(class_id) == Some(owner.clone()),
+        Error::<T>::NotOwner
+      );
+
+      Self::insert_auth_in_frunique_collection(freezer.clone(), class_id, FruniqueRole::Freezer)?;
+
+      Self::deposit_event(Event::FreezerAdded(owner, freezer, class_id));
+      Ok(())
+    }
+
+    /// ## Remove the freezer role from a user
+    /// ### Parameters:
+    /// - `origin` must be signed by the owner of the collection.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `freezer` the account to remove from the freezer role.
+    #[pallet::call_index(20)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
+    pub fn remove_freezer(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      freezer: T::AccountId,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let owner: T::AccountId = ensure_signed(origin)?;
+      ensure!(Self::collection_exists(&class_id), Error::<T>::CollectionNotFound);
+      ensure!(
+        pallet_uniques::Pallet::<T>::collection_owner(class_id) == Some(owner.clone()),
+        Error::<T>::NotOwner
+      );
+
+      Self::remove_auth_from_frunique_collection(freezer.clone(), class_id, FruniqueRole::Freezer)?;
+
+      Self::deposit_event(Event::FreezerRemoved(owner, freezer, class_id));
+      Ok(())
+    }
+
+    /// ## Freeze a frunique
+    /// ### Parameters:
+    /// - `origin` must be signed by a freezer of the collection, or by the holder of the
+    ///   frunique when the freeze policy allows it.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `instance_id` must be a valid instance of the asset class.
+    /// - `thaw_at` Optional block at which the frunique is thawed automatically.
+    /// ### Considerations:
+    /// A time-locked frunique can only be thawed before `thaw_at` by a freezer. When the policy
+    /// has a `max_lock_period`, `thaw_at` is required and can't be further than that.
+    #[pallet::call_index(21)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(3))]
+    pub fn freeze(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      instance_id: ItemId,
+      thaw_at: Option<T::BlockNumber>,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_freeze_frunique(who.clone(), class_id, instance_id, thaw_at)?;
+
+      Self::deposit_event(Event::FruniqueFrozen(who, class_id, instance_id, thaw_at));
+      Ok(())
+    }
+
+    /// ## Thaw a frunique
+    /// ### Parameters:
+    /// - `origin` must be signed by a freezer of the collection, or by the holder that froze
+    ///   the frunique.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `instance_id` must be a valid instance of the asset class.
+    /// ### Considerations:
+    /// Only fruniques frozen with the `freeze` extrinsic can be thawed, the ones frozen by a
+    /// marketplace are thawed by the marketplace.
+    #[pallet::call_index(22)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(3))]
+    pub fn thaw(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      instance_id: ItemId,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_thaw_frunique(who.clone(), class_id, instance_id)?;
+
+      Self::deposit_event(Event::FruniqueThawed(who, class_id, instance_id));
+      Ok(())
+    }
+
+    /// ## Accept an invitation
+    /// ### Parameters:
+    /// - `origin` must be signed by the invitee.
+    /// - `class_id` the collection the user was invited to.
+    /// ### Considerations:
+    /// The invitee gets the role chosen by the inviter. Expired invitations can't be accepted.
+    #[pallet::call_index(23)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(2))]
+    pub fn accept_invitation(origin: OriginFor<T>, class_id: CollectionId) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let invitee: T::AccountId = ensure_signed(origin)?;
+
+      let role = Self::do_accept_invitation(invitee.clone(), class_id)?;
+
+      Self::deposit_event(Event::InvitationAccepted(invitee, class_id, role));
+      Ok(())
+    }
+
+    /// ## Decline an invitation
+    /// ### Parameters:
+    /// - `origin` must be signed by the invitee.
+    /// - `class_id` the collection the user was invited to.
+    #[pallet::call_index(24)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
+    pub fn decline_invitation(origin: OriginFor<T>, class_id: CollectionId) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let invitee: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_decline_invitation(invitee.clone(), class_id)?;
+
+      Self::deposit_event(Event::InvitationDeclined(invitee, class_id));
+      Ok(())
+    }
+
+    /// ## Revoke a role or a pending invitation
+    /// ### Parameters:
+    /// - `origin` must be signed by a user with the `InviteCollaborator` permission.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `user` the account to revoke.
+    /// - `role` the role to remove from the user.
+    /// ### Considerations:
+    /// A pending invitation for `role` is cancelled, otherwise the role is removed from the
+    /// user. Only the owner of the collection can revoke admins, and owners can't be revoked.
+    #[pallet::call_index(25)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(2))]
+    pub fn revoke(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      user: T::AccountId,
+      role: FruniqueRole,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_revoke(who.clone(), class_id, user.clone(), role)?;
+
+      Self::deposit_event(Event::RoleRevoked(who, user, class_id, role));
+      Ok(())
+    }
+
+    /// ## Lease the usage rights of a frunique
+    /// ### Parameters:
+    /// - `origin` must be signed by the owner of the frunique.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `instance_id` must be a valid instance of the asset class.
+    /// - `user` the account that gets the usage rights.
+    /// - `expires_at` block at which the usage rights go back to the owner.
+    /// - `allow_sale` whether the frunique can be sold in a marketplace during the lease.
+    /// ### Considerations:
+    /// The ownership of the frunique doesn't change. A new lease can't be set until the current
+    /// one expires or the user ends it, and the lease is kept if the frunique is sold.
+    #[pallet::call_index(26)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(2))]
+    pub fn set_user(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      instance_id: ItemId,
+      user: T::AccountId,
+      expires_at: T::BlockNumber,
+      allow_sale: bool,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let owner: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_set_user(
+        owner.clone(),
+        class_id,
+        instance_id,
+        user.clone(),
+        expires_at,
+        allow_sale,
+      )?;
+
+      Self::deposit_event(Event::FruniqueUserSet(owner, class_id, instance_id, user, expires_at));
+      Ok(())
+    }
+
+    /// ## End a lease
+    /// ### Parameters:
+    /// - `origin` must be signed by the current user of the frunique.
+    /// - `class_id` must be a valid class of the asset class.
+    /// - `instance_id` must be a valid instance of the asset class.
+    /// ### Considerations:
+    /// Gives the usage rights back to the owner before the lease expires.
+    #[pallet::call_index(27)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(2))]
+    pub fn end_rental(
+      origin: OriginFor<T>,
+      class_id: CollectionId,
+      instance_id: ItemId,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let user: T::AccountId = ensure_signed(origin)?;
+
+      Self::do_end_rental(user.clone(), class_id, instance_id)?;
+
+      Self::deposit_event(Event::FruniqueRentalEnded(user, class_id, instance_id));
       Ok(())
     }
   }
@@ -1,5 +1,8 @@
 use crate as pallet_fruniques;
-use frame_support::{construct_runtime, parameter_types, traits::AsEnsureOriginWithArg};
+use frame_support::{
+  construct_runtime, parameter_types,
+  traits::{AsEnsureOriginWithArg, ConstU32, ConstU64},
+};
 use frame_system::{EnsureRoot, EnsureSigned};
 use pallet_balances;
 use sp_core::H256;
@@ -22,12 +25,19 @@ construct_runtime!(
     Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
     Fruniques: pallet_fruniques::{Pallet, Call, Storage, Event<T>},
     RBAC: pallet_rbac::{Pallet, Call, Storage, Event<T>},
+    Assets: pallet_mapped_assets::{Pallet, Call, Storage, Event<T>},
   }
 );
 parameter_types! {
   pub const BlockHashCount: u64 = 250;
   pub const ChildMaxLen: u32 = 10;
   pub const MaxParentsInCollection: u32 = 100;
+  pub const MaxVerifiers: u32 = 3;
+  pub const MaxSchemaFields: u32 = 10;
+  pub const MaxHistoryLength: u32 = 10;
+  pub const MaxBatchSpawn: u32 = 5;
+  pub const MaxThawsPerBlock: u32 = 3;
+  pub const InvitationExpiration: u64 = 10;
 }
 
 impl frame_system::Config for Test {
@@ -63,6 +73,12 @@ impl pallet_fruniques::Config for Test {
   type ChildMaxLen = ChildMaxLen;
   type MaxParentsInCollection = MaxParentsInCollection;
   type Rbac = RBAC;
+  type MaxVerifiers = MaxVerifiers;
+  type MaxSchemaFields = MaxSchemaFields;
+  type MaxHistoryLength = MaxHistoryLength;
+  type MaxBatchSpawn = MaxBatchSpawn;
+  type MaxThawsPerBlock = MaxThawsPerBlock;
+  type InvitationExpiration = InvitationExpiration;
 }
 
 parameter_types! {
@@ -116,7 +132,7 @@ impl pallet_balances::Config for Test {
 
 parameter_types! {
   pub const MaxScopesPerPallet: u32 = 2;
-  pub const MaxRolesPerPallet: u32 = 6;
+  pub const MaxRolesPerPallet: u32 = 7;
   pub const RoleMaxLen: u32 = 25;
   pub const PermissionMaxLen: u32 = 25;
   pub const MaxPermissionsPerRole: u32 = 11;
@@ -134,6 +150,36 @@ impl pallet_rbac::Config for Test {
   type MaxUsersPerRole = MaxUsersPerRole;
   type RemoveOrigin = EnsureRoot<Self::AccountId>;
 }
+pub struct AssetsCallbackHandle;
+impl pallet_mapped_assets::AssetsCallback<u32, u64> for AssetsCallbackHandle {
+  fn created(_id: &u32, _owner: &u64) {}
+
+  fn destroyed(_id: &u32) {}
+}
+
+impl pallet_mapped_assets::Config for Test {
+  type RuntimeEvent = RuntimeEvent;
+  type Balance = u64;
+  type AssetId = u32;
+  type AssetIdParameter = u32;
+  type Currency = Balances;
+  type CreateOrigin = AsEnsureOriginWithArg<EnsureSigned<u64>>;
+  type ForceOrigin = EnsureRoot<u64>;
+  type AssetDeposit = ConstU64<1>;
+  type AssetAccountDeposit = ConstU64<10>;
+  type MetadataDepositBase = ConstU64<1>;
+  type MetadataDepositPerByte = ConstU64<1>;
+  type ApprovalDeposit = ConstU64<1>;
+  type StringLimit = ConstU32<50>;
+  type Freezer = ();
+  type WeightInfo = ();
+  type CallbackHandle = AssetsCallbackHandle;
+  type Extra = ();
+  type RemoveItemsLimit = ConstU32<5>;
+  type MaxReserves = MaxReserves;
+  type ReserveIdentifier = u32;
+}
+
 // Build genesis storage according to the mock runtime.
 // pub(crate) fn new_test_ext() -> sp_io::TestExternalities {
 // 	frame_system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
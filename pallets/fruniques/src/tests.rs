@@ -1,9 +1,19 @@
-use crate::{mock::*, Error};
+use crate::{mock::*, Error, Event};
 use codec::Encode;
 use core::convert::TryFrom;
 
-use crate::types::ParentInfoCall;
-use frame_support::{assert_noop, assert_ok, BoundedVec};
+use crate::types::{
+  AttributeSchema, AttributeSchemaField, AttributeType, FreezePolicy, FruniqueAction, FruniqueRole,
+  InvitationStatus, ParentInfoCall, Permission, VerificationPolicy, CID,
+};
+use frame_support::{
+  assert_noop, assert_ok,
+  traits::{Get, Hooks},
+  weights::Weight,
+  BoundedVec,
+};
+use pallet_rbac::{purge::StoragePurge, types::RoleBasedAccessControl};
+use sp_runtime::Permill;
 pub struct ExtBuilder;
 
 // helper function to set BoundedVec
@@ -55,6 +65,10 @@ fn dummy_parent(collection_id: u32, parent_id: u32) -> ParentInfoCall<Test> {
   ParentInfoCall { collection_id, parent_id, parent_percentage: 10, is_hierarchical: true }
 }
 
+fn children(children: Vec<(u32, u32)>) -> BoundedVec<(u32, u32), ChildMaxLen> {
+  BoundedVec::try_from(children).unwrap()
+}
+
 #[test]
 fn create_collection_works() {
   new_test_ext().execute_with(|| {
@@ -148,8 +162,13 @@ fn set_attributes_works() {
 fn invite_collaborator_works() {
   new_test_ext().execute_with(|| {
     // Create a collection
+    System::set_block_number(1);
     assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
-    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 2));
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Collaborator));
+    System::assert_last_event(
+      Event::InvitedToCollaborate(1, 2, 0, FruniqueRole::Collaborator).into(),
+    );
+    assert_ok!(Fruniques::accept_invitation(RuntimeOrigin::signed(2), 0));
   });
 }
 
@@ -183,6 +202,21 @@ fn verify_by_owner_works() {
   });
 }
 
+#[test]
+fn verify_by_verifier_works() {
+  new_test_ext().execute_with(|| {
+    // Create a collection
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    // Spawn an NFT
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    // Invite a verifier
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Verifier));
+    assert_ok!(Fruniques::accept_invitation(RuntimeOrigin::signed(2), 0));
+    // Verify
+    assert_ok!(Fruniques::verify(RuntimeOrigin::signed(2), 0, 0));
+  });
+}
+
 #[test]
 fn verify_by_neither_admin_nor_owner_fails() {
   new_test_ext().execute_with(|| {
@@ -191,7 +225,8 @@ fn verify_by_neither_admin_nor_owner_fails() {
     // Spawn an NFT
     assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
     // Invite collaborator
-    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 2));
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Collaborator));
+    assert_ok!(Fruniques::accept_invitation(RuntimeOrigin::signed(2), 0));
     // Verify
     assert_noop!(Fruniques::verify(RuntimeOrigin::signed(3), 0, 0), Error::<Test>::NotAuthorized);
   });
@@ -219,3 +254,1023 @@ fn verify_already_verified_fails() {
     );
   });
 }
+
+#[test]
+fn merge_all_children_restores_parent_weight() {
+  new_test_ext().execute_with(|| {
+    // Create a collection
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    // Spawn the parent and two children
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+    assert_ne!(Fruniques::frunique_info(0, 0).unwrap().weight, Permill::from_percent(100));
+    // Merge all the children back
+    assert_ok!(Fruniques::merge(RuntimeOrigin::signed(1), 0, 0, None));
+
+    let parent = Fruniques::frunique_info(0, 0).unwrap();
+    assert_eq!(parent.weight, Permill::from_percent(100));
+    assert!(parent.children.is_none());
+    assert!(!Fruniques::instance_exists(&0, &1));
+    assert!(!Fruniques::instance_exists(&0, &2));
+    assert!(Fruniques::frunique_info(0, 1).is_none());
+  });
+}
+
+#[test]
+fn merge_some_children_works() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+    // Only the second child is merged back
+    assert_ok!(Fruniques::merge(RuntimeOrigin::signed(1), 0, 0, Some(children(vec![(0, 2)]))));
+
+    let parent = Fruniques::frunique_info(0, 0).unwrap();
+    assert_eq!(parent.weight, Permill::from_percent(90));
+    assert_eq!(parent.children.unwrap().len(), 1);
+    assert!(Fruniques::instance_exists(&0, &1));
+    assert!(!Fruniques::instance_exists(&0, &2));
+  });
+}
+
+#[test]
+fn merge_leased_child_fails() {
+  new_test_ext().execute_with(|| {
+    System::set_block_number(1);
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+    assert_ok!(Fruniques::set_user(RuntimeOrigin::signed(1), 0, 1, 3, 10, true));
+
+    assert_noop!(
+      Fruniques::merge(RuntimeOrigin::signed(1), 0, 0, None),
+      Error::<Test>::FruniqueRented
+    );
+
+    // Once the lease expires the child can be merged and its lease is removed
+    System::set_block_number(10);
+    assert_ok!(Fruniques::merge(RuntimeOrigin::signed(1), 0, 0, None));
+    assert!(Fruniques::frunique_rentals(0, 1).is_none());
+    assert!(Fruniques::frunique_history(0, 1).is_empty());
+  });
+}
+
+#[test]
+fn merge_without_children_fails() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_noop!(
+      Fruniques::merge(RuntimeOrigin::signed(1), 0, 0, None),
+      Error::<Test>::NoChildrenToMerge
+    );
+  });
+}
+
+#[test]
+fn merge_by_non_owner_fails() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+    assert_noop!(Fruniques::merge(RuntimeOrigin::signed(2), 0, 0, None), Error::<Test>::NotOwner);
+  });
+}
+
+#[test]
+fn merge_divided_child_fails() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+    // The child is divided as well
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 1))
+    ));
+    assert_noop!(
+      Fruniques::merge(RuntimeOrigin::signed(1), 0, 0, None),
+      Error::<Test>::ChildAlreadyDivided
+    );
+    // Not a child of the parent
+    assert_noop!(
+      Fruniques::merge(RuntimeOrigin::signed(1), 0, 0, Some(children(vec![(0, 2)]))),
+      Error::<Test>::ChildNotFound
+    );
+    // Merging from the bottom up works
+    assert_ok!(Fruniques::merge(RuntimeOrigin::signed(1), 0, 1, None));
+    assert_ok!(Fruniques::merge(RuntimeOrigin::signed(1), 0, 0, None));
+    assert_eq!(Fruniques::frunique_info(0, 0).unwrap().weight, Permill::from_percent(100));
+  });
+}
+
+#[test]
+fn fractionalize_and_redeem_works() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    // Lock the frunique and mint 100 shares
+    assert_ok!(Fruniques::fractionalize(RuntimeOrigin::signed(1), 0, 0, 1, 100));
+
+    assert_eq!(Fruniques::admin_of(&0, &0), Some(Fruniques::pallet_account()));
+    assert!(Fruniques::is_frozen(&0, &0));
+    assert_eq!(Assets::balance(1, 1), 100);
+    assert_noop!(
+      Fruniques::fractionalize(RuntimeOrigin::signed(1), 0, 0, 2, 100),
+      Error::<Test>::NotOwner
+    );
+
+    // Shares can be traded, but the full supply is needed to unlock the frunique
+    assert_ok!(Assets::transfer(RuntimeOrigin::signed(1), 1, 2, 40));
+    assert_noop!(
+      Fruniques::redeem_fractions(RuntimeOrigin::signed(1), 0, 0),
+      Error::<Test>::IncompleteFractionSupply
+    );
+    assert_ok!(Assets::transfer(RuntimeOrigin::signed(1), 1, 2, 60));
+    assert_ok!(Fruniques::redeem_fractions(RuntimeOrigin::signed(2), 0, 0));
+
+    assert_eq!(Fruniques::admin_of(&0, &0), Some(2));
+    assert!(!Fruniques::is_frozen(&0, &0));
+    assert_eq!(Assets::total_supply(1), 0);
+    assert!(Fruniques::fungible_fractions(0, 0).is_none());
+    // The fraction asset is destroyed, so its id can be used again
+    assert_ok!(Fruniques::fractionalize(RuntimeOrigin::signed(2), 0, 0, 1, 50));
+    assert_eq!(Assets::balance(1, 2), 50);
+  });
+}
+
+#[test]
+fn fractionalize_with_zero_supply_fails() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_noop!(
+      Fruniques::fractionalize(RuntimeOrigin::signed(1), 0, 0, 1, 0),
+      Error::<Test>::InvalidFractionSupply
+    );
+    assert_noop!(
+      Fruniques::redeem_fractions(RuntimeOrigin::signed(1), 0, 0),
+      Error::<Test>::FruniqueNotFractionalized
+    );
+  });
+}
+
+fn dummy_policy(
+  required_approvals: u32,
+  verifiers: Vec<u64>,
+  propagate_to_children: bool,
+) -> VerificationPolicy<Test> {
+  VerificationPolicy {
+    required_approvals,
+    verifiers: BoundedVec::try_from(verifiers).expect("Too many verifiers"),
+    propagate_to_children,
+  }
+}
+
+fn dummy_cid() -> CID {
+  BoundedVec::try_from(b"QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG".to_vec())
+    .expect("Error on creating the CID")
+}
+
+#[test]
+fn verification_requires_all_attestations_of_the_policy() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Verifier));
+    assert_ok!(Fruniques::accept_invitation(RuntimeOrigin::signed(2), 0));
+    // 2 of 2 verifiers are needed
+    assert_ok!(Fruniques::set_verification_policy(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_policy(2, vec![1, 2], false)
+    ));
+
+    assert_ok!(Fruniques::attest(RuntimeOrigin::signed(1), 0, 0, dummy_cid()));
+    assert!(!Fruniques::frunique_info(0, 0).unwrap().verified);
+    assert_noop!(
+      Fruniques::attest(RuntimeOrigin::signed(1), 0, 0, dummy_cid()),
+      Error::<Test>::AlreadyAttested
+    );
+    assert_noop!(
+      Fruniques::attest(RuntimeOrigin::signed(3), 0, 0, dummy_cid()),
+      Error::<Test>::NotAuthorized
+    );
+
+    assert_ok!(Fruniques::attest(RuntimeOrigin::signed(2), 0, 0, dummy_cid()));
+    let frunique = Fruniques::frunique_info(0, 0).unwrap();
+    assert!(frunique.verified);
+    assert_eq!(frunique.verified_by, Some(2));
+    assert_eq!(Fruniques::frunique_verified(0, 0), Some(true));
+    assert_eq!(Fruniques::frunique_attestations(0, 0).len(), 2);
+  });
+}
+
+#[test]
+fn set_invalid_verification_policy_fails() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_noop!(
+      Fruniques::set_verification_policy(
+        RuntimeOrigin::signed(1),
+        0,
+        dummy_policy(0, vec![], false)
+      ),
+      Error::<Test>::InvalidVerificationPolicy
+    );
+    assert_noop!(
+      Fruniques::set_verification_policy(
+        RuntimeOrigin::signed(1),
+        0,
+        dummy_policy(2, vec![1], false)
+      ),
+      Error::<Test>::InvalidVerificationPolicy
+    );
+    // Verifiers must be allowed to verify in the collection
+    assert_noop!(
+      Fruniques::set_verification_policy(
+        RuntimeOrigin::signed(1),
+        0,
+        dummy_policy(1, vec![3], false)
+      ),
+      Error::<Test>::UserNotInCollection
+    );
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 3, FruniqueRole::Collaborator));
+    assert_ok!(Fruniques::accept_invitation(RuntimeOrigin::signed(3), 0));
+    assert_noop!(
+      Fruniques::set_verification_policy(
+        RuntimeOrigin::signed(1),
+        0,
+        dummy_policy(1, vec![3], false)
+      ),
+      Error::<Test>::UserNotInCollection
+    );
+    // Only the owner of the collection can set the policy
+    assert_noop!(
+      Fruniques::set_verification_policy(
+        RuntimeOrigin::signed(2),
+        0,
+        dummy_policy(1, vec![], false)
+      ),
+      Error::<Test>::NotOwner
+    );
+  });
+}
+
+#[test]
+fn revoke_verification_works() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_noop!(
+      Fruniques::revoke_verification(RuntimeOrigin::signed(1), 0, 0, 0),
+      Error::<Test>::FruniqueNotVerified
+    );
+    assert_ok!(Fruniques::verify(RuntimeOrigin::signed(1), 0, 0));
+    assert_noop!(
+      Fruniques::revoke_verification(RuntimeOrigin::signed(2), 0, 0, 0),
+      Error::<Test>::NotAuthorized
+    );
+    assert_ok!(Fruniques::revoke_verification(RuntimeOrigin::signed(1), 0, 0, 0));
+
+    let frunique = Fruniques::frunique_info(0, 0).unwrap();
+    assert!(!frunique.verified);
+    assert!(frunique.verified_by.is_none());
+    assert!(Fruniques::frunique_verified(0, 0).is_none());
+    assert!(Fruniques::frunique_attestations(0, 0).is_empty());
+    // It can be verified again
+    assert_ok!(Fruniques::verify(RuntimeOrigin::signed(1), 0, 0));
+  });
+}
+
+#[test]
+fn verification_propagates_to_children() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    // Without propagation children are not verified
+    assert_ok!(Fruniques::verify(RuntimeOrigin::signed(1), 0, 0));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+    assert!(!Fruniques::frunique_info(0, 1).unwrap().verified);
+
+    assert_ok!(Fruniques::set_verification_policy(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_policy(1, vec![], true)
+    ));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+    let child = Fruniques::frunique_info(0, 2).unwrap();
+    assert!(child.verified);
+    assert_eq!(child.verified_by, Some(1));
+
+    // The grandchild inherits the verification too
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 2))
+    ));
+    assert!(Fruniques::frunique_info(0, 3).unwrap().verified);
+
+    // Revoking the parent revokes the inherited verification, as long as the descendants fit in
+    // the given max
+    assert_noop!(
+      Fruniques::revoke_verification(RuntimeOrigin::signed(1), 0, 0, 1),
+      Error::<Test>::TooManyDescendants
+    );
+    assert_ok!(Fruniques::revoke_verification(RuntimeOrigin::signed(1), 0, 0, 2));
+    assert!(!Fruniques::frunique_info(0, 2).unwrap().verified);
+    assert!(Fruniques::frunique_verified(0, 2).is_none());
+    assert!(!Fruniques::frunique_info(0, 3).unwrap().verified);
+  });
+}
+
+fn bounded<S: frame_support::traits::Get<u32>>(value: &[u8]) -> BoundedVec<u8, S> {
+  BoundedVec::<u8, S>::try_from(value.to_vec()).expect("Value too long")
+}
+
+fn dummy_schema() -> AttributeSchema<Test> {
+  BoundedVec::try_from(vec![
+    AttributeSchemaField {
+      key: bounded(b"amount"),
+      value_type: AttributeType::Int,
+      required: true,
+      mutable: false,
+    },
+    AttributeSchemaField {
+      key: bounded(b"state"),
+      value_type: AttributeType::Enum(
+        BoundedVec::try_from(vec![bounded(b"CA"), bounded(b"NY")]).unwrap(),
+      ),
+      required: false,
+      mutable: true,
+    },
+    AttributeSchemaField {
+      key: bounded(b"issued"),
+      value_type: AttributeType::Date,
+      required: false,
+      mutable: false,
+    },
+  ])
+  .expect("Too many fields")
+}
+
+fn attribute(key: &[u8], value: &[u8]) -> (BoundedVec<u8, KeyLimit>, BoundedVec<u8, ValueLimit>) {
+  (bounded(key), bounded(value))
+}
+
+#[test]
+fn attribute_types_validate_values() {
+  assert!(AttributeType::<Test>::Int.is_valid(b"-42"));
+  assert!(!AttributeType::<Test>::Int.is_valid(b"4.2"));
+  assert!(!AttributeType::<Test>::Int.is_valid(b"-"));
+  assert!(AttributeType::<Test>::Date.is_valid(b"2023-02-28"));
+  assert!(!AttributeType::<Test>::Date.is_valid(b"2023-13-01"));
+  assert!(!AttributeType::<Test>::Date.is_valid(b"23-02-2023"));
+  assert!(AttributeType::<Test>::Cid.is_valid(&dummy_cid()));
+  assert!(!AttributeType::<Test>::Cid.is_valid(b"not a cid"));
+}
+
+#[test]
+fn attribute_schema_is_enforced_on_spawn() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_noop!(
+      Fruniques::set_attribute_schema(RuntimeOrigin::signed(2), 0, dummy_schema()),
+      Error::<Test>::NotOwner
+    );
+    assert_ok!(Fruniques::set_attribute_schema(RuntimeOrigin::signed(1), 0, dummy_schema()));
+
+    assert_noop!(
+      Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None),
+      Error::<Test>::MissingRequiredAttribute
+    );
+    assert_noop!(
+      Fruniques::spawn(
+        RuntimeOrigin::signed(1),
+        0,
+        dummy_description(),
+        Some(vec![attribute(b"amount", b"ten")]),
+        None
+      ),
+      Error::<Test>::InvalidAttributeValue
+    );
+    assert_noop!(
+      Fruniques::spawn(
+        RuntimeOrigin::signed(1),
+        0,
+        dummy_description(),
+        Some(vec![attribute(b"amount", b"10"), attribute(b"color", b"red")]),
+        None
+      ),
+      Error::<Test>::AttributeNotInSchema
+    );
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      Some(vec![attribute(b"amount", b"10"), attribute(b"state", b"CA")]),
+      None
+    ));
+  });
+}
+
+#[test]
+fn attribute_schema_is_enforced_on_set_attributes() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::set_attribute_schema(RuntimeOrigin::signed(1), 0, dummy_schema()));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      Some(vec![attribute(b"amount", b"10")]),
+      None
+    ));
+
+    assert_noop!(
+      Fruniques::set_attributes(RuntimeOrigin::signed(1), 0, 0, vec![attribute(b"amount", b"20")]),
+      Error::<Test>::ImmutableAttribute
+    );
+    assert_noop!(
+      Fruniques::set_attributes(RuntimeOrigin::signed(1), 0, 0, vec![attribute(b"state", b"TX")]),
+      Error::<Test>::InvalidAttributeValue
+    );
+    assert_ok!(Fruniques::set_attributes(
+      RuntimeOrigin::signed(1),
+      0,
+      0,
+      vec![attribute(b"state", b"NY"), attribute(b"issued", b"2023-01-31")]
+    ));
+    // Immutable attributes can be set once
+    assert_noop!(
+      Fruniques::set_attributes(
+        RuntimeOrigin::signed(1),
+        0,
+        0,
+        vec![attribute(b"issued", b"2023-02-01")]
+      ),
+      Error::<Test>::ImmutableAttribute
+    );
+  });
+}
+
+#[test]
+fn children_inherit_the_attribute_schema() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::set_attribute_schema(RuntimeOrigin::signed(1), 0, dummy_schema()));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      Some(vec![attribute(b"amount", b"10")]),
+      None
+    ));
+    // The child takes the required values from its parent
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+    assert_eq!(Fruniques::get_nft_attribute(&0, &1, b"amount").to_vec(), b"10".to_vec());
+
+    // A child in a collection without schema follows the schema of its parent
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_noop!(
+      Fruniques::spawn(
+        RuntimeOrigin::signed(1),
+        1,
+        dummy_description(),
+        Some(vec![attribute(b"state", b"TX")]),
+        Some(dummy_parent(0, 0))
+      ),
+      Error::<Test>::InvalidAttributeValue
+    );
+  });
+}
+
+#[test]
+fn provenance_returns_ancestors_and_descendants() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    // 0 -> 1 -> 2 and 0 -> 3
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 1))
+    ));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+
+    let provenance = Fruniques::get_provenance(0, 1).unwrap();
+    assert_eq!(provenance.frunique.parent, Some((0, 0)));
+    assert_eq!(provenance.frunique.owner, Some(1));
+    assert_eq!(provenance.ancestors.len(), 1);
+    assert_eq!((provenance.ancestors[0].collection_id, provenance.ancestors[0].item_id), (0, 0));
+    assert_eq!(provenance.descendants.len(), 1);
+    assert_eq!(provenance.descendants[0].parent, Some((0, 1)));
+
+    let provenance = Fruniques::get_provenance(0, 0).unwrap();
+    assert!(provenance.ancestors.is_empty());
+    let descendants: Vec<(u32, u32)> = provenance
+      .descendants
+      .iter()
+      .map(|node| (node.collection_id, node.item_id))
+      .collect();
+    assert_eq!(descendants, vec![(0, 1), (0, 2), (0, 3)]);
+    assert_eq!(provenance.descendants[0].weight_inherited, Some(Permill::from_percent(10)));
+
+    assert!(Fruniques::get_provenance(0, 4).is_none());
+  });
+}
+
+#[test]
+fn history_records_actions_and_drops_the_oldest() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::spawn(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      None,
+      Some(dummy_parent(0, 0))
+    ));
+
+    let actions: Vec<FruniqueAction> = Fruniques::frunique_history(0, 0)
+      .iter()
+      .map(|entry| entry.action.clone())
+      .collect();
+    assert_eq!(actions, vec![FruniqueAction::Spawned, FruniqueAction::Divided]);
+    assert_eq!(Fruniques::frunique_history(0, 1)[0].who, Some(1));
+
+    // The history keeps only the most recent entries
+    for _ in 0..MaxHistoryLength::get() {
+      assert_ok!(Fruniques::do_freeze(&0, 0));
+    }
+    let history = Fruniques::frunique_history(0, 0);
+    assert_eq!(history.len() as u32, MaxHistoryLength::get());
+    assert!(history.iter().all(|entry| entry.action == FruniqueAction::Frozen));
+  });
+}
+
+#[test]
+fn spawn_batch_works() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::spawn_batch(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      BoundedVec::try_from(vec![
+        Some(vec![attribute(b"lot", b"1")]),
+        None,
+        Some(vec![attribute(b"lot", b"3")]),
+      ])
+      .unwrap()
+    ));
+
+    assert_eq!(Fruniques::next_frunique(0), 4);
+    for item in 1..4 {
+      assert!(Fruniques::instance_exists(&0, &item));
+      assert_eq!(Fruniques::get_nft_metadata(0, item), dummy_description());
+    }
+    assert_eq!(Fruniques::get_nft_attribute(&0, &3, b"lot"), bounded::<ValueLimit>(b"3"));
+  });
+}
+
+#[test]
+fn spawn_batch_is_bounded_and_atomic() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_noop!(
+      Fruniques::spawn_batch(
+        RuntimeOrigin::signed(1),
+        0,
+        dummy_description(),
+        BoundedVec::default()
+      ),
+      Error::<Test>::EmptyBatch
+    );
+    assert_noop!(
+      Fruniques::do_spawn_batch(
+        0,
+        1,
+        dummy_description(),
+        vec![None; MaxBatchSpawn::get() as usize + 1]
+      ),
+      Error::<Test>::BatchTooLarge
+    );
+
+    // The second item breaks the schema, so no item is spawned
+    assert_ok!(Fruniques::set_attribute_schema(RuntimeOrigin::signed(1), 0, dummy_schema()));
+    assert_noop!(
+      Fruniques::do_spawn_batch(
+        0,
+        1,
+        dummy_description(),
+        vec![Some(vec![attribute(b"amount", b"10")]), None]
+      ),
+      Error::<Test>::MissingRequiredAttribute
+    );
+    assert_eq!(Fruniques::next_frunique(0), 0);
+    assert!(!Fruniques::instance_exists(&0, &0));
+  });
+}
+
+#[test]
+fn freezers_can_freeze_and_thaw() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+
+    assert_noop!(
+      Fruniques::freeze(RuntimeOrigin::signed(2), 0, 0, None),
+      Error::<Test>::NotAuthorized
+    );
+    assert_noop!(Fruniques::add_freezer(RuntimeOrigin::signed(2), 0, 2), Error::<Test>::NotOwner);
+    assert_ok!(Fruniques::add_freezer(RuntimeOrigin::signed(1), 0, 2));
+
+    assert_ok!(Fruniques::freeze(RuntimeOrigin::signed(2), 0, 0, None));
+    assert!(Fruniques::is_frozen(&0, &0));
+    assert_noop!(
+      Fruniques::freeze(RuntimeOrigin::signed(2), 0, 0, None),
+      Error::<Test>::FruniqueFrozen
+    );
+    assert_ok!(Fruniques::thaw(RuntimeOrigin::signed(2), 0, 0));
+    assert!(!Fruniques::is_frozen(&0, &0));
+    assert!(Fruniques::frunique_freezes(0, 0).is_none());
+
+    assert_ok!(Fruniques::remove_freezer(RuntimeOrigin::signed(1), 0, 2));
+    assert_noop!(
+      Fruniques::freeze(RuntimeOrigin::signed(2), 0, 0, None),
+      Error::<Test>::NotAuthorized
+    );
+  });
+}
+
+#[test]
+fn holders_can_freeze_only_when_the_policy_allows_it() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Collaborator));
+    assert_ok!(Fruniques::accept_invitation(RuntimeOrigin::signed(2), 0));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(2), 0, dummy_description(), None, None));
+
+    assert_noop!(
+      Fruniques::freeze(RuntimeOrigin::signed(2), 0, 0, None),
+      Error::<Test>::NotAuthorized
+    );
+    assert_ok!(Fruniques::set_freeze_policy(
+      RuntimeOrigin::signed(1),
+      0,
+      FreezePolicy { owners_can_freeze: true, max_lock_period: None }
+    ));
+    assert_ok!(Fruniques::freeze(RuntimeOrigin::signed(2), 0, 0, None));
+    assert_ok!(Fruniques::thaw(RuntimeOrigin::signed(2), 0, 0));
+
+    // Fruniques frozen by a marketplace can't be thawed through the extrinsic
+    assert_ok!(Fruniques::do_freeze(&0, 0));
+    assert_noop!(Fruniques::thaw(RuntimeOrigin::signed(1), 0, 0), Error::<Test>::FruniqueNotFrozen);
+  });
+}
+
+#[test]
+fn time_locked_fruniques_are_thawed_automatically() {
+  new_test_ext().execute_with(|| {
+    System::set_block_number(1);
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Collaborator));
+    assert_ok!(Fruniques::accept_invitation(RuntimeOrigin::signed(2), 0));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(2), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::set_freeze_policy(
+      RuntimeOrigin::signed(1),
+      0,
+      FreezePolicy { owners_can_freeze: true, max_lock_period: Some(10) }
+    ));
+
+    assert_noop!(
+      Fruniques::freeze(RuntimeOrigin::signed(2), 0, 0, None),
+      Error::<Test>::InvalidTimeLock
+    );
+    assert_noop!(
+      Fruniques::freeze(RuntimeOrigin::signed(2), 0, 0, Some(20)),
+      Error::<Test>::InvalidTimeLock
+    );
+    assert_ok!(Fruniques::freeze(RuntimeOrigin::signed(2), 0, 0, Some(5)));
+    assert_eq!(Fruniques::scheduled_thaws(5).len(), 1);
+
+    // The holder committed to the time lock
+    assert_noop!(
+      Fruniques::thaw(RuntimeOrigin::signed(2), 0, 0),
+      Error::<Test>::FruniqueTimeLocked
+    );
+
+    Fruniques::on_initialize(4);
+    assert!(Fruniques::is_frozen(&0, &0));
+    Fruniques::on_initialize(5);
+    assert!(!Fruniques::is_frozen(&0, &0));
+    assert!(Fruniques::frunique_freezes(0, 0).is_none());
+    assert!(Fruniques::scheduled_thaws(5).is_empty());
+  });
+}
+
+#[test]
+fn invitations_must_be_accepted() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_noop!(
+      Fruniques::invite(RuntimeOrigin::signed(3), 0, 2, FruniqueRole::Collaborator),
+      Error::<Test>::NoPermission
+    );
+    assert_noop!(
+      Fruniques::invite(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Owner),
+      Error::<Test>::InvalidRole
+    );
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Collaborator));
+    assert_noop!(
+      Fruniques::invite(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Holder),
+      Error::<Test>::InvitationAlreadyPending
+    );
+
+    // The role is only granted once the invitation is accepted
+    assert_noop!(
+      Fruniques::spawn(RuntimeOrigin::signed(2), 0, dummy_description(), None, None),
+      Error::<Test>::UserNotInCollection
+    );
+    assert_noop!(
+      Fruniques::accept_invitation(RuntimeOrigin::signed(3), 0),
+      Error::<Test>::InvitationNotFound
+    );
+    assert_ok!(Fruniques::accept_invitation(RuntimeOrigin::signed(2), 0));
+    assert_eq!(Fruniques::invitations(0, 2).unwrap().status, InvitationStatus::Accepted);
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(2), 0, dummy_description(), None, None));
+  });
+}
+
+#[test]
+fn invitations_can_be_declined_and_expire() {
+  new_test_ext().execute_with(|| {
+    System::set_block_number(1);
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Admin));
+    assert_ok!(Fruniques::decline_invitation(RuntimeOrigin::signed(2), 0));
+    assert_eq!(Fruniques::invitations(0, 2).unwrap().status, InvitationStatus::Declined);
+    assert_noop!(
+      Fruniques::accept_invitation(RuntimeOrigin::signed(2), 0),
+      Error::<Test>::InvitationNotFound
+    );
+
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 3, FruniqueRole::Holder));
+    System::set_block_number(1 + InvitationExpiration::get() + 1);
+    assert_eq!(
+      Fruniques::invitations(0, 3).unwrap().status_at(System::block_number()),
+      InvitationStatus::Expired
+    );
+    assert_noop!(
+      Fruniques::accept_invitation(RuntimeOrigin::signed(3), 0),
+      Error::<Test>::InvitationExpired
+    );
+    // Expired invitations can be sent again
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 3, FruniqueRole::Holder));
+    assert_ok!(Fruniques::accept_invitation(RuntimeOrigin::signed(3), 0));
+  });
+}
+
+#[test]
+fn revoke_removes_roles_and_pending_invitations() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Collaborator));
+    assert_ok!(Fruniques::accept_invitation(RuntimeOrigin::signed(2), 0));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(2), 0, dummy_description(), None, None));
+
+    assert_noop!(
+      Fruniques::revoke(RuntimeOrigin::signed(1), 0, 1, FruniqueRole::Owner),
+      Error::<Test>::InvalidRole
+    );
+    assert_ok!(Fruniques::revoke(RuntimeOrigin::signed(1), 0, 2, FruniqueRole::Collaborator));
+    assert!(Fruniques::invitations(0, 2).is_none());
+    assert_noop!(
+      Fruniques::spawn(RuntimeOrigin::signed(2), 0, dummy_description(), None, None),
+      Error::<Test>::UserNotInCollection
+    );
+
+    // A pending invitation is cancelled without touching the roles
+    assert_ok!(Fruniques::invite(RuntimeOrigin::signed(1), 0, 3, FruniqueRole::Collaborator));
+    assert_ok!(Fruniques::revoke(RuntimeOrigin::signed(1), 0, 3, FruniqueRole::Collaborator));
+    assert_noop!(
+      Fruniques::accept_invitation(RuntimeOrigin::signed(3), 0),
+      Error::<Test>::InvitationNotFound
+    );
+  });
+}
+
+#[test]
+fn usage_rights_can_be_leased_until_they_expire() {
+  new_test_ext().execute_with(|| {
+    System::set_block_number(1);
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+
+    assert_noop!(
+      Fruniques::set_user(RuntimeOrigin::signed(2), 0, 0, 3, 10, false),
+      Error::<Test>::NotOwner
+    );
+    assert_noop!(
+      Fruniques::set_user(RuntimeOrigin::signed(1), 0, 0, 3, 1, false),
+      Error::<Test>::InvalidRentalPeriod
+    );
+    assert_ok!(Fruniques::set_user(RuntimeOrigin::signed(1), 0, 0, 3, 10, false));
+    assert_eq!(Fruniques::user_of(0, 0), Some(3));
+    assert_eq!(Fruniques::admin_of(&0, &0), Some(1));
+    assert_noop!(
+      Fruniques::set_user(RuntimeOrigin::signed(1), 0, 0, 4, 20, false),
+      Error::<Test>::RentalActive
+    );
+    assert_noop!(Fruniques::ensure_sellable(0, 0), Error::<Test>::FruniqueRented);
+
+    // The usage rights go back to the owner once the lease expires
+    System::set_block_number(10);
+    assert_eq!(Fruniques::user_of(0, 0), Some(1));
+    assert_ok!(Fruniques::ensure_sellable(0, 0));
+    assert_ok!(Fruniques::set_user(RuntimeOrigin::signed(1), 0, 0, 4, 20, true));
+    assert_ok!(Fruniques::ensure_sellable(0, 0));
+    assert_eq!(
+      Fruniques::frunique_history(0, 0).last().map(|entry| entry.action.clone()),
+      Some(FruniqueAction::Rented)
+    );
+  });
+}
+
+#[test]
+fn users_can_end_their_rental() {
+  new_test_ext().execute_with(|| {
+    System::set_block_number(1);
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_noop!(
+      Fruniques::end_rental(RuntimeOrigin::signed(3), 0, 0),
+      Error::<Test>::NoActiveRental
+    );
+    assert_ok!(Fruniques::set_user(RuntimeOrigin::signed(1), 0, 0, 3, 10, false));
+
+    assert_noop!(
+      Fruniques::end_rental(RuntimeOrigin::signed(1), 0, 0),
+      Error::<Test>::NotAuthorized
+    );
+    assert_ok!(Fruniques::end_rental(RuntimeOrigin::signed(3), 0, 0));
+    assert!(Fruniques::frunique_rentals(0, 0).is_none());
+    assert_eq!(Fruniques::user_of(0, 0), Some(1));
+    System::assert_last_event(Event::FruniqueRentalEnded(3, 0, 0).into());
+  });
+}
+
+#[test]
+fn kill_storage_purges_in_multiple_blocks() {
+  new_test_ext().execute_with(|| {
+    System::set_block_number(1);
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn_batch(
+      RuntimeOrigin::signed(1),
+      0,
+      dummy_description(),
+      BoundedVec::try_from(vec![None, None, None]).unwrap()
+    ));
+
+    // Only a couple of keys can be removed right away
+    assert!(!Fruniques::start_purge(2).complete);
+    assert!(Fruniques::storage_purge_progress().is_some());
+    // Calling it again doesn't restart the purge
+    assert_noop!(
+      Fruniques::kill_storage(RuntimeOrigin::root()),
+      Error::<Test>::StoragePurgeInProgress
+    );
+
+    Fruniques::on_idle(1, Weight::from_ref_time(u64::MAX));
+    assert!(Fruniques::storage_purge_progress().is_none());
+    assert!(Fruniques::frunique_info(0, 2).is_none());
+    assert_eq!(Fruniques::next_frunique(0), 0);
+    System::assert_last_event(Event::StoragePurged.into());
+  });
+}
+
+#[test]
+fn kill_storage_works() {
+  new_test_ext().execute_with(|| {
+    System::set_block_number(1);
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::kill_storage(RuntimeOrigin::root()));
+    assert!(Fruniques::frunique_info(0, 0).is_none());
+    assert!(Fruniques::storage_purge_progress().is_none());
+    System::assert_last_event(Event::StoragePurged.into());
+  });
+}
+
+#[test]
+fn initial_setup_adds_missing_roles_and_permissions() {
+  new_test_ext().execute_with(|| {
+    let pallet = Fruniques::pallet_id();
+    // A setup made before the Verify permission existed
+    assert_ok!(RBAC::do_revoke_permission_from_role(
+      pallet.clone(),
+      FruniqueRole::Owner.id(),
+      Permission::Verify.id()
+    ));
+    assert!(RBAC::is_permission_linked_to_role(
+      pallet.clone(),
+      &FruniqueRole::Owner.id(),
+      &Permission::Verify.id()
+    )
+    .is_err());
+
+    // Running the setup again only adds what's missing
+    assert_ok!(Fruniques::initial_setup(RuntimeOrigin::root(), 1));
+    assert_ok!(RBAC::is_permission_linked_to_role(
+      pallet.clone(),
+      &FruniqueRole::Owner.id(),
+      &Permission::Verify.id()
+    ));
+    assert_ok!(RBAC::is_role_linked_to_pallet(pallet, &FruniqueRole::Verifier.id()));
+  });
+}
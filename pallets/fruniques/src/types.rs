@@ -12,6 +12,32 @@ pub type Children<T> = BoundedVec<ChildInfo<T>, <T as Config>::ChildMaxLen>;
 
 pub type StringLimit<T> = BoundedVec<u8, <T as pallet_uniques::Config>::StringLimit>;
 
+/// Defines the type used by fields that store an IPFS CID
+pub type CID = BoundedVec<u8, ConstU32<100>>;
+pub type Verifiers<T> =
+  BoundedVec<<T as frame_system::Config>::AccountId, <T as Config>::MaxVerifiers>;
+pub type Attestations<T> = BoundedVec<Attestation<T>, <T as Config>::MaxVerifiers>;
+pub type EnumOptions<T> = BoundedVec<AttributeValue<T>, <T as Config>::MaxSchemaFields>;
+pub type AttributeSchema<T> = BoundedVec<AttributeSchemaField<T>, <T as Config>::MaxSchemaFields>;
+pub type HistoryEntryOf<T> =
+  HistoryEntry<<T as frame_system::Config>::AccountId, <T as frame_system::Config>::BlockNumber>;
+pub type History<T> = BoundedVec<HistoryEntryOf<T>, <T as Config>::MaxHistoryLength>;
+pub type ScheduledThawsOf<T> = BoundedVec<
+  (<T as pallet_uniques::Config>::CollectionId, <T as pallet_uniques::Config>::ItemId),
+  <T as Config>::MaxThawsPerBlock,
+>;
+pub type ProvenanceNodeOf<T> = ProvenanceNode<
+  <T as frame_system::Config>::AccountId,
+  <T as pallet_uniques::Config>::CollectionId,
+  <T as pallet_uniques::Config>::ItemId,
+>;
+pub type ProvenanceOf<T> = Provenance<
+  <T as frame_system::Config>::AccountId,
+  <T as pallet_uniques::Config>::CollectionId,
+  <T as pallet_uniques::Config>::ItemId,
+  <T as frame_system::Config>::BlockNumber,
+>;
+
 pub type CollectionId = u32;
 pub type ItemId = u32;
 
@@ -133,6 +159,247 @@ impl<T: Config> FruniqueData<T> {
   }
 }
 
+pub type FractionAssetId<T> = <T as pallet_mapped_assets::Config>::AssetId;
+pub type FractionBalance<T> = <T as pallet_mapped_assets::Config>::Balance;
+
+/// Max number of leftover asset accounts and approvals removed when fractions are redeemed.
+pub const FRACTION_DESTROY_LIMIT: u32 = 10;
+
+/// A frunique locked in the pallet account, whose shares are represented by a dedicated
+/// pallet-mapped-assets asset.
+#[derive(Encode, Decode, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct FungibleFractionData<T: Config> {
+  pub asset_id: FractionAssetId<T>,
+  pub total_supply: FractionBalance<T>,
+  pub locked_by: T::AccountId,
+}
+
+/// Rules a collection follows to consider a frunique verified.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct VerificationPolicy<T: Config> {
+  /// Number of attestations needed to verify a frunique
+  pub required_approvals: u32,
+  /// Accounts allowed to attest. When empty, any account with the `Verify` permission can.
+  pub verifiers: Verifiers<T>,
+  /// Whether children spawned from a verified parent are verified as well
+  pub propagate_to_children: bool,
+}
+
+impl<T: Config> Default for VerificationPolicy<T> {
+  fn default() -> Self {
+    Self {
+      required_approvals: 1,
+      verifiers: Verifiers::<T>::default(),
+      propagate_to_children: false,
+    }
+  }
+}
+
+/// Rules a collection follows to freeze its fruniques.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct FreezePolicy<T: Config> {
+  /// Whether holders can freeze the fruniques they own
+  pub owners_can_freeze: bool,
+  /// When set, every freeze must be time-locked for at most this number of blocks
+  pub max_lock_period: Option<T::BlockNumber>,
+}
+
+impl<T: Config> Default for FreezePolicy<T> {
+  fn default() -> Self {
+    Self { owners_can_freeze: false, max_lock_period: None }
+  }
+}
+
+/// Freeze requested through the `freeze` extrinsic.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct FreezeInfo<T: Config> {
+  pub frozen_by: T::AccountId,
+  /// Block at which the frunique is thawed automatically
+  pub thaw_at: Option<T::BlockNumber>,
+}
+
+#[derive(Encode, Decode, Clone, Copy, Eq, PartialEq, RuntimeDebug, TypeInfo, MaxEncodedLen)]
+pub enum InvitationStatus {
+  Pending,
+  Accepted,
+  Declined,
+  Expired,
+}
+
+/// Invitation to join a collection with a given role.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct Invitation<T: Config> {
+  pub role: FruniqueRole,
+  pub invited_by: T::AccountId,
+  /// Last block at which the invitation can be accepted
+  pub expires_at: T::BlockNumber,
+  pub status: InvitationStatus,
+}
+
+impl<T: Config> Invitation<T> {
+  /// Status of the invitation at the given block, pending invitations past their expiration
+  /// are expired.
+  pub fn status_at(&self, now: T::BlockNumber) -> InvitationStatus {
+    match self.status {
+      InvitationStatus::Pending if now > self.expires_at => InvitationStatus::Expired,
+      status => status,
+    }
+  }
+}
+
+/// Usage rights of a frunique leased to a user, without transferring its ownership.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct RentalInfo<T: Config> {
+  pub user: T::AccountId,
+  /// Block at which the usage rights go back to the owner
+  pub expires_at: T::BlockNumber,
+  /// The frunique can be listed in a marketplace while the lease is active
+  pub allow_sale: bool,
+}
+
+impl<T: Config> RentalInfo<T> {
+  pub fn is_active(&self, now: T::BlockNumber) -> bool {
+    now < self.expires_at
+  }
+}
+
+/// A verifier's statement about a frunique, with an optional CID pointing to the evidence.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct Attestation<T: Config> {
+  pub verifier: T::AccountId,
+  pub evidence: Option<CID>,
+  pub attested_at: T::BlockNumber,
+}
+
+/// Type of the values an attribute of a collection schema can hold.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub enum AttributeType<T: Config> {
+  /// Free text
+  Text,
+  /// Decimal integer, optionally negative
+  Int,
+  /// Date formatted as `YYYY-MM-DD`
+  Date,
+  /// One of the given values
+  Enum(EnumOptions<T>),
+  /// IPFS CID
+  Cid,
+}
+
+impl<T: Config> AttributeType<T> {
+  pub fn is_valid(&self, value: &[u8]) -> bool {
+    match self {
+      Self::Text => true,
+      Self::Int => {
+        let digits = value.strip_prefix(b"-").unwrap_or(value);
+        !digits.is_empty() && digits.iter().all(u8::is_ascii_digit)
+      },
+      Self::Date => {
+        if value.len() != 10 || value[4] != b'-' || value[7] != b'-' {
+          return false;
+        }
+        let number = |digits: &[u8]| -> Option<u32> {
+          if !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+          }
+          Some(digits.iter().fold(0, |acc, digit| acc * 10 + (digit - b'0') as u32))
+        };
+        match (number(&value[0..4]), number(&value[5..7]), number(&value[8..10])) {
+          (Some(_), Some(month), Some(day)) => (1..=12).contains(&month) && (1..=31).contains(&day),
+          _ => false,
+        }
+      },
+      Self::Enum(options) => options.iter().any(|option| option.as_slice() == value),
+      Self::Cid => value.len() >= 46 && value.iter().all(u8::is_ascii_alphanumeric),
+    }
+  }
+}
+
+/// Declares a key of a collection schema, the type of its value and how it can be used.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct AttributeSchemaField<T: Config> {
+  pub key: AttributeKey<T>,
+  pub value_type: AttributeType<T>,
+  /// The attribute must be set when spawning a frunique
+  pub required: bool,
+  /// The attribute can be changed once it's set
+  pub mutable: bool,
+}
+
+/// Actions recorded in the history of a frunique
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug, TypeInfo, MaxEncodedLen)]
+pub enum FruniqueAction {
+  Spawned,
+  Divided,
+  Merged,
+  AttributesSet,
+  Attested,
+  Verified,
+  VerificationRevoked,
+  Frozen,
+  Thawed,
+  Redeemed,
+  Fractionalized,
+  FractionsRedeemed,
+  Rented,
+  RentalEnded,
+}
+
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug, TypeInfo, MaxEncodedLen)]
+pub struct HistoryEntry<AccountId, BlockNumber> {
+  pub action: FruniqueAction,
+  /// Account that performed the action, if any
+  pub who: Option<AccountId>,
+  pub block_number: BlockNumber,
+}
+
+/// Snapshot of a frunique as it's shown in its provenance tree
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug, TypeInfo)]
+pub struct ProvenanceNode<AccountId, CollectionId, ItemId> {
+  pub collection_id: CollectionId,
+  pub item_id: ItemId,
+  /// Parent of the frunique, `None` for roots
+  pub parent: Option<(CollectionId, ItemId)>,
+  /// Weight the frunique still holds
+  pub weight: Permill,
+  /// Weight the frunique took from its parent
+  pub weight_inherited: Option<Permill>,
+  pub owner: Option<AccountId>,
+  pub verified: bool,
+  pub verified_by: Option<AccountId>,
+  pub frozen: bool,
+  pub redeemed: bool,
+}
+
+/// Ancestor chain, descendant subtree and history of a frunique
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug, TypeInfo)]
+pub struct Provenance<AccountId, CollectionId, ItemId, BlockNumber> {
+  pub frunique: ProvenanceNode<AccountId, CollectionId, ItemId>,
+  /// From the direct parent up to the root
+  pub ancestors: Vec<ProvenanceNode<AccountId, CollectionId, ItemId>>,
+  /// Every descendant, each parent is listed before its children
+  pub descendants: Vec<ProvenanceNode<AccountId, CollectionId, ItemId>>,
+  pub history: Vec<HistoryEntry<AccountId, BlockNumber>>,
+}
+
 #[derive(
   Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, MaxEncodedLen, TypeInfo, Copy,
 )]
@@ -142,6 +409,8 @@ pub enum FruniqueRole {
   Collaborator,
   Collector,
   Holder,
+  Freezer,
+  Verifier,
 }
 
 impl Default for FruniqueRole {
@@ -158,6 +427,8 @@ impl FruniqueRole {
       Self::Collaborator => "Collaborator".as_bytes().to_vec(),
       Self::Collector => "Collector".as_bytes().to_vec(),
       Self::Holder => "Holder".as_bytes().to_vec(),
+      Self::Freezer => "Freezer".as_bytes().to_vec(),
+      Self::Verifier => "Verifier".as_bytes().to_vec(),
     }
   }
 
@@ -185,10 +456,26 @@ impl FruniqueRole {
     [Self::Holder.to_vec()].to_vec()
   }
 
+  pub fn get_freezer_roles() -> Vec<Vec<u8>> {
+    [Self::Freezer.to_vec()].to_vec()
+  }
+
+  pub fn get_verifier_roles() -> Vec<Vec<u8>> {
+    [Self::Verifier.to_vec()].to_vec()
+  }
+
   pub fn enum_to_vec() -> Vec<Vec<u8>> {
     use crate::types::FruniqueRole::*;
-    [Owner.to_vec(), Admin.to_vec(), Collaborator.to_vec(), Collector.to_vec(), Holder.to_vec()]
-      .to_vec()
+    [
+      Owner.to_vec(),
+      Admin.to_vec(),
+      Collaborator.to_vec(),
+      Collector.to_vec(),
+      Holder.to_vec(),
+      Freezer.to_vec(),
+      Verifier.to_vec(),
+    ]
+    .to_vec()
   }
 }
 
@@ -209,6 +496,8 @@ pub enum Permission {
   InviteCollaborator,
   /// Verify spawned NFTs
   Verify,
+  /// Freeze and thaw NFTs of a collection
+  Freeze,
 }
 
 impl Permission {
@@ -220,6 +509,7 @@ impl Permission {
       Self::Transfer => "Transfer".as_bytes().to_vec(),
       Self::InviteCollaborator => "InviteCollaborator".as_bytes().to_vec(),
       Self::Verify => "Verify".as_bytes().to_vec(),
+      Self::Freeze => "Freeze".as_bytes().to_vec(),
     }
   }
 
@@ -234,14 +524,22 @@ impl Permission {
 
   pub fn owner_permissions() -> Vec<Vec<u8>> {
     use crate::types::Permission::*;
-    [Mint.to_vec(), Burn.to_vec(), Transfer.to_vec(), InviteCollaborator.to_vec(), Verify.to_vec()]
-      .to_vec()
+    [
+      Mint.to_vec(),
+      Burn.to_vec(),
+      Transfer.to_vec(),
+      InviteCollaborator.to_vec(),
+      Verify.to_vec(),
+      Freeze.to_vec(),
+    ]
+    .to_vec()
   }
 
   pub fn admin_permissions() -> Vec<Vec<u8>> {
     use crate::types::Permission::*;
     let mut admin_permissions =
-      [Mint.to_vec(), Burn.to_vec(), InviteCollaborator.to_vec(), Verify.to_vec()].to_vec();
+      [Mint.to_vec(), Burn.to_vec(), InviteCollaborator.to_vec(), Verify.to_vec(), Freeze.to_vec()]
+        .to_vec();
     admin_permissions.append(&mut Permission::holder_permissions());
     admin_permissions
   }
@@ -260,4 +558,14 @@ impl Permission {
     use crate::types::Permission::*;
     [Transfer.to_vec()].to_vec()
   }
+
+  pub fn freezer_permissions() -> Vec<Vec<u8>> {
+    use crate::types::Permission::*;
+    [Freeze.to_vec()].to_vec()
+  }
+
+  pub fn verifier_permissions() -> Vec<Vec<u8>> {
+    use crate::types::Permission::*;
+    [Verify.to_vec()].to_vec()
+  }
 }
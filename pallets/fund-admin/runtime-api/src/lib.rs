@@ -0,0 +1,34 @@
+//! Runtime API definition for the fund admin pallet
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use pallet_fund_admin::types::{
+  DrawdownId, ExpenditureAmount, ExpenditureId, ExpenditureType, ProjectId,
+};
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+  /// Budget of the projects & their on-chain disbursements. The spend of an expenditure is the
+  /// sum of its approved and confirmed transactions.
+  pub trait FundAdminApi {
+    /// Budget left for each expenditure of a project, zero for expenditures over budget
+    fn remaining_budget_by_expenditure(
+      project_id: ProjectId,
+    ) -> Vec<(ExpenditureId, ExpenditureAmount)>;
+
+    /// Budget left for each expenditure type of a project
+    fn remaining_budget_by_expenditure_type(
+      project_id: ProjectId,
+    ) -> Vec<(ExpenditureType, ExpenditureAmount)>;
+
+    /// Expenditures that would go over budget if a drawdown is approved, with the amount over
+    /// budget
+    fn drawdown_over_budget(
+      project_id: ProjectId,
+      drawdown_id: DrawdownId,
+    ) -> Vec<(ExpenditureId, ExpenditureAmount)>;
+
+    /// Whether the on-chain disbursement of a drawdown still matches its total amount & bank
+    /// documents, `None` if the drawdown wasn't disbursed on-chain
+    fn is_disbursement_reconciled(drawdown_id: DrawdownId) -> Option<bool>;
+  }
+}
@@ -1,12 +1,19 @@
 use super::*;
-use frame_support::{pallet_prelude::*, sp_io::hashing::blake2_256};
+use frame_support::{
+  pallet_prelude::*,
+  sp_io::{hashing::blake2_256, MultiRemovalResults},
+};
 use scale_info::prelude::vec;
 use sp_runtime::sp_std::vec::Vec; // vec primitive // vec![] macro
+use sp_runtime::{traits::AccountIdConversion, PerThing, Permill};
 
-use frame_support::traits::{Currency, ExistenceRequirement::KeepAlive};
+use frame_support::traits::{tokens::fungibles, Currency, ExistenceRequirement::KeepAlive};
 
 use crate::types::*;
-use pallet_rbac::types::*;
+use pallet_rbac::{
+  purge::{PurgeProgress, StoragePurge},
+  types::*,
+};
 
 use frame_support::traits::Time;
 
@@ -325,6 +332,7 @@ impl<T: Config> Pallet<T> {
       .collect::<Vec<[u8; 32]>>();
     for expenditure_id in expenditures_by_project.iter().cloned() {
       <ExpendituresInfo<T>>::remove(expenditure_id);
+      <ExpenditureSpend<T>>::remove(expenditure_id);
     }
 
     // Deletes all expenditures from ExpendituresByProject storagemap
@@ -347,6 +355,9 @@ impl<T: Config> Pallet<T> {
       // Deletes all transactions from TransactionsByDrawdown storagemap
       <TransactionsByDrawdown<T>>::remove(project_id, drawdown_id);
 
+      // Delete drawdown disbursement from Disbursements storagemap
+      <Disbursements<T>>::remove(drawdown_id);
+
       // Delete drawdown from DrawdownsInfo storagemap
       <DrawdownsInfo<T>>::remove(drawdown_id);
     }
@@ -382,10 +393,20 @@ impl<T: Config> Pallet<T> {
       // Deletes all revenue transactions from TransactionsByRevenue storagemap
       <TransactionsByRevenue<T>>::remove(project_id, revenue_id);
 
+      // Delete revenue distributions from Distributions storagemap
+      let _ = <Distributions<T>>::clear_prefix(revenue_id, u32::MAX, None);
+
       // Delete revenue from RevenuesInfo storagemap
       <RevenuesInfo<T>>::remove(revenue_id);
     }
 
+    // Delete distribution terms & investor positions
+    <ProjectDistributionTerms<T>>::remove(project_id);
+    let _ = <InvestorPositions<T>>::clear_prefix(project_id, u32::MAX, None);
+
+    // Delete disbursement settings
+    <DisbursementSettings<T>>::remove(project_id);
+
     // Deletes all revenues from RevenuesByProject storagemap
     <RevenuesByProject<T>>::remove(project_id);
 
@@ -503,6 +524,23 @@ impl<T: Config> Pallet<T> {
     // Update project data depending on the role unassigned
     Self::remove_project_role(project_id, user.clone(), role)?;
 
+    // Unassigned investors keep their position but don't receive further distributions
+    if role == ProxyRole::Investor {
+      <InvestorPositions<T>>::mutate(project_id, user.clone(), |position| {
+        if let Some(position) = position.as_mut() {
+          position.active = false;
+        }
+      });
+    }
+
+    // Unassigned builders don't receive further disbursements
+    if role == ProxyRole::Builder
+      && <DisbursementSettings<T>>::get(project_id)
+        .map_or(false, |settings| settings.builder == user)
+    {
+      <DisbursementSettings<T>>::remove(project_id);
+    }
+
     // Remove user from UsersByProject storagemap.
     <UsersByProject<T>>::try_mutate_exists::<_, _, DispatchError, _>(project_id, |users_option| {
       let users = users_option.as_mut().ok_or(Error::<T>::ProjectHasNoUsers)?;
@@ -902,6 +940,7 @@ impl<T: Config> Pallet<T> {
 
     // Delete expenditure data from ExpendituresInfo
     <ExpendituresInfo<T>>::remove(expenditure_id);
+    <ExpenditureSpend<T>>::remove(expenditure_id);
 
     // Delete expenditure_id from ExpendituresByProject
     <ExpendituresByProject<T>>::try_mutate_exists::<_, _, DispatchError, _>(
@@ -1043,6 +1082,16 @@ impl<T: Config> Pallet<T> {
       )?;
     }
 
+    // Flag the expenditures that would go over budget if the drawdown is approved
+    for (expenditure_id, excess) in Self::get_drawdown_over_budget(project_id, drawdown_id)? {
+      Self::deposit_event(Event::ExpenditureOverBudget(
+        project_id,
+        drawdown_id,
+        expenditure_id,
+        excess,
+      ));
+    }
+
     // Update drawdown status
     <DrawdownsInfo<T>>::try_mutate::<_, _, DispatchError, _>(drawdown_id, |drawdown_data| {
       let drawdown_data = drawdown_data.as_mut().ok_or(Error::<T>::DrawdownNotFound)?;
@@ -1109,6 +1158,15 @@ impl<T: Config> Pallet<T> {
           Ok(())
         },
       )?;
+
+      // Add the transaction to the approved spend of its expenditure
+      let transaction_data =
+        TransactionsInfo::<T>::get(transaction_id).ok_or(Error::<T>::TransactionNotFound)?;
+      Self::do_update_expenditure_spend(
+        transaction_data.expenditure_id,
+        0,
+        transaction_data.amount,
+      );
     }
 
     // Update drawdown status to approved
@@ -1470,6 +1528,17 @@ impl<T: Config> Pallet<T> {
       },
     )?;
 
+    // Update the approved spend of the expenditure if an approved transaction was edited
+    if let Some(mod_amount) = amount {
+      if Self::is_spent(transaction_data.status) {
+        Self::do_update_expenditure_spend(
+          transaction_data.expenditure_id,
+          transaction_data.amount,
+          mod_amount,
+        );
+      }
+    }
+
     // Event
     Self::deposit_event(Event::TransactionEdited(
       transaction_data.project_id,
@@ -1507,6 +1576,15 @@ impl<T: Config> Pallet<T> {
     // Remove transaction from TransactionsInfo
     <TransactionsInfo<T>>::remove(transaction_id);
 
+    // Remove an approved transaction from the approved spend of its expenditure
+    if Self::is_spent(transaction_data.status) {
+      Self::do_update_expenditure_spend(
+        transaction_data.expenditure_id,
+        transaction_data.amount,
+        0,
+      );
+    }
+
     // Event
     Self::deposit_event(Event::TransactionDeleted(
       transaction_data.project_id,
@@ -2189,6 +2267,9 @@ impl<T: Config> Pallet<T> {
       RevenueStatus::Approved,
     )?;
 
+    // Distribute the revenue to the investors
+    Self::do_distribute_revenue(project_id, revenue_id, revenue_data.total_amount, timestamp)?;
+
     // Generate the next revenue
     Self::do_create_revenue(project_id, revenue_data.revenue_number + 1)?;
 
@@ -2360,6 +2441,14 @@ impl<T: Config> Pallet<T> {
       Error::<T>::DrawdowMustBeInApprovedStatus
     );
 
+    // Disburse the drawdown on-chain if the project has it enabled
+    Self::do_disburse_drawdown(
+      project_id,
+      drawdown_id,
+      drawdown_data.total_amount,
+      confirming_documents.clone(),
+    )?;
+
     // Mutate drawdown data: Upload bank documents & update drawdown status to Confirmed
     <DrawdownsInfo<T>>::try_mutate::<_, _, DispatchError, _>(drawdown_id, |drawdown_data| {
       let drawdown_data = drawdown_data.as_mut().ok_or(Error::<T>::DrawdownNotFound)?;
@@ -2416,7 +2505,8 @@ impl<T: Config> Pallet<T> {
       Error::<T>::DrawdownHasNoBankConfirmingDocuments
     );
 
-    // Mutate drawdown data: Update bank documents
+    // Mutate drawdown data: Update bank documents. An on-chain disbursement keeps the documents
+    // of its transfer, see `is_disbursement_reconciled`
     <DrawdownsInfo<T>>::try_mutate::<_, _, DispatchError, _>(drawdown_id, |drawdown_data| {
       let drawdown_data = drawdown_data.as_mut().ok_or(Error::<T>::DrawdownNotFound)?;
       drawdown_data.bank_documents = Some(confirming_documents);
@@ -2447,6 +2537,9 @@ impl<T: Config> Pallet<T> {
       Error::<T>::DrawdownHasNoBankConfirmingDocuments
     );
 
+    // Ensure the drawdown wasn't disbursed on-chain, the transfer can't be rolled back
+    ensure!(!<Disbursements<T>>::contains_key(drawdown_id), Error::<T>::DrawdownAlreadyDisbursed);
+
     // Rollback drawdown status to Approved & remove bank confirming documents
     <DrawdownsInfo<T>>::try_mutate::<_, _, DispatchError, _>(drawdown_id, |drawdown_data| {
       let drawdown_data = drawdown_data.as_mut().ok_or(Error::<T>::DrawdownNotFound)?;
@@ -2484,6 +2577,273 @@ impl<T: Config> Pallet<T> {
     Ok(())
   }
 
+  // D I S B U R S E M E N T S
+  // ================================================================================================
+  pub fn do_set_disbursement_settings(
+    admin: T::AccountId,
+    project_id: ProjectId,
+    settings: Option<DisbursementSettingsData<T>>,
+  ) -> DispatchResult {
+    // Ensure admin permissions
+    Self::is_authorized(admin, &project_id, ProxyPermission::SetDisbursementSettings)?;
+
+    // Ensure project exists & is not completed
+    Self::is_project_completed(project_id)?;
+
+    match settings {
+      Some(settings) => {
+        // Ensure the builder is assigned to the project
+        let project_data = ProjectsInfo::<T>::get(project_id).ok_or(Error::<T>::ProjectNotFound)?;
+        ensure!(
+          project_data.builder.unwrap_or_default().contains(&settings.builder),
+          Error::<T>::BuilderNotAssignedToProject
+        );
+
+        <DisbursementSettings<T>>::insert(project_id, settings);
+      },
+      None => <DisbursementSettings<T>>::remove(project_id),
+    }
+
+    // Event
+    Self::deposit_event(Event::DisbursementSettingsUpdated(project_id));
+    Ok(())
+  }
+
+  fn do_disburse_drawdown(
+    project_id: ProjectId,
+    drawdown_id: DrawdownId,
+    amount: TotalAmount,
+    bank_documents: Documents<T>,
+  ) -> DispatchResult {
+    // Projects without disbursement settings are paid off-chain
+    let settings = match <DisbursementSettings<T>>::get(project_id) {
+      Some(settings) => settings,
+      None => return Ok(()),
+    };
+
+    // Get timestamp
+    let timestamp = Self::get_timestamp_in_milliseconds().ok_or(Error::<T>::TimestampError)?;
+
+    // Transfer the drawdown total amount from the project account to the builder
+    let asset_amount: AssetBalanceOf<T> =
+      amount.try_into().map_err(|_| Error::<T>::DisbursementAmountOverflow)?;
+    <T::Assets as fungibles::Transfer<T::AccountId>>::transfer(
+      settings.asset_id,
+      &Self::project_account_id(project_id),
+      &settings.builder,
+      asset_amount,
+      false,
+    )?;
+
+    <Disbursements<T>>::insert(
+      drawdown_id,
+      DisbursementData {
+        project_id,
+        asset_id: settings.asset_id,
+        builder: settings.builder.clone(),
+        amount,
+        bank_documents,
+        disbursed_date: timestamp,
+      },
+    );
+
+    // Event
+    Self::deposit_event(Event::DrawdownDisbursed(
+      project_id,
+      drawdown_id,
+      settings.builder,
+      amount,
+    ));
+    Ok(())
+  }
+
+  /// Get the account that holds the stablecoins disbursed to the builder of a project & the
+  /// revenues distributed to its investors
+  pub fn project_account_id(project_id: ProjectId) -> T::AccountId {
+    T::PalletId::get().into_sub_account_truncating(project_id)
+  }
+
+  /// Check whether the on-chain disbursement of a drawdown still matches its total amount & bank
+  /// documents. A recovery on a confirmed drawdown can change its total amount after the
+  /// transfer was made
+  pub fn is_disbursement_reconciled(drawdown_id: DrawdownId) -> Option<bool> {
+    let disbursement = <Disbursements<T>>::get(drawdown_id)?;
+    let drawdown_data = <DrawdownsInfo<T>>::get(drawdown_id)?;
+    Some(
+      disbursement.amount == drawdown_data.total_amount
+        && drawdown_data.bank_documents.as_ref() == Some(&disbursement.bank_documents),
+    )
+  }
+
+  // D I S T R I B U T I O N S
+  // ================================================================================================
+  pub fn do_set_distribution_terms(
+    admin: T::AccountId,
+    project_id: ProjectId,
+    terms: DistributionTerms,
+    investor_shares: InvestorShares<T>,
+  ) -> DispatchResult {
+    // Ensure admin permissions
+    Self::is_authorized(admin, &project_id, ProxyPermission::SetDistributionTerms)?;
+
+    // Ensure project exists & is not completed
+    Self::is_project_completed(project_id)?;
+
+    // Ensure every investor is assigned to the project & appears only once
+    let project_data = ProjectsInfo::<T>::get(project_id).ok_or(Error::<T>::ProjectNotFound)?;
+    let project_investors = project_data.investor.unwrap_or_default();
+    for (i, (investor, _, _)) in investor_shares.iter().enumerate() {
+      ensure!(project_investors.contains(investor), Error::<T>::InvestorNotAssignedToProject);
+      ensure!(
+        !investor_shares.iter().skip(i + 1).any(|(other, _, _)| other == investor),
+        Error::<T>::DuplicatedInvestorShare
+      );
+    }
+
+    // Ensure the ownership of the investors doesn't exceed 100%
+    let total_ownership = investor_shares
+      .iter()
+      .fold(0u64, |total, (_, ownership, _)| total.saturating_add(ownership.deconstruct().into()));
+    ensure!(
+      total_ownership <= Permill::one().deconstruct().into(),
+      Error::<T>::InvestorSharesExceedTotal
+    );
+
+    // Investors left out of the shares keep what was already distributed to them
+    let previous_positions = <InvestorPositions<T>>::iter_prefix(project_id).collect::<Vec<_>>();
+    for (investor, position) in previous_positions {
+      if position.active
+        && !investor_shares.iter().any(|(shareholder, _, _)| shareholder == &investor)
+      {
+        <InvestorPositions<T>>::insert(
+          project_id,
+          investor,
+          InvestorPosition { active: false, ..position },
+        );
+      }
+    }
+
+    // Update the positions of the investors, keeping what was already distributed
+    for (investor, ownership, capital) in investor_shares.iter().cloned() {
+      let previous_position =
+        <InvestorPositions<T>>::get(project_id, &investor).unwrap_or_default();
+      <InvestorPositions<T>>::insert(
+        project_id,
+        investor,
+        InvestorPosition { ownership, capital, active: true, ..previous_position },
+      );
+    }
+
+    <ProjectDistributionTerms<T>>::insert(project_id, terms);
+
+    // Event
+    Self::deposit_event(Event::DistributionTermsSet(project_id));
+    Ok(())
+  }
+
+  /// Distribute an approved revenue to the active investors of the project through the
+  /// waterfall of its distribution terms. Each investor is paid from the project account in the
+  /// disbursement asset, projects without disbursement settings are paid off-chain & only the
+  /// distributions are recorded
+  fn do_distribute_revenue(
+    project_id: ProjectId,
+    revenue_id: RevenueId,
+    amount: RevenueAmount,
+    timestamp: u64,
+  ) -> DispatchResult {
+    // Revenues are only distributed once the distribution terms are set
+    let terms = match <ProjectDistributionTerms<T>>::get(project_id) {
+      Some(terms) => terms,
+      None => return Ok(()),
+    };
+    let mut positions = <InvestorPositions<T>>::iter_prefix(project_id)
+      .filter(|(_, position)| position.active)
+      .collect::<Vec<_>>();
+    if positions.is_empty() {
+      return Ok(());
+    }
+    let mut distributions = vec![DistributionData::default(); positions.len()];
+    let mut remaining = amount;
+
+    // 1. Return of capital, up to the capital each investor contributed
+    let owed = positions
+      .iter()
+      .map(|(_, position)| position.capital.saturating_sub(position.capital_returned))
+      .collect::<Vec<_>>();
+    for (i, paid) in Self::pro_rata(remaining, &owed).into_iter().enumerate() {
+      distributions[i].return_of_capital = paid;
+      positions[i].1.capital_returned = positions[i].1.capital_returned.saturating_add(paid);
+      remaining = remaining.saturating_sub(paid);
+    }
+
+    // 2. Preferred return, up to the preferred return owed to each investor
+    let owed = positions
+      .iter()
+      .map(|(_, position)| {
+        terms
+          .preferred_return
+          .mul_floor(position.capital)
+          .saturating_sub(position.preferred_return_paid)
+      })
+      .collect::<Vec<_>>();
+    for (i, paid) in Self::pro_rata(remaining, &owed).into_iter().enumerate() {
+      distributions[i].preferred_return = paid;
+      positions[i].1.preferred_return_paid =
+        positions[i].1.preferred_return_paid.saturating_add(paid);
+      remaining = remaining.saturating_sub(paid);
+    }
+
+    // 3. Profit split, each investor gets its ownership of the investors' part, what the
+    // investors don't own is kept by the project
+    let investors_profit = terms.investors_profit_split.mul_floor(remaining);
+    for (i, (_, position)) in positions.iter().enumerate() {
+      distributions[i].profit_split = position.ownership.mul_floor(investors_profit);
+    }
+
+    // Pay & record the distribution of each investor
+    let settings = <DisbursementSettings<T>>::get(project_id);
+    for ((investor, position), mut distribution) in positions.into_iter().zip(distributions) {
+      if let Some(settings) = settings.as_ref() {
+        let payout = distribution
+          .return_of_capital
+          .saturating_add(distribution.preferred_return)
+          .saturating_add(distribution.profit_split);
+        if payout > 0 {
+          let asset_amount: AssetBalanceOf<T> =
+            payout.try_into().map_err(|_| Error::<T>::DistributionAmountOverflow)?;
+          <T::Assets as fungibles::Transfer<T::AccountId>>::transfer(
+            settings.asset_id,
+            &Self::project_account_id(project_id),
+            &investor,
+            asset_amount,
+            false,
+          )?;
+        }
+      }
+      distribution.created_date = timestamp;
+      <Distributions<T>>::insert(revenue_id, investor.clone(), distribution);
+      <InvestorPositions<T>>::insert(project_id, investor, position);
+    }
+
+    // Event
+    Self::deposit_event(Event::RevenueDistributed(project_id, revenue_id));
+    Ok(())
+  }
+
+  // Splits `pool` proportionally to the amounts `owed`, no one gets more than they are owed.
+  // Rounding leftovers aren't assigned.
+  fn pro_rata(pool: DistributionAmount, owed: &[DistributionAmount]) -> Vec<DistributionAmount> {
+    let total_owed = owed.iter().fold(0u128, |total, owed| total + *owed as u128);
+    if total_owed == 0 {
+      return vec![0; owed.len()];
+    }
+    let pool = (pool as u128).min(total_owed);
+    owed
+      .iter()
+      .map(|owed| (pool * *owed as u128 / total_owed) as DistributionAmount)
+      .collect()
+  }
+
   // H E L P E R S
   // ================================================================================================
 
@@ -3482,6 +3842,13 @@ impl<T: Config> Pallet<T> {
                 transaction_data.as_mut().ok_or(Error::<T>::TransactionNotFound)?;
               transaction_data.status =
                 Self::get_transaction_status_for_a_given_drawdown(drawdown_id)?;
+              if Self::is_spent(transaction_data.status) {
+                Self::do_update_expenditure_spend(
+                  transaction_data.expenditure_id,
+                  0,
+                  transaction_data.amount,
+                );
+              }
               Ok(())
             },
           )?;
@@ -3611,5 +3978,129 @@ impl<T: Config> Pallet<T> {
     }
   }
 
+  // B U D G E T   S P E N D
+  // =================================================================================================
+  // Approved & confirmed transactions count towards the spend of their expenditure
+  fn is_spent(status: TransactionStatus) -> bool {
+    status == TransactionStatus::Approved || status == TransactionStatus::Confirmed
+  }
+
+  fn do_update_expenditure_spend(
+    expenditure_id: ExpenditureId,
+    removed_amount: ExpenditureAmount,
+    added_amount: ExpenditureAmount,
+  ) {
+    <ExpenditureSpend<T>>::mutate(expenditure_id, |spend| {
+      *spend = spend.saturating_sub(removed_amount).saturating_add(added_amount);
+    });
+  }
+
+  /// Expenditures whose budget would be exceeded if the drawdown is approved, along with the
+  /// amount over budget.
+  pub fn get_drawdown_over_budget(
+    project_id: ProjectId,
+    drawdown_id: DrawdownId,
+  ) -> Result<Vec<(ExpenditureId, ExpenditureAmount)>, DispatchError> {
+    // Amount requested for each expenditure by the transactions pending approval
+    let mut requested: Vec<(ExpenditureId, ExpenditureAmount)> = Vec::new();
+    for transaction_id in <TransactionsByDrawdown<T>>::get(project_id, drawdown_id).iter() {
+      let transaction_data =
+        TransactionsInfo::<T>::get(transaction_id).ok_or(Error::<T>::TransactionNotFound)?;
+      if Self::is_spent(transaction_data.status) {
+        continue;
+      }
+      match requested.iter_mut().find(|(id, _)| *id == transaction_data.expenditure_id) {
+        Some((_, amount)) => *amount = amount.saturating_add(transaction_data.amount),
+        None => requested.push((transaction_data.expenditure_id, transaction_data.amount)),
+      }
+    }
+
+    let mut over_budget = Vec::new();
+    for (expenditure_id, amount) in requested {
+      let expenditure_data =
+        ExpendituresInfo::<T>::get(expenditure_id).ok_or(Error::<T>::ExpenditureNotFound)?;
+      let spend = <ExpenditureSpend<T>>::get(expenditure_id).saturating_add(amount);
+      if spend > expenditure_data.expenditure_amount {
+        over_budget.push((expenditure_id, spend - expenditure_data.expenditure_amount));
+      }
+    }
+    Ok(over_budget)
+  }
+
+  /// Budget left for each expenditure of the project, zero for expenditures over budget.
+  pub fn get_remaining_budget_by_expenditure(
+    project_id: ProjectId,
+  ) -> Vec<(ExpenditureId, ExpenditureAmount)> {
+    <ExpendituresByProject<T>>::get(project_id)
+      .iter()
+      .filter_map(|expenditure_id| {
+        let expenditure_data = <ExpendituresInfo<T>>::get(expenditure_id)?;
+        let remaining = expenditure_data
+          .expenditure_amount
+          .saturating_sub(<ExpenditureSpend<T>>::get(expenditure_id));
+        Some((*expenditure_id, remaining))
+      })
+      .collect()
+  }
+
+  /// Budget left for each expenditure type of the project, the sum of the budget left of its
+  /// expenditures.
+  pub fn get_remaining_budget_by_expenditure_type(
+    project_id: ProjectId,
+  ) -> Vec<(ExpenditureType, ExpenditureAmount)> {
+    let mut remaining_by_type: Vec<(ExpenditureType, ExpenditureAmount)> = Vec::new();
+    for (expenditure_id, remaining) in Self::get_remaining_budget_by_expenditure(project_id) {
+      let expenditure_type = match <ExpendituresInfo<T>>::get(expenditure_id) {
+        Some(expenditure_data) => expenditure_data.expenditure_type,
+        None => continue,
+      };
+      match remaining_by_type.iter_mut().find(|(t, _)| *t == expenditure_type) {
+        Some((_, amount)) => *amount = amount.saturating_add(remaining),
+        None => remaining_by_type.push((expenditure_type, remaining)),
+      }
+    }
+    remaining_by_type
+  }
+
   // Do not code beyond this line
 }
+
+impl<T: Config> StoragePurge for Pallet<T> {
+  const STAGES: u32 = 22;
+
+  fn clear_stage(stage: u32, limit: u32, cursor: Option<&[u8]>) -> MultiRemovalResults {
+    match stage {
+      0 => <UsersInfo<T>>::clear(limit, cursor),
+      1 => <ProjectsInfo<T>>::clear(limit, cursor),
+      2 => <UsersByProject<T>>::clear(limit, cursor),
+      3 => <ProjectsByUser<T>>::clear(limit, cursor),
+      4 => <ExpendituresInfo<T>>::clear(limit, cursor),
+      5 => <ExpendituresByProject<T>>::clear(limit, cursor),
+      6 => <DrawdownsInfo<T>>::clear(limit, cursor),
+      7 => <DrawdownsByProject<T>>::clear(limit, cursor),
+      8 => <TransactionsInfo<T>>::clear(limit, cursor),
+      9 => <TransactionsByDrawdown<T>>::clear(limit, cursor),
+      10 => <JobEligiblesInfo<T>>::clear(limit, cursor),
+      11 => <JobEligiblesByProject<T>>::clear(limit, cursor),
+      12 => <RevenuesInfo<T>>::clear(limit, cursor),
+      13 => <RevenuesByProject<T>>::clear(limit, cursor),
+      14 => <RevenueTransactionsInfo<T>>::clear(limit, cursor),
+      15 => <TransactionsByRevenue<T>>::clear(limit, cursor),
+      16 => <ExpenditureSpend<T>>::clear(limit, cursor),
+      17 => <ProjectDistributionTerms<T>>::clear(limit, cursor),
+      18 => <InvestorPositions<T>>::clear(limit, cursor),
+      19 => <Distributions<T>>::clear(limit, cursor),
+      20 => <DisbursementSettings<T>>::clear(limit, cursor),
+      21 => <Disbursements<T>>::clear(limit, cursor),
+      _ => MultiRemovalResults { maybe_cursor: None, backend: 0, unique: 0, loops: 0 },
+    }
+  }
+
+  fn purge_progress() -> Option<PurgeProgress> {
+    <StoragePurgeProgress<T>>::get()
+  }
+
+  fn set_purge_progress(progress: Option<PurgeProgress>) {
+    <StoragePurgeProgress<T>>::set(progress);
+  }
+}
@@ -10,23 +10,30 @@ mod tests;
 
 mod functions;
 pub mod migration;
-mod types;
+pub mod types;
 #[frame_support::pallet]
 pub mod pallet {
   use frame_support::{
     pallet_prelude::{ValueQuery, *},
-    traits::{Currency, Time},
-    BoundedVec,
+    traits::{tokens::fungibles, Currency, Time},
+    BoundedVec, PalletId,
   };
   use frame_system::pallet_prelude::*;
   use scale_info::prelude::vec;
   use sp_runtime::{sp_std::vec::Vec, traits::Scale};
 
-  const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+  const STORAGE_VERSION: StorageVersion = StorageVersion::new(2);
   use crate::types::*;
-  use pallet_rbac::types::RoleBasedAccessControl;
+  use pallet_rbac::{
+    purge::{self, PurgeProgress, StoragePurge},
+    types::RoleBasedAccessControl,
+  };
   pub type BalanceOf<T> =
     <<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+  pub type AssetIdOf<T> =
+    <<T as Config>::Assets as fungibles::Inspect<<T as frame_system::Config>::AccountId>>::AssetId;
+  pub type AssetBalanceOf<T> =
+    <<T as Config>::Assets as fungibles::Inspect<<T as frame_system::Config>::AccountId>>::Balance;
 
   #[pallet::config]
   pub trait Config: frame_system::Config {
@@ -48,6 +55,13 @@ pub mod pallet {
 
     type Currency: Currency<Self::AccountId>;
 
+    /// Stablecoins the drawdowns are disbursed in, when a project enables on-chain disbursements
+    type Assets: fungibles::Transfer<Self::AccountId>;
+
+    /// Used to derive the account that holds the stablecoins of each project
+    #[pallet::constant]
+    type PalletId: Get<PalletId>;
+
     #[pallet::constant]
     type MaxDocuments: Get<u32>;
 
@@ -187,6 +201,16 @@ pub mod pallet {
     ValueQuery,
   >;
 
+  #[pallet::storage]
+  #[pallet::getter(fn expenditure_spend)]
+  pub(super) type ExpenditureSpend<T: Config> = StorageMap<
+    _,
+    Identity,
+    ExpenditureId,     // Key expenditure_id
+    ExpenditureAmount, // Value amount of the approved & confirmed transactions
+    ValueQuery,
+  >;
+
   #[pallet::storage]
   #[pallet::getter(fn drawdowns_info)]
   pub(super) type DrawdownsInfo<T: Config> = StorageMap<
@@ -291,6 +315,68 @@ pub mod pallet {
     ValueQuery,
   >;
 
+  #[pallet::storage]
+  #[pallet::getter(fn project_distribution_terms)]
+  pub(super) type ProjectDistributionTerms<T: Config> = StorageMap<
+    _,
+    Identity,
+    ProjectId,         // Key project_id
+    DistributionTerms, // Value DistributionTerms
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn investor_positions)]
+  pub(super) type InvestorPositions<T: Config> = StorageDoubleMap<
+    _,
+    Identity,
+    ProjectId, //K1: project id
+    Blake2_128Concat,
+    T::AccountId,     //K2: investor
+    InvestorPosition, // Value ownership, capital & what has been distributed so far
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn distributions)]
+  pub(super) type Distributions<T: Config> = StorageDoubleMap<
+    _,
+    Identity,
+    RevenueId, //K1: revenue id
+    Blake2_128Concat,
+    T::AccountId,     //K2: investor
+    DistributionData, // Value amount distributed at each stage of the waterfall
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn disbursement_settings)]
+  pub(super) type DisbursementSettings<T: Config> = StorageMap<
+    _,
+    Identity,
+    ProjectId,                   // Key project_id
+    DisbursementSettingsData<T>, // Value DisbursementSettingsData<T>
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn disbursements)]
+  pub(super) type Disbursements<T: Config> = StorageMap<
+    _,
+    Identity,
+    DrawdownId,          // Key drawdown_id
+    DisbursementData<T>, // Value transfer made when the bank documents were uploaded
+    OptionQuery,
+  >;
+
+  #[pallet::storage]
+  #[pallet::getter(fn storage_purge_progress)]
+  pub(super) type StoragePurgeProgress<T: Config> = StorageValue<
+    _,
+    PurgeProgress, // Progress of the storage removal started by kill_storage
+    OptionQuery,
+  >;
+
   // E V E N T S
   // ------------------------------------------------------------------------------------------------------------
 
@@ -389,6 +475,18 @@ pub mod pallet {
     RevenueErrorRecoveryExecuted(ProjectId, RevenueId),
     /// Error recovery for drawdowns was executed successfully
     DrawdownErrorRecoveryExecuted(ProjectId, DrawdownId),
+    /// The submitted drawdown exceeds the budget of an expenditure by the given amount
+    ExpenditureOverBudget(ProjectId, DrawdownId, ExpenditureId, ExpenditureAmount),
+    /// The distribution terms & investor shares of a project were set successfully
+    DistributionTermsSet(ProjectId),
+    /// An approved revenue was distributed to the investors of the project
+    RevenueDistributed(ProjectId, RevenueId),
+    /// The on-chain disbursement settings of a project were set or removed successfully
+    DisbursementSettingsUpdated(ProjectId),
+    /// The total amount of a confirmed drawdown was transferred on-chain to the builder
+    DrawdownDisbursed(ProjectId, DrawdownId, T::AccountId, TotalAmount),
+    /// The storage removal started by kill_storage finished
+    StoragePurged,
   }
 
   // E R R O R S
@@ -694,6 +792,35 @@ pub mod pallet {
     AdminHasNoFreeBalance,
     /// Administrator account has insuficiente balance to register a new user
     InsufficientFundsToTransfer,
+    /// The account is not an investor of the project
+    InvestorNotAssignedToProject,
+    /// The investor appears more than once in the investor shares
+    DuplicatedInvestorShare,
+    /// The ownership of the investors adds up to more than 100%
+    InvestorSharesExceedTotal,
+    /// The builder is not assigned to the project
+    BuilderNotAssignedToProject,
+    /// The drawdown amount can't be represented in the disbursement asset
+    DisbursementAmountOverflow,
+    /// The drawdown was already disbursed on-chain, its bank documents can't be deleted
+    DrawdownAlreadyDisbursed,
+    /// A storage removal started by kill_storage is still in progress
+    StoragePurgeInProgress,
+    /// The distribution amount can't be represented in the disbursement asset
+    DistributionAmountOverflow,
+  }
+
+  // H O O K S
+  // ------------------------------------------------------------------------------------------------------------
+  #[pallet::hooks]
+  impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+    fn on_idle(_n: T::BlockNumber, remaining_weight: Weight) -> Weight {
+      let (weight, complete) = Self::purge_on_idle(remaining_weight, T::DbWeight::get());
+      if complete {
+        Self::deposit_event(Event::StoragePurged);
+      }
+      weight
+    }
   }
 
   // E X T R I N S I C S
@@ -711,6 +838,7 @@ pub mod pallet {
     #[pallet::call_index(1)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(10))]
     pub fn initial_setup(origin: OriginFor<T>) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       T::RemoveOrigin::ensure_origin(origin.clone())?;
       Self::do_initial_setup()?;
       Ok(())
@@ -736,6 +864,7 @@ pub mod pallet {
       admin: T::AccountId,
       name: FieldName,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       T::RemoveOrigin::ensure_origin(origin.clone())?;
       Self::do_sudo_add_administrator(admin, name)?;
       Ok(())
@@ -759,6 +888,7 @@ pub mod pallet {
     #[pallet::call_index(3)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(10))]
     pub fn sudo_remove_administrator(origin: OriginFor<T>, admin: T::AccountId) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       T::RemoveOrigin::ensure_origin(origin.clone())?;
       Self::do_sudo_remove_administrator(admin)?;
       Ok(())
@@ -801,6 +931,7 @@ pub mod pallet {
     #[pallet::call_index(4)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(10))]
     pub fn users(origin: OriginFor<T>, users: Users<T>) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be an admin
 
       Self::do_execute_users(who, users)
@@ -831,6 +962,7 @@ pub mod pallet {
       email: Option<FieldName>,
       documents: Option<Documents<T>>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_edit_user(who, name, image, email, documents)
@@ -901,6 +1033,7 @@ pub mod pallet {
       users: Option<UsersAssignation<T>>,
       private_group_id: PrivateGroupId,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be an admin
 
       Self::do_create_project(
@@ -956,6 +1089,7 @@ pub mod pallet {
       creation_date: Option<CreationDate>,
       completion_date: Option<CompletionDate>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be an admin
 
       Self::do_edit_project(
@@ -990,6 +1124,7 @@ pub mod pallet {
     #[pallet::call_index(8)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(10))]
     pub fn projects_delete_project(origin: OriginFor<T>, project_id: ProjectId) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be an admin
 
       Self::do_delete_project(who, project_id)
@@ -1036,6 +1171,7 @@ pub mod pallet {
       project_id: ProjectId,
       users: UsersAssignation<T>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be an admin
 
       Self::do_execute_assign_users(who, project_id, users)
@@ -1087,6 +1223,7 @@ pub mod pallet {
       expenditures: Option<Expenditures<T>>,
       job_eligibles: Option<JobEligibles<T>>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be an admin
 
       if let Some(mod_expenditures) = expenditures {
@@ -1151,6 +1288,7 @@ pub mod pallet {
       transactions: Option<Transactions<T>>,
       submit: bool,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be an admin
 
       match submit {
@@ -1233,6 +1371,7 @@ pub mod pallet {
       bulkupload: Option<bool>,
       transactions: Option<Transactions<T>>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be an admin
 
       // Match bulkupload parameter
@@ -1325,6 +1464,7 @@ pub mod pallet {
       transactions_feedback: Option<TransactionsFeedback<T>>,
       drawdown_feedback: Option<FieldDescription>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be an admin
 
       Self::do_reject_drawdown(
@@ -1366,6 +1506,7 @@ pub mod pallet {
       total_amount: TotalAmount,
       documents: Documents<T>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be a builder
 
       Self::do_up_bulk_upload(who, project_id, drawdown_id, description, total_amount, documents)
@@ -1395,6 +1536,7 @@ pub mod pallet {
     #[pallet::call_index(15)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(10))]
     pub fn inflation_rate(origin: OriginFor<T>, projects: ProjectsInflation<T>) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_execute_inflation_adjustment(who, projects)
@@ -1452,6 +1594,7 @@ pub mod pallet {
       revenue_transactions: Option<RevenueTransactions<T>>,
       submit: bool,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       match submit {
@@ -1504,13 +1647,24 @@ pub mod pallet {
     ///   rejected revenue
     /// new revenue version uploaded by the builder.
     /// - The revenue status will be updated to Approved.
+    /// - The revenue is distributed to the investors of the project, see
+    ///   `set_distribution_terms`. Projects with disbursement settings pay each investor from the
+    ///   project account.
     #[pallet::call_index(17)]
-    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(10))]
+    #[pallet::weight(
+      Weight::from_ref_time(10_000)
+        + T::DbWeight::get().writes(10)
+        // The distribution & the payout of each investor
+        + T::DbWeight::get()
+          .reads_writes(4, 4)
+          .saturating_mul(T::MaxInvestorsPerProject::get().into())
+    )]
     pub fn approve_revenue(
       origin: OriginFor<T>,
       project_id: ProjectId,
       revenue_id: RevenueId,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_approve_revenue(who, project_id, revenue_id)
@@ -1543,6 +1697,7 @@ pub mod pallet {
       revenue_id: RevenueId,
       revenue_transactions_feedback: TransactionsFeedback<T>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_reject_revenue(who, project_id, revenue_id, revenue_transactions_feedback)
@@ -1581,6 +1736,7 @@ pub mod pallet {
       confirming_documents: Option<Documents<T>>,
       action: CUDAction,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_bank_confirming_documents(who, project_id, drawdown_id, confirming_documents, action)
@@ -1605,6 +1761,7 @@ pub mod pallet {
       project_id: ProjectId,
       drawdown_id: DrawdownId,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_reset_drawdown(who, project_id, drawdown_id)
@@ -1637,6 +1794,7 @@ pub mod pallet {
       drawdown_id: DrawdownId,
       transactions: Transactions<T>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be an admin
 
       Self::do_recovery_drawdown(who, project_id, drawdown_id, transactions)
@@ -1668,6 +1826,7 @@ pub mod pallet {
       revenue_id: RevenueId,
       transactions: Transactions<T>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?; // origin need to be an admin
 
       Self::do_recovery_revenue(who, project_id, revenue_id, transactions)
@@ -1683,35 +1842,32 @@ pub mod pallet {
     ///
     /// ### Considerations:
     /// - This function is only available to the `admin` with sudo access.
+    /// - The storage maps that can't be cleared in this call are cleared in the next blocks,
+    ///   `StoragePurged` is emitted once they are empty.
     #[pallet::call_index(23)]
-    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(10))]
+    #[pallet::weight(
+      Weight::from_ref_time(10_000)
+        + T::DbWeight::get().reads_writes(2, 2 + u64::from(purge::KEYS_PER_CALL))
+    )]
     pub fn kill_storage(origin: OriginFor<T>) -> DispatchResult {
       T::RemoveOrigin::ensure_origin(origin.clone())?;
+      // Starting over would drop the progress of the purge in progress
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let _ = <GlobalScope<T>>::kill();
-      let _ = <UsersInfo<T>>::clear(1000, None);
-      let _ = <ProjectsInfo<T>>::clear(1000, None);
-      let _ = <UsersByProject<T>>::clear(1000, None);
-      let _ = <ProjectsByUser<T>>::clear(1000, None);
-      let _ = <ExpendituresInfo<T>>::clear(1000, None);
-      let _ = <ExpendituresByProject<T>>::clear(1000, None);
-      let _ = <DrawdownsInfo<T>>::clear(1000, None);
-      let _ = <DrawdownsByProject<T>>::clear(1000, None);
-      let _ = <TransactionsInfo<T>>::clear(1000, None);
-      let _ = <TransactionsByDrawdown<T>>::clear(1000, None);
-      let _ = <JobEligiblesInfo<T>>::clear(1000, None);
-      let _ = <JobEligiblesByProject<T>>::clear(1000, None);
-      let _ = <RevenuesInfo<T>>::clear(1000, None);
-      let _ = <RevenuesByProject<T>>::clear(1000, None);
-      let _ = <RevenueTransactionsInfo<T>>::clear(1000, None);
-      let _ = <TransactionsByRevenue<T>>::clear(1000, None);
 
       T::Rbac::remove_pallet_storage(Self::pallet_id())?;
+
+      // The maps are cleared in multiple blocks, see `on_idle`
+      if Self::start_purge(purge::KEYS_PER_CALL).complete {
+        Self::deposit_event(Event::StoragePurged);
+      }
       Ok(())
     }
 
     #[pallet::call_index(24)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(10))]
     pub fn set_new_admin_permissions(origin: OriginFor<T>) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       T::RemoveOrigin::ensure_origin(origin.clone())?;
       // New permissions for fund admin administrator role
       let admin_id: [u8; 32] = ProxyRole::Administrator.id();
@@ -1723,11 +1879,80 @@ pub mod pallet {
         ProxyPermission::RecoveryTransaction.to_vec(),
         ProxyPermission::RecoveryRevenueTransaction.to_vec(),
         ProxyPermission::BulkUploadTransaction.to_vec(),
+        ProxyPermission::SetDistributionTerms.to_vec(),
+        ProxyPermission::SetDisbursementSettings.to_vec(),
       ];
 
       T::Rbac::create_and_set_permissions(pallet_id.clone(), admin_id, new_admin_permissions)?;
 
       Ok(())
     }
+
+    /// Set how the approved revenues of a project are distributed to its investors.
+    ///
+    /// ### Parameters:
+    /// - `origin`: The administrator account who is setting the distribution terms
+    /// - `project_id`: The selected project id
+    /// - `terms`: The preferred return & the investors' profit split of the waterfall
+    /// - `investor_shares`: The ownership & the capital contributed by each investor
+    ///
+    /// ### Considerations:
+    /// - This function is only callable by an administrator role account
+    /// - Every investor must be assigned to the project & the ownership of the investors can't
+    ///   add up to more than 100%
+    /// - What was already distributed to an investor is kept, investors left out of
+    ///   `investor_shares` keep their position but won't receive further distributions
+    /// - Each approved revenue is distributed in three stages: return of capital, preferred return
+    ///   and profit split, see `DistributionTerms`
+    #[pallet::call_index(25)]
+    #[pallet::weight(
+      Weight::from_ref_time(10_000)
+        + T::DbWeight::get().reads_writes(4, 1)
+        + T::DbWeight::get().writes(investor_shares.len() as u64)
+        // The previous positions of the project
+        + T::DbWeight::get()
+          .reads_writes(1, 1)
+          .saturating_mul(T::MaxInvestorsPerProject::get().into())
+    )]
+    pub fn set_distribution_terms(
+      origin: OriginFor<T>,
+      project_id: ProjectId,
+      terms: DistributionTerms,
+      investor_shares: InvestorShares<T>,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?; // origin need to be an admin
+
+      Self::do_set_distribution_terms(who, project_id, terms, investor_shares)
+    }
+
+    /// Enable or disable the on-chain disbursement of the drawdowns of a project.
+    ///
+    /// ### Parameters:
+    /// - `origin`: The administrator account who is setting the disbursement settings
+    /// - `project_id`: The selected project id
+    /// - `settings`: The stablecoin & the builder the drawdowns are disbursed to. `None` disables
+    ///   on-chain disbursements for the project
+    ///
+    /// ### Considerations:
+    /// - This function is only callable by an administrator role account
+    /// - The builder must be assigned to the project
+    /// - The stablecoins are taken from the project account, see `project_account_id`. It has to
+    ///   be funded before the bank documents of a drawdown are uploaded
+    /// - Once enabled, uploading the bank confirming documents of an EB5 drawdown transfers its
+    ///   total amount to the builder. The transfer is recorded alongside the bank documents
+    ///   and those documents can't be deleted afterwards
+    #[pallet::call_index(26)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(10))]
+    pub fn set_disbursement_settings(
+      origin: OriginFor<T>,
+      project_id: ProjectId,
+      settings: Option<DisbursementSettingsData<T>>,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?; // origin need to be an admin
+
+      Self::do_set_disbursement_settings(who, project_id, settings)
+    }
   }
 }
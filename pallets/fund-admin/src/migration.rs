@@ -101,7 +101,7 @@ pub mod v1 {
         onchain_version
       );
 
-      if onchain_version == 0 && current_version == 1 {
+      if onchain_version == 0 && current_version >= 1 {
         // migrate to v1
         // Very inefficient, mostly here for illustration purposes.
         let count_drawdowns = v0::DrawdownsInfo::<T>::iter().count();
@@ -125,7 +125,7 @@ pub mod v1 {
         );
 
         // Update storage version
-        current_version.put::<Pallet<T>>();
+        StorageVersion::new(1).put::<Pallet<T>>();
 
         log::info!(
           target: LOG_TARGET,
@@ -162,7 +162,7 @@ pub mod v1 {
       );
 
       ensure!(Pallet::<T>::on_chain_storage_version() == 0, "must upgrade linearly");
-      ensure!(Pallet::<T>::current_storage_version() == 1, "migration from version 0 to 1");
+      ensure!(Pallet::<T>::current_storage_version() >= 1, "migration from version 0 to 1");
 
       let prev_count_drawdowns = v0::DrawdownsInfo::<T>::iter().count();
       let keys_drawdowns = v0::DrawdownsInfo::<T>::iter_keys().count() as u32;
@@ -213,14 +213,9 @@ pub mod v1 {
         "the records count before and after the migration should be the same"
       );
 
-      let current_version = Pallet::<T>::current_storage_version();
       let onchain_version = Pallet::<T>::on_chain_storage_version();
 
-      ensure!(current_version == 1, "must upgrade to v1");
-      assert_eq!(
-        current_version, onchain_version,
-        "after migration, the current_version and onchain_version should be the same"
-      );
+      ensure!(onchain_version == 1, "must upgrade to v1");
 
       crate::DrawdownsInfo::<T>::iter().for_each(|(_key, value)| {
         assert!(
@@ -241,3 +236,89 @@ pub mod v1 {
     }
   }
 }
+
+pub mod v2 {
+  use super::*;
+
+  /// Fills `ExpenditureSpend` with the approved & confirmed transactions of each expenditure
+  pub struct MigrateToV2<T>(sp_std::marker::PhantomData<T>);
+  impl<T: Config> OnRuntimeUpgrade for MigrateToV2<T> {
+    fn on_runtime_upgrade() -> Weight {
+      let onchain_version = Pallet::<T>::on_chain_storage_version();
+      let current_version = Pallet::<T>::current_storage_version();
+
+      log::info!(
+        target: LOG_TARGET,
+        "Running migration with current storage version: {:?} / onchain version: {:?}",
+        current_version,
+        onchain_version
+      );
+
+      if onchain_version == 1 && current_version == 2 {
+        let mut reads = 0u64;
+        let mut spent_transactions = 0u64;
+
+        TransactionsInfo::<T>::iter_values().for_each(|transaction_data| {
+          reads.saturating_inc();
+          if transaction_data.status == TransactionStatus::Approved
+            || transaction_data.status == TransactionStatus::Confirmed
+          {
+            spent_transactions.saturating_inc();
+            ExpenditureSpend::<T>::mutate(transaction_data.expenditure_id, |spend| {
+              *spend = spend.saturating_add(transaction_data.amount)
+            });
+          }
+        });
+
+        // Update storage version
+        current_version.put::<Pallet<T>>();
+
+        log::info!(
+          target: LOG_TARGET,
+          "Added {} spent transactions from {} transactions, storage to version {:?}",
+          spent_transactions,
+          reads,
+          current_version
+        );
+
+        T::DbWeight::get().reads_writes(reads.saturating_mul(2) + 1, spent_transactions + 1)
+      } else {
+        log::info!(
+          target: LOG_TARGET,
+          "Migration did not execute. This probably should be removed"
+        );
+        T::DbWeight::get().reads(1)
+      }
+    }
+
+    #[cfg(feature = "try-runtime")]
+    fn pre_upgrade() -> Result<Vec<u8>, &'static str> {
+      ensure!(Pallet::<T>::on_chain_storage_version() == 1, "must upgrade linearly");
+      ensure!(Pallet::<T>::current_storage_version() == 2, "migration from version 1 to 2");
+
+      let spent_total = TransactionsInfo::<T>::iter_values()
+        .filter(|transaction_data| {
+          transaction_data.status == TransactionStatus::Approved
+            || transaction_data.status == TransactionStatus::Confirmed
+        })
+        .fold(0u64, |total, transaction_data| total.saturating_add(transaction_data.amount));
+
+      Ok(spent_total.encode())
+    }
+
+    #[cfg(feature = "try-runtime")]
+    fn post_upgrade(spent_total: Vec<u8>) -> Result<(), &'static str> {
+      let prev_spent_total =
+        <u64>::decode(&mut &spent_total[..]).map_err(|_| "Unable to decode spent_total")?;
+      let post_spent_total =
+        ExpenditureSpend::<T>::iter_values().fold(0u64, |total, spend| total.saturating_add(spend));
+
+      assert_eq!(
+        prev_spent_total, post_spent_total,
+        "the spend of the expenditures should add up to the approved transactions"
+      );
+      ensure!(Pallet::<T>::on_chain_storage_version() == 2, "must upgrade to v2");
+      Ok(())
+    }
+  }
+}
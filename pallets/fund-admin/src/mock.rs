@@ -1,5 +1,9 @@
 use crate as pallet_fund_admin;
-use frame_support::parameter_types;
+use frame_support::{
+  parameter_types,
+  traits::{AsEnsureOriginWithArg, ConstU32, ConstU64},
+  PalletId,
+};
 use frame_system as system;
 use sp_core::H256;
 use sp_runtime::{
@@ -23,6 +27,7 @@ frame_support::construct_runtime!(
     Timestamp: pallet_timestamp::{Pallet, Call, Storage, Inherent},
     RBAC: pallet_rbac::{Pallet, Call, Storage, Event<T>},
     Balances: pallet_balances::{Pallet, Call, Storage, Event<T>},
+    Assets: pallet_mapped_assets::{Pallet, Call, Storage, Event<T>},
   }
 );
 
@@ -98,6 +103,7 @@ parameter_types! {
   pub const MinAdminBalance:u64 = 10;
   pub const TransferAmount:u64 = 10;
   pub const InitialAdminBalance:u64 = 1_000_000;
+  pub const FundAdminPalletId: PalletId = PalletId(*b"fundadmn");
 }
 
 impl pallet_fund_admin::Config for Test {
@@ -107,6 +113,8 @@ impl pallet_fund_admin::Config for Test {
   type Moment = u64;
   type Rbac = RBAC;
   type Currency = Balances;
+  type Assets = Assets;
+  type PalletId = FundAdminPalletId;
 
   type MaxDocuments = MaxDocuments;
   type MaxProjectsPerUser = MaxProjectsPerUser;
@@ -159,6 +167,36 @@ impl pallet_rbac::Config for Test {
   type RemoveOrigin = EnsureRoot<Self::AccountId>;
 }
 
+pub struct AssetsCallbackHandle;
+impl pallet_mapped_assets::AssetsCallback<u32, u64> for AssetsCallbackHandle {
+  fn created(_id: &u32, _owner: &u64) {}
+
+  fn destroyed(_id: &u32) {}
+}
+
+impl pallet_mapped_assets::Config for Test {
+  type RuntimeEvent = RuntimeEvent;
+  type Balance = u64;
+  type AssetId = u32;
+  type AssetIdParameter = u32;
+  type Currency = Balances;
+  type CreateOrigin = AsEnsureOriginWithArg<frame_system::EnsureSigned<u64>>;
+  type ForceOrigin = EnsureRoot<u64>;
+  type AssetDeposit = ConstU64<1>;
+  type AssetAccountDeposit = ConstU64<10>;
+  type MetadataDepositBase = ConstU64<1>;
+  type MetadataDepositPerByte = ConstU64<1>;
+  type ApprovalDeposit = ConstU64<1>;
+  type StringLimit = ConstU32<50>;
+  type Freezer = ();
+  type WeightInfo = ();
+  type CallbackHandle = AssetsCallbackHandle;
+  type Extra = ();
+  type RemoveItemsLimit = ConstU32<5>;
+  type MaxReserves = MaxReserves;
+  type ReserveIdentifier = u32;
+}
+
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
   let balance_amount = InitialAdminBalance::get();
@@ -1,13 +1,19 @@
 use crate::{
-  mock::*, types::*, DrawdownsByProject, DrawdownsInfo, Error, ExpendituresByProject,
-  ExpendituresInfo, GlobalScope, JobEligiblesByProject, JobEligiblesInfo, ProjectsByUser,
-  ProjectsInfo, RevenueTransactionsInfo, RevenuesByProject, RevenuesInfo, TransactionsByDrawdown,
-  TransactionsByRevenue, TransactionsInfo, UsersByProject, UsersInfo,
+  mock::*, types::*, Disbursements, Distributions, DrawdownsByProject, DrawdownsInfo, Error, Event,
+  ExpenditureSpend, ExpendituresByProject, ExpendituresInfo, GlobalScope, InvestorPositions,
+  JobEligiblesByProject, JobEligiblesInfo, ProjectsByUser, ProjectsInfo, RevenueTransactionsInfo,
+  RevenuesByProject, RevenuesInfo, TransactionsByDrawdown, TransactionsByRevenue, TransactionsInfo,
+  UsersByProject, UsersInfo,
 };
 use frame_support::{
-  assert_noop, assert_ok, bounded_vec, error::BadOrigin, traits::ConstU32, BoundedVec,
+  assert_noop, assert_ok, bounded_vec,
+  error::BadOrigin,
+  traits::{ConstU32, Hooks},
+  weights::Weight,
+  BoundedVec,
 };
-use sp_runtime::DispatchResult;
+use pallet_rbac::purge::StoragePurge;
+use sp_runtime::{DispatchResult, Permill};
 
 type RbacErr = pallet_rbac::Error<Test>;
 
@@ -4355,6 +4361,100 @@ fn drawdowns_after_a_drawdown_is_approved_the_next_one_is_generated_autoamticall
   });
 }
 
+#[test]
+fn drawdowns_an_approved_drawdown_adds_its_transactions_to_the_expenditure_spend_works() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(make_default_full_project());
+    let project_id = ProjectsInfo::<Test>::iter_keys().next().unwrap();
+
+    let drawdown_id = get_drawdown_id(project_id, DrawdownType::EB5, 1);
+    let expenditure_id = get_budget_expenditure_id(
+      project_id,
+      make_field_name("Expenditure Test 1"),
+      ExpenditureType::HardCost,
+    );
+
+    let transaction_data =
+      make_transaction(Some(expenditure_id), Some(60), CUDAction::Create, None);
+
+    assert_ok!(FundAdmin::submit_drawdown(
+      RuntimeOrigin::signed(2),
+      project_id,
+      drawdown_id,
+      Some(transaction_data),
+      true,
+    ));
+    assert_eq!(ExpenditureSpend::<Test>::get(expenditure_id), 0);
+
+    assert_ok!(FundAdmin::approve_drawdown(
+      RuntimeOrigin::signed(1),
+      project_id,
+      drawdown_id,
+      None,
+      None,
+    ));
+
+    assert_eq!(ExpenditureSpend::<Test>::get(expenditure_id), 60);
+    assert!(
+      FundAdmin::get_remaining_budget_by_expenditure(project_id).contains(&(expenditure_id, 40))
+    );
+    assert!(FundAdmin::get_remaining_budget_by_expenditure_type(project_id)
+      .contains(&(ExpenditureType::HardCost, 40)));
+  });
+}
+
+#[test]
+fn drawdowns_a_drawdown_over_the_remaining_budget_is_flagged_works() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(make_default_full_project());
+    let project_id = ProjectsInfo::<Test>::iter_keys().next().unwrap();
+
+    let drawdown_id = get_drawdown_id(project_id, DrawdownType::EB5, 1);
+    let expenditure_id = get_budget_expenditure_id(
+      project_id,
+      make_field_name("Expenditure Test 1"),
+      ExpenditureType::HardCost,
+    );
+
+    let transaction_data =
+      make_transaction(Some(expenditure_id), Some(60), CUDAction::Create, None);
+
+    assert_ok!(FundAdmin::submit_drawdown(
+      RuntimeOrigin::signed(2),
+      project_id,
+      drawdown_id,
+      Some(transaction_data),
+      true,
+    ));
+    assert!(FundAdmin::get_drawdown_over_budget(project_id, drawdown_id).unwrap().is_empty());
+
+    assert_ok!(FundAdmin::approve_drawdown(
+      RuntimeOrigin::signed(1),
+      project_id,
+      drawdown_id,
+      None,
+      None,
+    ));
+
+    let next_drawdown_id = get_drawdown_id(project_id, DrawdownType::EB5, 2);
+    let transaction_data =
+      make_transaction(Some(expenditure_id), Some(70), CUDAction::Create, None);
+
+    assert_ok!(FundAdmin::submit_drawdown(
+      RuntimeOrigin::signed(2),
+      project_id,
+      next_drawdown_id,
+      Some(transaction_data),
+      true,
+    ));
+
+    assert_eq!(
+      FundAdmin::get_drawdown_over_budget(project_id, next_drawdown_id).unwrap(),
+      vec![(expenditure_id, 30)]
+    );
+  });
+}
+
 #[test]
 fn drawdowns_an_administrator_rejects_a_given_drawdown_works() {
   new_test_ext().execute_with(|| {
@@ -5602,6 +5702,236 @@ fn revenues_an_administrator_approves_a_submitted_revenue_works() {
   });
 }
 
+#[test]
+fn revenues_an_approved_revenue_is_distributed_through_the_waterfall_works() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(make_default_full_project());
+    let project_id = ProjectsInfo::<Test>::iter_keys().next().unwrap();
+
+    let terms = DistributionTerms {
+      preferred_return: Permill::from_percent(10),
+      investors_profit_split: Permill::from_percent(80),
+    };
+    let investor_shares: InvestorShares<Test> = bounded_vec![(3, Permill::from_percent(60), 5000)];
+    assert_ok!(FundAdmin::set_distribution_terms(
+      RuntimeOrigin::signed(1),
+      project_id,
+      terms,
+      investor_shares,
+    ));
+
+    let revenue_id = get_revenue_id(project_id, 1);
+    let job_eligible_id = get_job_eligible_id(project_id, make_field_name("Job Eligible Test"));
+
+    let revenue_transaction_data =
+      make_revenue_transaction(Some(job_eligible_id), Some(10000), CUDAction::Create, None);
+
+    assert_ok!(FundAdmin::submit_revenue(
+      RuntimeOrigin::signed(2),
+      project_id,
+      revenue_id,
+      Some(revenue_transaction_data),
+      true,
+    ));
+
+    assert_ok!(FundAdmin::approve_revenue(RuntimeOrigin::signed(1), project_id, revenue_id,));
+
+    // 5000 of capital, 500 of preferred return & 60% of the 80% of the 4500 left
+    let distribution = Distributions::<Test>::get(revenue_id, 3).unwrap();
+    assert_eq!(distribution.return_of_capital, 5000);
+    assert_eq!(distribution.preferred_return, 500);
+    assert_eq!(distribution.profit_split, 2160);
+
+    let position = InvestorPositions::<Test>::get(project_id, 3).unwrap();
+    assert_eq!(position.capital_returned, 5000);
+    assert_eq!(position.preferred_return_paid, 500);
+  });
+}
+
+#[test]
+fn revenues_the_profit_split_follows_the_ownership_of_each_investor_works() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(make_default_full_project());
+    let project_id = ProjectsInfo::<Test>::iter_keys().next().unwrap();
+    assert_ok!(FundAdmin::users(
+      RuntimeOrigin::signed(1),
+      make_user(
+        6,
+        Some(make_field_name("Second Investor Test")),
+        Some(ProxyRole::Investor),
+        CUDAction::Create
+      )
+    ));
+    assert_ok!(FundAdmin::projects_assign_user(
+      RuntimeOrigin::signed(1),
+      project_id,
+      make_user_assignation(6, ProxyRole::Investor, AssignAction::Assign),
+    ));
+
+    // The investors own half of the project
+    let terms = DistributionTerms {
+      preferred_return: Permill::from_percent(10),
+      investors_profit_split: Permill::from_percent(80),
+    };
+    let investor_shares: InvestorShares<Test> =
+      bounded_vec![(3, Permill::from_percent(30), 2000), (6, Permill::from_percent(20), 3000)];
+    assert_ok!(FundAdmin::set_distribution_terms(
+      RuntimeOrigin::signed(1),
+      project_id,
+      terms,
+      investor_shares,
+    ));
+
+    let revenue_id = get_revenue_id(project_id, 1);
+    let job_eligible_id = get_job_eligible_id(project_id, make_field_name("Job Eligible Test"));
+    let revenue_transaction_data =
+      make_revenue_transaction(Some(job_eligible_id), Some(10000), CUDAction::Create, None);
+    assert_ok!(FundAdmin::submit_revenue(
+      RuntimeOrigin::signed(2),
+      project_id,
+      revenue_id,
+      Some(revenue_transaction_data),
+      true,
+    ));
+    assert_ok!(FundAdmin::approve_revenue(RuntimeOrigin::signed(1), project_id, revenue_id,));
+
+    // 5000 of capital & 500 of preferred return leave 4500, the investors' 80% of it is 3600
+    let distribution = Distributions::<Test>::get(revenue_id, 3).unwrap();
+    assert_eq!(distribution.return_of_capital, 2000);
+    assert_eq!(distribution.preferred_return, 200);
+    assert_eq!(distribution.profit_split, 1080);
+    let distribution = Distributions::<Test>::get(revenue_id, 6).unwrap();
+    assert_eq!(distribution.return_of_capital, 3000);
+    assert_eq!(distribution.preferred_return, 300);
+    assert_eq!(distribution.profit_split, 720);
+  });
+}
+
+// Submits & approves the revenue with the given number for `amount`
+fn submit_and_approve_revenue(
+  project_id: ProjectId,
+  revenue_number: RevenueNumber,
+  amount: RevenueAmount,
+) -> RevenueId {
+  let revenue_id = get_revenue_id(project_id, revenue_number);
+  let job_eligible_id = get_job_eligible_id(project_id, make_field_name("Job Eligible Test"));
+  let revenue_transaction_data =
+    make_revenue_transaction(Some(job_eligible_id), Some(amount), CUDAction::Create, None);
+  assert_ok!(FundAdmin::submit_revenue(
+    RuntimeOrigin::signed(2),
+    project_id,
+    revenue_id,
+    Some(revenue_transaction_data),
+    true,
+  ));
+  assert_ok!(FundAdmin::approve_revenue(RuntimeOrigin::signed(1), project_id, revenue_id,));
+  revenue_id
+}
+
+#[test]
+fn revenues_the_distributions_are_paid_from_the_project_account_works() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(make_default_full_project());
+    let project_id = ProjectsInfo::<Test>::iter_keys().next().unwrap();
+    let project_account = FundAdmin::project_account_id(project_id);
+
+    assert_ok!(Assets::force_create(RuntimeOrigin::root(), 1, 1, true, 1));
+    assert_ok!(Assets::mint(RuntimeOrigin::signed(1), 1, project_account, 20000));
+    assert_ok!(FundAdmin::set_disbursement_settings(
+      RuntimeOrigin::signed(1),
+      project_id,
+      Some(DisbursementSettingsData { asset_id: 1, builder: 2 }),
+    ));
+
+    let terms = DistributionTerms {
+      preferred_return: Permill::from_percent(10),
+      investors_profit_split: Permill::from_percent(80),
+    };
+    let investor_shares: InvestorShares<Test> = bounded_vec![(3, Permill::from_percent(60), 5000)];
+    assert_ok!(FundAdmin::set_distribution_terms(
+      RuntimeOrigin::signed(1),
+      project_id,
+      terms,
+      investor_shares,
+    ));
+
+    submit_and_approve_revenue(project_id, 1, 10000);
+
+    // 5000 of capital, 500 of preferred return & 2160 of profit split
+    assert_eq!(Assets::balance(1, 3), 7660);
+    assert_eq!(Assets::balance(1, project_account), 12340);
+  });
+}
+
+#[test]
+fn revenues_investors_left_out_of_the_shares_keep_their_position_works() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(make_default_full_project());
+    let project_id = ProjectsInfo::<Test>::iter_keys().next().unwrap();
+
+    let terms = DistributionTerms {
+      preferred_return: Permill::from_percent(10),
+      investors_profit_split: Permill::from_percent(80),
+    };
+    let investor_shares: InvestorShares<Test> = bounded_vec![(3, Permill::from_percent(60), 5000)];
+    assert_ok!(FundAdmin::set_distribution_terms(
+      RuntimeOrigin::signed(1),
+      project_id,
+      terms.clone(),
+      investor_shares,
+    ));
+    submit_and_approve_revenue(project_id, 1, 10000);
+
+    // The investor is left out of the new shares
+    assert_ok!(FundAdmin::set_distribution_terms(
+      RuntimeOrigin::signed(1),
+      project_id,
+      terms,
+      InvestorShares::<Test>::default(),
+    ));
+    let position = InvestorPositions::<Test>::get(project_id, 3).unwrap();
+    assert!(!position.active);
+    assert_eq!(position.capital_returned, 5000);
+    assert_eq!(position.preferred_return_paid, 500);
+
+    // No further distributions
+    let revenue_id = submit_and_approve_revenue(project_id, 2, 10000);
+    assert_eq!(Distributions::<Test>::get(revenue_id, 3), None);
+    assert_eq!(InvestorPositions::<Test>::get(project_id, 3), Some(position));
+  });
+}
+
+#[test]
+fn revenues_distribution_terms_can_only_include_investors_of_the_project_should_fail() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(make_default_full_project());
+    let project_id = ProjectsInfo::<Test>::iter_keys().next().unwrap();
+
+    let investor_shares: InvestorShares<Test> = bounded_vec![(2, Permill::from_percent(60), 5000)];
+    assert_noop!(
+      FundAdmin::set_distribution_terms(
+        RuntimeOrigin::signed(1),
+        project_id,
+        DistributionTerms::default(),
+        investor_shares,
+      ),
+      Error::<Test>::InvestorNotAssignedToProject
+    );
+
+    let investor_shares: InvestorShares<Test> =
+      bounded_vec![(3, Permill::from_percent(30), 5000), (3, Permill::from_percent(30), 5000)];
+    assert_noop!(
+      FundAdmin::set_distribution_terms(
+        RuntimeOrigin::signed(1),
+        project_id,
+        DistributionTerms::default(),
+        investor_shares,
+      ),
+      Error::<Test>::DuplicatedInvestorShare
+    );
+  });
+}
+
 #[test]
 fn revenues_an_administrator_cannot_approve_a_revenue_if_it_is_not_submitted_should_fail() {
   new_test_ext().execute_with(|| {
@@ -6560,6 +6890,111 @@ fn bank_documents_an_administrator_deletes_bank_documents_should_work() {
   });
 }
 
+#[test]
+fn bank_documents_uploading_bank_documents_disburses_the_drawdown_on_chain_works() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(make_default_full_project());
+    let project_id = ProjectsInfo::<Test>::iter_keys().next().unwrap();
+    let project_account = FundAdmin::project_account_id(project_id);
+
+    assert_ok!(Assets::force_create(RuntimeOrigin::root(), 1, 1, true, 1));
+    assert_ok!(Assets::mint(RuntimeOrigin::signed(1), 1, project_account, 25000));
+
+    assert_ok!(FundAdmin::set_disbursement_settings(
+      RuntimeOrigin::signed(1),
+      project_id,
+      Some(DisbursementSettingsData { asset_id: 1, builder: 2 }),
+    ));
+
+    let drawdown_id = get_drawdown_id(project_id, DrawdownType::EB5, 1);
+    let expenditure_id = get_budget_expenditure_id(
+      project_id,
+      make_field_name("Expenditure Test 1"),
+      ExpenditureType::HardCost,
+    );
+
+    let transaction_data =
+      make_transaction(Some(expenditure_id), Some(10000), CUDAction::Create, None);
+
+    assert_ok!(FundAdmin::submit_drawdown(
+      RuntimeOrigin::signed(2),
+      project_id,
+      drawdown_id,
+      Some(transaction_data),
+      true,
+    ));
+
+    assert_ok!(FundAdmin::approve_drawdown(
+      RuntimeOrigin::signed(1),
+      project_id,
+      drawdown_id,
+      None,
+      None,
+    ));
+
+    let bank_documents = make_documents(1);
+
+    assert_ok!(FundAdmin::bank_confirming_documents(
+      RuntimeOrigin::signed(1),
+      project_id,
+      drawdown_id,
+      Some(bank_documents.clone()),
+      CUDAction::Create,
+    ));
+
+    assert_eq!(Assets::balance(1, 2), 10000);
+    assert_eq!(Assets::balance(1, project_account), 15000);
+
+    let disbursement = Disbursements::<Test>::get(drawdown_id).unwrap();
+    assert_eq!(disbursement.builder, 2);
+    assert_eq!(disbursement.amount, 10000);
+    assert_eq!(disbursement.bank_documents, bank_documents);
+    assert_eq!(FundAdmin::is_disbursement_reconciled(drawdown_id), Some(true));
+
+    let updated_bank_documents = make_documents(2);
+
+    assert_ok!(FundAdmin::bank_confirming_documents(
+      RuntimeOrigin::signed(1),
+      project_id,
+      drawdown_id,
+      Some(updated_bank_documents),
+      CUDAction::Update,
+    ));
+
+    // The disbursement keeps the documents of the transfer
+    assert_eq!(Disbursements::<Test>::get(drawdown_id).unwrap().bank_documents, bank_documents);
+    assert_eq!(FundAdmin::is_disbursement_reconciled(drawdown_id), Some(false));
+
+    assert_noop!(
+      FundAdmin::bank_confirming_documents(
+        RuntimeOrigin::signed(1),
+        project_id,
+        drawdown_id,
+        None,
+        CUDAction::Delete,
+      ),
+      Error::<Test>::DrawdownAlreadyDisbursed
+    );
+  });
+}
+
+#[test]
+fn bank_documents_cannot_set_disbursement_settings_for_a_builder_not_assigned_should_fail() {
+  new_test_ext().execute_with(|| {
+    assert_ok!(make_default_full_project());
+    let project_id = ProjectsInfo::<Test>::iter_keys().next().unwrap();
+
+    assert_noop!(
+      FundAdmin::set_disbursement_settings(
+        RuntimeOrigin::signed(1),
+        project_id,
+        Some(DisbursementSettingsData { asset_id: 1, builder: 3 }),
+      ),
+      Error::<Test>::BuilderNotAssignedToProject
+    );
+  });
+}
+
 #[test]
 fn bank_documents_cannot_delete_documents_if_the_drawdown_is_not_confirmed_should_fail() {
   new_test_ext().execute_with(|| {
@@ -7519,3 +7954,33 @@ fn an_administrators_deletes_a_transaction_for_an_approved_revenue_works() {
     assert_eq!(RevenueTransactionsInfo::<Test>::get(transaction_id).is_none(), true);
   });
 }
+
+#[test]
+fn kill_storage_purges_in_multiple_blocks() {
+  new_test_ext().execute_with(|| {
+    System::set_block_number(1);
+    assert_ok!(make_default_simple_project());
+    assert_eq!(ProjectsInfo::<Test>::iter_keys().count(), 1);
+    assert!(ExpendituresInfo::<Test>::iter_keys().count() > 0);
+
+    // Only a couple of keys can be removed right away
+    assert!(!FundAdmin::start_purge(2).complete);
+    assert!(FundAdmin::storage_purge_progress().is_some());
+    // Nothing can be written until the purge finishes
+    assert_noop!(
+      FundAdmin::sudo_add_administrator(RuntimeOrigin::root(), 2, make_field_name("Admin 2")),
+      Error::<Test>::StoragePurgeInProgress
+    );
+    assert_noop!(
+      FundAdmin::kill_storage(RuntimeOrigin::root()),
+      Error::<Test>::StoragePurgeInProgress
+    );
+
+    FundAdmin::on_idle(1, Weight::from_ref_time(u64::MAX));
+    assert!(FundAdmin::storage_purge_progress().is_none());
+    assert_eq!(ProjectsInfo::<Test>::iter_keys().count(), 0);
+    assert_eq!(ExpendituresInfo::<Test>::iter_keys().count(), 0);
+    assert_eq!(UsersInfo::<Test>::iter_keys().count(), 0);
+    System::assert_last_event(Event::StoragePurged.into());
+  });
+}
@@ -1,6 +1,6 @@
 use super::*;
 use frame_support::{pallet_prelude::*, sp_io::hashing::blake2_256};
-use sp_runtime::sp_std::vec::Vec;
+use sp_runtime::{sp_std::vec::Vec, Permill};
 
 pub type FieldName = BoundedVec<u8, ConstU32<100>>;
 pub type FieldDescription = BoundedVec<u8, ConstU32<400>>;
@@ -114,6 +114,13 @@ pub type RevenueTransactions<T> = BoundedVec<
   <T as Config>::MaxRegistrationsAtTime,
 >;
 
+// Distributions
+pub type DistributionAmount = Amount;
+pub type InvestorShares<T> = BoundedVec<
+  (<T as frame_system::Config>::AccountId, Permill, DistributionAmount),
+  <T as Config>::MaxInvestorsPerProject,
+>;
+
 #[derive(CloneNoBound, Encode, Decode, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen)]
 #[scale_info(skip_type_params(T))]
 #[codec(mel_bound())]
@@ -346,6 +353,69 @@ impl Default for RevenueTransactionStatus {
   }
 }
 
+/// Waterfall an approved revenue is distributed with: the investors' capital is returned first,
+/// then their preferred return is paid, and what is left is split with the developer.
+#[derive(
+  Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, Default, MaxEncodedLen, TypeInfo,
+)]
+pub struct DistributionTerms {
+  /// Preferred return owed to each investor over the life of the project, relative to the
+  /// capital they contributed
+  pub preferred_return: Permill,
+  /// Share of the profit left after the preferred return that goes to the investors, each of
+  /// them gets its ownership of it
+  pub investors_profit_split: Permill,
+}
+
+#[derive(
+  Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, Default, MaxEncodedLen, TypeInfo,
+)]
+pub struct InvestorPosition {
+  pub ownership: Permill,
+  pub capital: DistributionAmount,
+  pub capital_returned: DistributionAmount,
+  pub preferred_return_paid: DistributionAmount,
+  /// Whether the investor receives the distributions, removed investors keep their position
+  pub active: bool,
+}
+
+#[derive(
+  Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, Default, MaxEncodedLen, TypeInfo,
+)]
+pub struct DistributionData {
+  pub return_of_capital: DistributionAmount,
+  pub preferred_return: DistributionAmount,
+  pub profit_split: DistributionAmount,
+  pub created_date: CreatedDate,
+}
+
+#[derive(
+  CloneNoBound, Encode, Decode, PartialEqNoBound, RuntimeDebugNoBound, MaxEncodedLen, TypeInfo,
+)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct DisbursementSettingsData<T: Config> {
+  /// Stablecoin the drawdowns of the project are disbursed in
+  pub asset_id: AssetIdOf<T>,
+  /// Builder that receives the disbursed drawdowns
+  pub builder: T::AccountId,
+}
+
+#[derive(
+  CloneNoBound, Encode, Decode, PartialEqNoBound, RuntimeDebugNoBound, MaxEncodedLen, TypeInfo,
+)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct DisbursementData<T: Config> {
+  pub project_id: ProjectId,
+  pub asset_id: AssetIdOf<T>,
+  pub builder: T::AccountId,
+  pub amount: TotalAmount,
+  /// Bank documents the transfer is reconciled against
+  pub bank_documents: Documents<T>,
+  pub disbursed_date: CreatedDate,
+}
+
 #[derive(
   Encode, Decode, Clone, Eq, PartialEq, RuntimeDebugNoBound, MaxEncodedLen, TypeInfo, Copy,
 )]
@@ -431,6 +501,8 @@ pub enum ProxyPermission {
   RecoveryTransaction,        // recovery_drawdown_transaction: admin
   RecoveryRevenueTransaction, // recovery_revenue_transaction: admin
   BulkUploadTransaction,      // bulk_upload_transaction: admin
+  SetDistributionTerms,       // set_distribution_terms: admin
+  SetDisbursementSettings,    // set_disbursement_settings: admin
 }
 
 impl ProxyPermission {
@@ -461,6 +533,8 @@ impl ProxyPermission {
       Self::RecoveryTransaction => "RecoveryTransaction".as_bytes().to_vec(),
       Self::RecoveryRevenueTransaction => "RecoveryRevenueTransaction".as_bytes().to_vec(),
       Self::BulkUploadTransaction => "BulkUploadTransaction".as_bytes().to_vec(),
+      Self::SetDistributionTerms => "SetDistributionTerms".as_bytes().to_vec(),
+      Self::SetDisbursementSettings => "SetDisbursementSettings".as_bytes().to_vec(),
     }
   }
 
@@ -496,6 +570,8 @@ impl ProxyPermission {
       RecoveryTransaction.to_vec(),
       RecoveryRevenueTransaction.to_vec(),
       BulkUploadTransaction.to_vec(),
+      SetDistributionTerms.to_vec(),
+      SetDisbursementSettings.to_vec(),
     ]
     .to_vec()
   }
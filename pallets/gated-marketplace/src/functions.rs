@@ -1,11 +1,21 @@
 use super::*;
 use crate::types::*;
-use frame_support::{pallet_prelude::*, sp_io::hashing::blake2_256, traits::Time};
+use frame_support::{
+  pallet_prelude::*,
+  sp_io::{hashing::blake2_256, MultiRemovalResults},
+  traits::{tokens::fungibles, Time},
+};
 use frame_system::{pallet_prelude::*, RawOrigin};
-use pallet_rbac::types::*;
+use pallet_rbac::{
+  purge::{PurgeProgress, StoragePurge},
+  types::*,
+};
 use scale_info::prelude::vec; // vec![] macro
 use sp_runtime::sp_std::vec::Vec; // vec primitive
-use sp_runtime::{traits::StaticLookup, Permill};
+use sp_runtime::{
+  traits::{CheckedMul, Saturating, StaticLookup, Zero},
+  PerThing, Permill,
+};
 
 impl<T: Config> Pallet<T> {
   pub fn do_initial_setup() -> DispatchResult {
@@ -322,7 +332,7 @@ impl<T: Config> Pallet<T> {
     collection_id: T::CollectionId,
     item_id: T::ItemId,
     price: T::Balance,
-    percentage: u32,
+    percentage: Permill,
   ) -> Result<[u8; 32], DispatchError> {
     //This function is only called by the owner of the marketplace
     //ensure the marketplace exists
@@ -334,9 +344,11 @@ impl<T: Config> Pallet<T> {
     } else {
       return Err(Error::<T>::CollectionNotFound.into());
     }
+    //ensure the item isn't leased, or the lease allows to sell it
+    pallet_fruniques::Pallet::<T>::ensure_sellable(collection_id, item_id)?;
 
     //ensure the price is valid
-    Self::is_the_offer_valid(price, Permill::from_percent(percentage))?;
+    Self::is_the_offer_valid(price, percentage)?;
 
     //Add timestamp to the offer
     let creation_date = Self::get_timestamp_in_milliseconds().ok_or(Error::<T>::TimestampError)?;
@@ -357,7 +369,7 @@ impl<T: Config> Pallet<T> {
       creator: authority.clone(),
       price,
       fee: price * Permill::deconstruct(marketplace.sell_fee).into() / 1_000_000u32.into(),
-      percentage: Permill::from_percent(percentage),
+      percentage,
       creation_date,
       status: OfferStatus::Open,
       offer_type: OfferType::SellOrder,
@@ -409,6 +421,7 @@ impl<T: Config> Pallet<T> {
     } else {
       return Err(Error::<T>::CollectionNotFound.into());
     }
+    pallet_fruniques::Pallet::<T>::ensure_sellable(collection_id, item_id)?;
 
     //ensure the holder of NFT is in the same marketplace as the caller making the offer
     Self::can_this_item_receive_buy_orders(
@@ -508,6 +521,8 @@ impl<T: Config> Pallet<T> {
 
     //ensure the offer is open and available
     ensure!(offer_data.status == OfferStatus::Open, Error::<T>::OfferIsNotAvailable);
+    //ensure the item wasn't leased after the offer was created
+    pallet_fruniques::Pallet::<T>::ensure_sellable(offer_data.collection_id, offer_data.item_id)?;
     //TODO: Use free_balance instead of total_balance
     //Get asset id
     let asset_id = <Marketplaces<T>>::get(marketplace_id)
@@ -615,6 +630,8 @@ impl<T: Config> Pallet<T> {
 
     //ensure the offer is open and available
     ensure!(offer_data.status == OfferStatus::Open, Error::<T>::OfferIsNotAvailable);
+    //ensure the item wasn't leased after the offer was created
+    pallet_fruniques::Pallet::<T>::ensure_sellable(offer_data.collection_id, offer_data.item_id)?;
 
     let marketplace_id = offer_data.marketplace_id;
     //Get asset id
@@ -1249,6 +1266,130 @@ impl<T: Config> Pallet<T> {
     Ok(())
   }
 
+  pub fn do_enlist_fraction_offer(
+    authority: T::AccountId,
+    marketplace_id: [u8; 32],
+    collection_id: T::CollectionId,
+    item_id: T::ItemId,
+    amount: T::Balance,
+    price_per_fraction: T::Balance,
+  ) -> Result<[u8; 32], DispatchError> {
+    ensure!(<Marketplaces<T>>::contains_key(marketplace_id), Error::<T>::MarketplaceNotFound);
+    Self::is_authorized(authority.clone(), &marketplace_id, Permission::EnlistSellOffer)?;
+
+    let fractions = pallet_fruniques::Pallet::<T>::fungible_fractions(collection_id, item_id)
+      .ok_or(Error::<T>::ItemNotFractionalized)?;
+    ensure!(!amount.is_zero(), Error::<T>::InvalidFractionAmount);
+    ensure!(!price_per_fraction.is_zero(), Error::<T>::PriceMustBeGreaterThanZero);
+    ensure!(
+      pallet_mapped_assets::Pallet::<T>::balance(fractions.asset_id, authority.clone()) >= amount,
+      Error::<T>::NotEnoughFractions
+    );
+
+    let creation_date = Self::get_timestamp_in_milliseconds().ok_or(Error::<T>::TimestampError)?;
+    let offer_id = (marketplace_id, authority.clone(), collection_id, item_id, creation_date)
+      .using_encoded(blake2_256);
+    ensure!(!<FractionOffers<T>>::contains_key(offer_id), Error::<T>::OfferAlreadyExists);
+
+    <FractionOffers<T>>::insert(
+      offer_id,
+      FractionOfferData::<T> {
+        marketplace_id,
+        collection_id,
+        item_id,
+        fraction_asset_id: fractions.asset_id,
+        creator: authority,
+        amount,
+        price_per_fraction,
+        creation_date,
+      },
+    );
+
+    Self::deposit_event(Event::FractionOfferStored(collection_id, item_id, offer_id));
+    Ok(offer_id)
+  }
+
+  pub fn do_take_fraction_offer(
+    origin: OriginFor<T>,
+    offer_id: [u8; 32],
+    amount: T::Balance,
+  ) -> DispatchResult {
+    let buyer = ensure_signed(origin.clone())?;
+    let offer_data = <FractionOffers<T>>::get(offer_id).ok_or(Error::<T>::OfferNotFound)?;
+
+    Self::is_authorized(buyer.clone(), &offer_data.marketplace_id, Permission::TakeSellOffer)?;
+    ensure!(offer_data.creator != buyer, Error::<T>::CannotTakeOffer);
+    ensure!(!amount.is_zero() && amount <= offer_data.amount, Error::<T>::InvalidFractionAmount);
+    // the fractions could have been redeemed after the offer was created
+    ensure!(
+      pallet_fruniques::Pallet::<T>::fungible_fractions(
+        offer_data.collection_id,
+        offer_data.item_id
+      )
+      .map_or(false, |fractions| fractions.asset_id == offer_data.fraction_asset_id),
+      Error::<T>::ItemNotFractionalized
+    );
+    ensure!(
+      pallet_mapped_assets::Pallet::<T>::balance(
+        offer_data.fraction_asset_id,
+        offer_data.creator.clone()
+      ) >= amount,
+      Error::<T>::NotEnoughFractions
+    );
+
+    let marketplace =
+      <Marketplaces<T>>::get(offer_data.marketplace_id).ok_or(Error::<T>::MarketplaceNotFound)?;
+    let price = offer_data
+      .price_per_fraction
+      .checked_mul(&amount)
+      .ok_or(Error::<T>::PriceOverflow)?;
+    let fee = marketplace.sell_fee.mul_floor(price);
+    ensure!(
+      pallet_mapped_assets::Pallet::<T>::balance(marketplace.asset_id, buyer.clone()) >= price,
+      Error::<T>::NotEnoughBalance
+    );
+
+    pallet_mapped_assets::Pallet::<T>::transfer(
+      origin.clone(),
+      marketplace.asset_id.into(),
+      T::Lookup::unlookup(offer_data.creator.clone()),
+      price - fee,
+    )?;
+    pallet_mapped_assets::Pallet::<T>::transfer(
+      origin,
+      marketplace.asset_id.into(),
+      T::Lookup::unlookup(marketplace.creator),
+      fee,
+    )?;
+    <pallet_mapped_assets::Pallet<T> as fungibles::Transfer<T::AccountId>>::transfer(
+      offer_data.fraction_asset_id,
+      &offer_data.creator,
+      &buyer,
+      amount,
+      false,
+    )?;
+
+    let remaining = offer_data.amount - amount;
+    if remaining.is_zero() {
+      <FractionOffers<T>>::remove(offer_id);
+    } else {
+      <FractionOffers<T>>::insert(offer_id, FractionOfferData { amount: remaining, ..offer_data });
+    }
+
+    Self::deposit_event(Event::FractionOfferTaken(offer_id, buyer, amount));
+    Ok(())
+  }
+
+  pub fn do_remove_fraction_offer(authority: T::AccountId, offer_id: [u8; 32]) -> DispatchResult {
+    let offer_data = <FractionOffers<T>>::get(offer_id).ok_or(Error::<T>::OfferNotFound)?;
+    ensure!(offer_data.creator == authority, Error::<T>::CannotRemoveOffer);
+
+    <FractionOffers<T>>::remove(offer_id);
+
+    Self::deposit_event(Event::FractionOfferRemoved(offer_id, offer_data.marketplace_id));
+    Ok(())
+  }
+
   pub fn do_ask_for_redeem(
     who: T::AccountId,
     marketplace: MarketplaceId,
@@ -1328,3 +1469,33 @@ impl<T: Config> Pallet<T> {
     IdOrVec::Vec(Self::module_name().as_bytes().to_vec())
   }
 }
+
+impl<T: Config> StoragePurge for Pallet<T> {
+  const STAGES: u32 = 12;
+
+  fn clear_stage(stage: u32, limit: u32, cursor: Option<&[u8]>) -> MultiRemovalResults {
+    match stage {
+      0 => <Marketplaces<T>>::clear(limit, cursor),
+      1 => <Applications<T>>::clear(limit, cursor),
+      2 => <ApplicationsByAccount<T>>::clear(limit, cursor),
+      3 => <ApplicantsByMarketplace<T>>::clear(limit, cursor),
+      4 => <Custodians<T>>::clear(limit, cursor),
+      5 => <OffersByItem<T>>::clear(limit, cursor),
+      6 => <OffersByAccount<T>>::clear(limit, cursor),
+      7 => <OffersByMarketplace<T>>::clear(limit, cursor),
+      8 => <OffersInfo<T>>::clear(limit, cursor),
+      9 => <AskingForRedemption<T>>::clear(limit, cursor),
+      10 => <BlockedUsersByMarketplace<T>>::clear(limit, cursor),
+      11 => <FractionOffers<T>>::clear(limit, cursor),
+      _ => MultiRemovalResults { maybe_cursor: None, backend: 0, unique: 0, loops: 0 },
+    }
+  }
+
+  fn purge_progress() -> Option<PurgeProgress> {
+    <StoragePurgeProgress<T>>::get()
+  }
+
+  fn set_purge_progress(progress: Option<PurgeProgress>) {
+    <StoragePurgeProgress<T>>::set(progress);
+  }
+}
@@ -23,7 +23,10 @@ pub mod pallet {
   const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
 
   use crate::types::*;
-  use pallet_rbac::types::RoleBasedAccessControl;
+  use pallet_rbac::{
+    purge::{self, PurgeProgress, StoragePurge},
+    types::RoleBasedAccessControl,
+  };
 
   pub type BalanceOf<T> = <<T as pallet_uniques::Config>::Currency as Currency<
     <T as frame_system::Config>::AccountId,
@@ -175,6 +178,16 @@ pub mod pallet {
     OptionQuery,
   >;
 
+  #[pallet::storage]
+  #[pallet::getter(fn fraction_offers)]
+  pub(super) type FractionOffers<T: Config> = StorageMap<
+    _,
+    Identity,
+    OfferId,
+    FractionOfferData<T>, // fraction offer data
+    OptionQuery,
+  >;
+
   #[pallet::storage]
   #[pallet::getter(fn asking_for_redemption)]
   pub(super) type AskingForRedemption<T: Config> = StorageDoubleMap<
@@ -197,6 +210,11 @@ pub mod pallet {
     ValueQuery,
   >;
 
+  #[pallet::storage]
+  #[pallet::getter(fn storage_purge_progress)]
+  /// Progress of the storage removal started by `kill_storage`.
+  pub(super) type StoragePurgeProgress<T: Config> = StorageValue<_, PurgeProgress, OptionQuery>;
+
   #[pallet::event]
   #[pallet::generate_deposit(pub(super) fn deposit_event)]
   pub enum Event<T: Config> {
@@ -233,6 +251,25 @@ pub mod pallet {
     UserBlocked(MarketplaceId, T::AccountId),
     /// User was unblocked. [marketplace_id, account]
     UserUnblocked(MarketplaceId, T::AccountId),
+    /// The storage removal started by kill_storage finished.
+    StoragePurged,
+    /// Fungible shares of a frunique offered. [collection_id, item_id, offer_id]
+    FractionOfferStored(T::CollectionId, T::ItemId, OfferId),
+    /// Part of a fraction offer was taken. [offer_id, buyer, amount]
+    FractionOfferTaken(OfferId, T::AccountId, T::Balance),
+    /// A fraction offer was removed. [offer_id, marketplace_id]
+    FractionOfferRemoved(OfferId, MarketplaceId),
+  }
+
+  #[pallet::hooks]
+  impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+    fn on_idle(_n: T::BlockNumber, remaining_weight: Weight) -> Weight {
+      let (weight, complete) = Self::purge_on_idle(remaining_weight, T::DbWeight::get());
+      if complete {
+        Self::deposit_event(Event::StoragePurged);
+      }
+      weight
+    }
   }
 
   // Errors inform users that something went wrong.
@@ -338,6 +375,16 @@ pub mod pallet {
     OwnerNotInMarketplace,
     /// MappedAssetId not found
     AssetNotFound,
+    /// The frunique hasn't been split into fungible shares
+    ItemNotFractionalized,
+    /// The amount of fractions must be greater than zero
+    InvalidFractionAmount,
+    /// The seller doesn't hold enough fractions for the offer
+    NotEnoughFractions,
+    /// A storage removal started by kill_storage is still in progress
+    StoragePurgeInProgress,
+    /// The price of the fractions overflowed
+    PriceOverflow,
   }
 
   #[pallet::call]
@@ -348,6 +395,7 @@ pub mod pallet {
     #[pallet::call_index(0)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(10))]
     pub fn initial_setup(origin: OriginFor<T>) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       T::RemoveOrigin::ensure_origin(origin.clone())?;
       Self::do_initial_setup()?;
       Ok(())
@@ -371,6 +419,7 @@ pub mod pallet {
       sell_fee: u32,
       asset_id: T::AssetId,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin.clone())?; // origin will be market owner
       let m = Marketplace {
         label,
@@ -400,6 +449,7 @@ pub mod pallet {
       marketplace_id: MarketplaceId,
       block_args: BlockUserArgs<T>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
       match block_args {
         BlockUserArgs::BlockUser(user) => Self::do_block_user(who, marketplace_id, user),
@@ -431,6 +481,7 @@ pub mod pallet {
       fields: Fields<T>,
       custodian_fields: Option<CustodianFields<T>>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       let (custodian, fields) = Self::set_up_application(fields, custodian_fields);
@@ -468,6 +519,7 @@ pub mod pallet {
       fields: Fields<T>,
       custodian_fields: Option<CustodianFields<T>>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       let (custodian, fields) = Self::set_up_application(fields, custodian_fields);
@@ -511,6 +563,7 @@ pub mod pallet {
       approved: bool,
       feedback: BoundedVec<u8, T::MaxFeedbackLen>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_enroll(who, marketplace_id, account_or_application, approved, feedback)
@@ -535,6 +588,7 @@ pub mod pallet {
       fields: Fields<T>,
       custodian_fields: Option<CustodianFields<T>>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_invite(who, marketplace_id, account, fields, custodian_fields)
@@ -562,6 +616,7 @@ pub mod pallet {
       authority_type: MarketplaceRole,
       marketplace_id: [u8; 32],
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_authority(who, account, authority_type, marketplace_id)
@@ -590,6 +645,7 @@ pub mod pallet {
       authority_type: MarketplaceRole,
       marketplace_id: [u8; 32],
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
       // TODO: review If we're allowing more than one role per user per marketplace, we should
       // check what role we want to remove instead of removing the user completely from
@@ -618,6 +674,7 @@ pub mod pallet {
       marketplace_id: [u8; 32],
       new_label: BoundedVec<u8, T::LabelMaxLen>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_update_label_marketplace(who, marketplace_id, new_label)
@@ -639,6 +696,7 @@ pub mod pallet {
     #[pallet::call_index(10)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
     pub fn remove_marketplace(origin: OriginFor<T>, marketplace_id: [u8; 32]) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_remove_marketplace(who, marketplace_id)
@@ -654,6 +712,8 @@ pub mod pallet {
     /// - `collection_id`: The id of the collection.
     /// - `item_id`: The id of the item inside the collection.
     /// - `price`: The price of the item.
+    /// - `percentage`: The percentage of the item to sell, from 1 to 99. The buyer gets a child
+    ///   frunique with that weight.
     ///
     /// ### Considerations:
     /// - You can only create a sell order in the marketplace if you are the owner of the item.
@@ -670,9 +730,17 @@ pub mod pallet {
       price: T::Balance,
       percentage: u32,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
-      Self::do_enlist_sell_offer(who, marketplace_id, collection_id, item_id, price, percentage)?;
+      Self::do_enlist_sell_offer(
+        who,
+        marketplace_id,
+        collection_id,
+        item_id,
+        price,
+        Permill::from_percent(percentage),
+      )?;
 
       Ok(())
     }
@@ -694,6 +762,7 @@ pub mod pallet {
     #[pallet::call_index(12)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
     pub fn take_sell_offer(origin: OriginFor<T>, offer_id: [u8; 32]) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       ensure_signed(origin.clone())?;
 
       Self::do_take_sell_offer(origin, offer_id)
@@ -716,6 +785,7 @@ pub mod pallet {
     #[pallet::call_index(13)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
     pub fn remove_offer(origin: OriginFor<T>, offer_id: [u8; 32]) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       //Currently, we can only remove one offer at a time.
       //TODO: Add support for removing multiple offers at a time.
       let who = ensure_signed(origin.clone())?;
@@ -748,6 +818,7 @@ pub mod pallet {
       price: T::Balance,
       percentage: u32,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
 
       Self::do_enlist_buy_offer(who, marketplace_id, collection_id, item_id, price, percentage)?;
@@ -774,6 +845,7 @@ pub mod pallet {
     #[pallet::call_index(15)]
     #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
     pub fn take_buy_offer(origin: OriginFor<T>, offer_id: [u8; 32]) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin.clone())?;
 
       Self::do_take_buy_offer(who, offer_id)
@@ -794,6 +866,7 @@ pub mod pallet {
       marketplace: MarketplaceId,
       redeem: RedeemArgs<T>,
     ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       let who = ensure_signed(origin)?;
       match redeem {
         RedeemArgs::AskForRedemption { collection_id, item_id } => {
@@ -817,22 +890,101 @@ pub mod pallet {
     ///
     /// ### Considerations:
     /// - This function is only available to the `admin` with sudo access.
+    /// - The storage maps that can't be cleared in this call are cleared in the next blocks,
+    ///   `StoragePurged` is emitted once they are empty.
     #[pallet::call_index(17)]
-    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
+    #[pallet::weight(
+      Weight::from_ref_time(10_000)
+        + T::DbWeight::get().reads_writes(2, 1 + u64::from(purge::KEYS_PER_CALL))
+    )]
     pub fn kill_storage(origin: OriginFor<T>) -> DispatchResult {
       T::RemoveOrigin::ensure_origin(origin.clone())?;
-      let _ = <Marketplaces<T>>::clear(1000, None);
-      let _ = <Applications<T>>::clear(1000, None);
-      let _ = <ApplicationsByAccount<T>>::clear(1000, None);
-      let _ = <ApplicantsByMarketplace<T>>::clear(1000, None);
-      let _ = <Custodians<T>>::clear(1000, None);
-      let _ = <OffersByItem<T>>::clear(1000, None);
-      let _ = <OffersByAccount<T>>::clear(1000, None);
-      let _ = <OffersByMarketplace<T>>::clear(1000, None);
-      let _ = <OffersInfo<T>>::clear(1000, None);
-      let _ = <AskingForRedemption<T>>::clear(1000, None);
+      // Starting over would drop the progress of the purge in progress
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
       <T as Config>::Rbac::remove_pallet_storage(Self::pallet_id())?;
+
+      // The maps are cleared in multiple blocks, see `on_idle`
+      if Self::start_purge(purge::KEYS_PER_CALL).complete {
+        Self::deposit_event(Event::StoragePurged);
+      }
       Ok(())
     }
+
+    /// Enlist the fungible shares of a fractionalized item.
+    ///
+    /// ### Parameters:
+    /// - `origin`: The user who performs the action.
+    /// - `marketplace_id`: The id of the marketplace where we want to create the offer.
+    /// - `collection_id`: The id of the collection.
+    /// - `item_id`: The id of the fractionalized item.
+    /// - `amount`: The amount of fractions to sell.
+    /// - `price_per_fraction`: The price of each fraction, in the marketplace asset.
+    ///
+    /// ### Considerations:
+    /// - The item needs to be fractionalized with the fruniques pallet first.
+    /// - The fractions stay with the seller until the offer is taken, the seller needs to hold
+    ///   them when that happens.
+    #[pallet::call_index(18)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
+    pub fn enlist_fraction_offer(
+      origin: OriginFor<T>,
+      marketplace_id: [u8; 32],
+      collection_id: T::CollectionId,
+      item_id: T::ItemId,
+      amount: T::Balance,
+      price_per_fraction: T::Balance,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+
+      Self::do_enlist_fraction_offer(
+        who,
+        marketplace_id,
+        collection_id,
+        item_id,
+        amount,
+        price_per_fraction,
+      )?;
+
+      Ok(())
+    }
+
+    /// Buy fractions from a fraction offer.
+    ///
+    /// ### Parameters:
+    /// - `origin`: The user who performs the action.
+    /// - `offer_id`: The id of the fraction offer.
+    /// - `amount`: The amount of fractions to buy, up to the remaining amount of the offer.
+    ///
+    /// ### Considerations:
+    /// - The buyer pays `amount * price_per_fraction`, the marketplace sell fee goes to the
+    ///   marketplace creator.
+    /// - The offer is removed once all its fractions are sold.
+    #[pallet::call_index(19)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(4))]
+    pub fn take_fraction_offer(
+      origin: OriginFor<T>,
+      offer_id: [u8; 32],
+      amount: T::Balance,
+    ) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      ensure_signed(origin.clone())?;
+
+      Self::do_take_fraction_offer(origin, offer_id, amount)
+    }
+
+    /// Remove a fraction offer.
+    ///
+    /// ### Parameters:
+    /// - `origin`: The creator of the offer.
+    /// - `offer_id`: The id of the fraction offer.
+    #[pallet::call_index(20)]
+    #[pallet::weight(Weight::from_ref_time(10_000) + T::DbWeight::get().writes(1))]
+    pub fn remove_fraction_offer(origin: OriginFor<T>, offer_id: [u8; 32]) -> DispatchResult {
+      ensure!(!Self::is_purging(), Error::<T>::StoragePurgeInProgress);
+      let who = ensure_signed(origin)?;
+
+      Self::do_remove_fraction_offer(who, offer_id)
+    }
   }
 }
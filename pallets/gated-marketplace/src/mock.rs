@@ -111,6 +111,12 @@ impl pallet_gated_marketplace::Config for Test {
 parameter_types! {
   pub const ChildMaxLen: u32 = 10;
   pub const MaxParentsInCollection: u32 = 10;
+  pub const MaxVerifiers: u32 = 3;
+  pub const MaxSchemaFields: u32 = 10;
+  pub const MaxHistoryLength: u32 = 10;
+  pub const MaxBatchSpawn: u32 = 5;
+  pub const MaxThawsPerBlock: u32 = 3;
+  pub const InvitationExpiration: u64 = 10;
 }
 
 impl pallet_fruniques::Config for Test {
@@ -119,6 +125,12 @@ impl pallet_fruniques::Config for Test {
   type ChildMaxLen = ChildMaxLen;
   type MaxParentsInCollection = MaxParentsInCollection;
   type Rbac = RBAC;
+  type MaxVerifiers = MaxVerifiers;
+  type MaxSchemaFields = MaxSchemaFields;
+  type MaxHistoryLength = MaxHistoryLength;
+  type MaxBatchSpawn = MaxBatchSpawn;
+  type MaxThawsPerBlock = MaxThawsPerBlock;
+  type InvitationExpiration = InvitationExpiration;
 }
 
 parameter_types! {
@@ -172,7 +184,7 @@ impl pallet_balances::Config for Test {
 
 parameter_types! {
   pub const MaxScopesPerPallet: u32 = 2;
-  pub const MaxRolesPerPallet: u32 = 6;
+  pub const MaxRolesPerPallet: u32 = 7;
   pub const RoleMaxLen: u32 = 25;
   pub const PermissionMaxLen: u32 = 25;
   pub const MaxPermissionsPerRole: u32 = 30;
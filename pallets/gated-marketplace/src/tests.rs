@@ -1,11 +1,12 @@
-use crate::{mock::*, types::*, Config, Error};
+use crate::{mock::*, types::*, Config, Error, Event};
 use codec::Encode;
 use frame_support::{
   assert_noop, assert_ok,
-  traits::{ConstU32, Currency, Len},
+  traits::{ConstU32, Currency, Hooks, Len},
+  weights::Weight,
   BoundedVec,
 };
-use pallet_rbac::types::RoleBasedAccessControl;
+use pallet_rbac::{purge::StoragePurge, types::RoleBasedAccessControl};
 use sp_io::hashing::blake2_256;
 use sp_runtime::{sp_std::vec::Vec, Permill};
 use std::vec;
@@ -1845,6 +1846,43 @@ fn enlist_sell_offer_works() {
   });
 }
 
+#[test]
+fn enlist_sell_offer_rented_item_shouldnt_work() {
+  new_test_ext().execute_with(|| {
+    Balances::make_free_balance_be(&1, 100);
+
+    assert_ok!(GatedMarketplace::create_marketplace(
+      RuntimeOrigin::signed(1),
+      2,
+      create_label("my marketplace"),
+      500,
+      600,
+      1,
+    ));
+    let m_id = get_marketplace_id("my marketplace", 500, 600, 1);
+
+    assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_ok!(Fruniques::set_user(RuntimeOrigin::signed(1), 0, 0, 3, 10, false));
+    assert_ok!(Fruniques::set_user(RuntimeOrigin::signed(1), 0, 1, 3, 10, true));
+
+    assert_noop!(
+      GatedMarketplace::enlist_sell_offer(RuntimeOrigin::signed(1), m_id, 0, 0, 10000, 10),
+      pallet_fruniques::Error::<Test>::FruniqueRented
+    );
+    // The lease of the second item allows to sell it
+    assert_ok!(GatedMarketplace::enlist_sell_offer(
+      RuntimeOrigin::signed(1),
+      m_id,
+      0,
+      1,
+      10000,
+      10
+    ));
+  });
+}
+
 #[test]
 fn enlist_sell_offer_item_does_not_exist_shouldnt_work() {
   new_test_ext().execute_with(|| {
@@ -3170,3 +3208,184 @@ fn self_enroll_while_already_participant_should_fail() {
     assert_noop!(GatedMarketplace::self_enroll(2, m_id,), Error::<Test>::UserAlreadyParticipant);
   });
 }
+
+fn fraction_offer_id(marketplace_id: [u8; 32], creator: u64) -> [u8; 32] {
+  (marketplace_id, creator, 0u32, 0u32, 0u64).using_encoded(blake2_256)
+}
+
+fn setup_fraction_market() -> [u8; 32] {
+  Balances::make_free_balance_be(&1, 100);
+  Balances::make_free_balance_be(&2, 100);
+  assert_ok!(GatedMarketplace::create_marketplace(
+    RuntimeOrigin::signed(1),
+    2,
+    create_label("fractions"),
+    10,
+    10,
+    1,
+  ));
+  assert_ok!(Assets::create(RuntimeOrigin::signed(1), 1, 1, 1));
+  assert_ok!(Assets::mint(RuntimeOrigin::signed(1), 1, 2, 10000));
+
+  assert_ok!(Fruniques::create_collection(RuntimeOrigin::signed(1), dummy_description()));
+  assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+  assert_ok!(Fruniques::fractionalize(RuntimeOrigin::signed(1), 0, 0, 2, 100));
+  get_marketplace_id("fractions", 10, 10, 1)
+}
+
+#[test]
+fn take_fraction_offer_works() {
+  new_test_ext().execute_with(|| {
+    let m_id = setup_fraction_market();
+    assert_noop!(
+      GatedMarketplace::enlist_fraction_offer(RuntimeOrigin::signed(1), m_id, 0, 0, 101, 100),
+      Error::<Test>::NotEnoughFractions
+    );
+    assert_ok!(GatedMarketplace::enlist_fraction_offer(
+      RuntimeOrigin::signed(1),
+      m_id,
+      0,
+      0,
+      60,
+      100
+    ));
+    let offer_id = fraction_offer_id(m_id, 1);
+    assert_eq!(GatedMarketplace::fraction_offers(offer_id).unwrap().amount, 60);
+
+    assert_noop!(
+      GatedMarketplace::take_fraction_offer(RuntimeOrigin::signed(1), offer_id, 10),
+      Error::<Test>::CannotTakeOffer
+    );
+    assert_noop!(
+      GatedMarketplace::take_fraction_offer(RuntimeOrigin::signed(2), offer_id, 61),
+      Error::<Test>::InvalidFractionAmount
+    );
+
+    // Part of the offer is taken, the buyer pays 40 * 100 and 10% goes to the marketplace creator
+    assert_ok!(GatedMarketplace::take_fraction_offer(RuntimeOrigin::signed(2), offer_id, 40));
+    assert_eq!(Assets::balance(1, 2), 6000);
+    assert_eq!(Assets::balance(1, 1), 4000);
+    assert_eq!(Assets::balance(2, 2), 40);
+    assert_eq!(Assets::balance(2, 1), 60);
+    assert_eq!(GatedMarketplace::fraction_offers(offer_id).unwrap().amount, 20);
+
+    // The offer is removed once it's sold out
+    assert_ok!(GatedMarketplace::take_fraction_offer(RuntimeOrigin::signed(2), offer_id, 20));
+    assert!(GatedMarketplace::fraction_offers(offer_id).is_none());
+    assert_eq!(Assets::balance(2, 2), 60);
+  });
+}
+
+#[test]
+fn take_fraction_offer_with_an_overflowing_price_shouldnt_work() {
+  new_test_ext().execute_with(|| {
+    let m_id = setup_fraction_market();
+    assert_ok!(GatedMarketplace::enlist_fraction_offer(
+      RuntimeOrigin::signed(1),
+      m_id,
+      0,
+      0,
+      60,
+      u64::MAX
+    ));
+    let offer_id = fraction_offer_id(m_id, 1);
+
+    assert_noop!(
+      GatedMarketplace::take_fraction_offer(RuntimeOrigin::signed(2), offer_id, 2),
+      Error::<Test>::PriceOverflow
+    );
+  });
+}
+
+#[test]
+fn enlist_fraction_offer_without_fractions_shouldnt_work() {
+  new_test_ext().execute_with(|| {
+    let m_id = setup_fraction_market();
+    assert_ok!(Fruniques::spawn(RuntimeOrigin::signed(1), 0, dummy_description(), None, None));
+    assert_noop!(
+      GatedMarketplace::enlist_fraction_offer(RuntimeOrigin::signed(1), m_id, 0, 1, 10, 100),
+      Error::<Test>::ItemNotFractionalized
+    );
+    assert_noop!(
+      GatedMarketplace::enlist_fraction_offer(RuntimeOrigin::signed(1), m_id, 0, 0, 0, 100),
+      Error::<Test>::InvalidFractionAmount
+    );
+  });
+}
+
+#[test]
+fn remove_fraction_offer_works() {
+  new_test_ext().execute_with(|| {
+    let m_id = setup_fraction_market();
+    assert_ok!(GatedMarketplace::enlist_fraction_offer(
+      RuntimeOrigin::signed(1),
+      m_id,
+      0,
+      0,
+      10,
+      100
+    ));
+    let offer_id = fraction_offer_id(m_id, 1);
+    assert_noop!(
+      GatedMarketplace::remove_fraction_offer(RuntimeOrigin::signed(2), offer_id),
+      Error::<Test>::CannotRemoveOffer
+    );
+    assert_ok!(GatedMarketplace::remove_fraction_offer(RuntimeOrigin::signed(1), offer_id));
+    assert!(GatedMarketplace::fraction_offers(offer_id).is_none());
+    assert_noop!(
+      GatedMarketplace::take_fraction_offer(RuntimeOrigin::signed(2), offer_id, 10),
+      Error::<Test>::OfferNotFound
+    );
+  });
+}
+
+#[test]
+fn kill_storage_purges_in_multiple_blocks() {
+  new_test_ext().execute_with(|| {
+    System::set_block_number(1);
+    let m_id = setup_fraction_market();
+    assert_ok!(GatedMarketplace::enlist_fraction_offer(
+      RuntimeOrigin::signed(1),
+      m_id,
+      0,
+      0,
+      10,
+      100
+    ));
+
+    // Only a couple of keys can be removed right away
+    assert!(!GatedMarketplace::start_purge(2).complete);
+    assert!(GatedMarketplace::storage_purge_progress().is_some());
+    // Nothing can be written until the purge finishes
+    assert_noop!(
+      GatedMarketplace::create_marketplace(
+        RuntimeOrigin::signed(1),
+        2,
+        create_label("other marketplace"),
+        10,
+        10,
+        1,
+      ),
+      Error::<Test>::StoragePurgeInProgress
+    );
+    assert_noop!(
+      GatedMarketplace::kill_storage(RuntimeOrigin::root()),
+      Error::<Test>::StoragePurgeInProgress
+    );
+
+    GatedMarketplace::on_idle(1, Weight::from_ref_time(u64::MAX));
+    assert!(GatedMarketplace::storage_purge_progress().is_none());
+    assert!(GatedMarketplace::marketplaces(m_id).is_none());
+    assert!(GatedMarketplace::fraction_offers(fraction_offer_id(m_id, 1)).is_none());
+    System::assert_last_event(Event::StoragePurged.into());
+
+    assert_ok!(GatedMarketplace::create_marketplace(
+      RuntimeOrigin::signed(1),
+      2,
+      create_label("other marketplace"),
+      10,
+      10,
+      1,
+    ));
+  });
+}
@@ -265,3 +265,19 @@ pub struct OfferData<T: Config> {
   pub offer_type: OfferType,
   pub buyer: Option<(T::AccountId, [u8; 32])>,
 }
+
+/// Sell offer for the fungible shares of a fractionalized frunique. The shares stay with the
+/// creator until the offer is taken, buyers can take any part of the remaining `amount`.
+#[derive(CloneNoBound, Encode, Decode, RuntimeDebugNoBound, TypeInfo, MaxEncodedLen)]
+#[scale_info(skip_type_params(T))]
+#[codec(mel_bound())]
+pub struct FractionOfferData<T: Config> {
+  pub marketplace_id: [u8; 32],
+  pub collection_id: T::CollectionId,
+  pub item_id: T::ItemId,
+  pub fraction_asset_id: T::AssetId,
+  pub creator: T::AccountId,
+  pub amount: T::Balance,
+  pub price_per_fraction: T::Balance,
+  pub creation_date: u64,
+}
@@ -15,6 +15,7 @@ mod tests;
 mod benchmarking;
 
 mod functions;
+pub mod purge;
 pub mod types;
 
 #[frame_support::pallet]
@@ -0,0 +1,108 @@
+//! Multi-block removal of the storage of a pallet.
+//!
+//! Pallets implement [`StoragePurge`] listing their storage maps as stages, keep the
+//! [`PurgeProgress`] in a storage value, start the purge from their `kill_storage` extrinsic
+//! and continue it from `on_idle` until every stage is empty.
+use frame_support::{pallet_prelude::*, sp_io::MultiRemovalResults, weights::RuntimeDbWeight};
+
+/// Maximum length of the cursor returned when clearing a storage map
+pub const MAX_CURSOR_LEN: u32 = 512;
+
+/// Keys removed by the extrinsic that starts a purge, the rest is removed in `on_idle`
+pub const KEYS_PER_CALL: u32 = 1000;
+
+pub type PurgeCursor = BoundedVec<u8, ConstU32<MAX_CURSOR_LEN>>;
+
+#[derive(Encode, Decode, Clone, Eq, PartialEq, Default, RuntimeDebug, TypeInfo, MaxEncodedLen)]
+pub struct PurgeProgress {
+  /// Storage map being cleared
+  pub stage: u32,
+  /// Cursor returned by the last removal in the current stage
+  pub cursor: Option<PurgeCursor>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, RuntimeDebug)]
+pub struct PurgeStep {
+  /// Keys removed, at least one per removal call
+  pub removed: u32,
+  /// Whether every stage is empty and the purge finished
+  pub complete: bool,
+}
+
+pub trait StoragePurge {
+  /// Number of storage maps removed by the purge
+  const STAGES: u32;
+
+  /// Removes up to `limit` keys of the storage map at `stage`, starting from `cursor`
+  fn clear_stage(stage: u32, limit: u32, cursor: Option<&[u8]>) -> MultiRemovalResults;
+
+  fn purge_progress() -> Option<PurgeProgress>;
+
+  fn set_purge_progress(progress: Option<PurgeProgress>);
+
+  /// Whether a purge is in progress. Extrinsics that write to the pallet storage are rejected
+  /// until it finishes, otherwise their data would be removed by the purge.
+  fn is_purging() -> bool {
+    Self::purge_progress().is_some()
+  }
+
+  /// Starts a purge from the first stage, removing up to `limit` keys right away.
+  /// A purge in progress is restarted.
+  fn start_purge(limit: u32) -> PurgeStep {
+    Self::set_purge_progress(Some(PurgeProgress::default()));
+    Self::continue_purge(limit).unwrap_or(PurgeStep { removed: 0, complete: true })
+  }
+
+  /// Removes up to `limit` keys of the purge in progress, `None` if there's no purge
+  fn continue_purge(limit: u32) -> Option<PurgeStep> {
+    let mut progress = Self::purge_progress()?;
+    let mut removed: u32 = 0;
+
+    while removed < limit && progress.stage < Self::STAGES {
+      let result = Self::clear_stage(
+        progress.stage,
+        limit - removed,
+        progress.cursor.as_ref().map(|cursor| cursor.as_slice()),
+      );
+      removed = removed.saturating_add(result.loops.max(1));
+      match result.maybe_cursor {
+        // A cursor too long is dropped, the removal restarts from the first remaining key
+        Some(cursor) => progress.cursor = PurgeCursor::try_from(cursor).ok(),
+        None => {
+          progress.stage += 1;
+          progress.cursor = None;
+        },
+      }
+    }
+
+    let complete = progress.stage >= Self::STAGES;
+    Self::set_purge_progress(if complete { None } else { Some(progress) });
+
+    Some(PurgeStep { removed, complete })
+  }
+
+  /// Continues the purge with the weight left in the block, returns the weight used and
+  /// whether the purge finished in this call
+  fn purge_on_idle(remaining_weight: Weight, db_weight: RuntimeDbWeight) -> (Weight, bool) {
+    // Reading and writing the progress
+    let overhead = db_weight.reads_writes(1, 1);
+    if remaining_weight.any_lt(overhead) {
+      return (Weight::zero(), false);
+    }
+
+    let per_key = db_weight.reads_writes(1, 1).ref_time().max(1);
+    let limit =
+      (remaining_weight.saturating_sub(overhead).ref_time() / per_key).min(u32::MAX as u64) as u32;
+    if limit == 0 {
+      return (Weight::zero(), false);
+    }
+
+    match Self::continue_purge(limit) {
+      Some(step) => (
+        overhead.saturating_add(db_weight.reads_writes(step.removed as u64, step.removed as u64)),
+        step.complete,
+      ),
+      None => (db_weight.reads(1), false),
+    }
+  }
+}
@@ -129,7 +129,10 @@ pub type CheckedExtrinsic = generic::CheckedExtrinsic<AccountId, RuntimeCall, Si
 ///
 /// Should be cleared after every release.
 /// Example: "pallet_template::migration::v1::MigrateToV1<Runtime>"
-pub type Migrations = ();
+pub type Migrations = (
+  pallet_fund_admin::migration::v2::MigrateToV2<Runtime>,
+  pallet_afloat::migration::v2::MigrateToV2<Runtime>,
+);
 
 /// Executive: handles dispatch to the various modules.
 pub type Executive = frame_executive::Executive<
@@ -977,6 +980,12 @@ parameter_types! {
   pub const ValueLimit: u32 = 256;
   pub const ChildMaxLen: u32 = 25;
   pub const MaxParentsInCollection: u32 = 4_294_967_295;
+  pub const MaxVerifiers: u32 = 10;
+  pub const MaxSchemaFields: u32 = 50;
+  pub const MaxHistoryLength: u32 = 100;
+  pub const MaxBatchSpawn: u32 = 100;
+  pub const MaxThawsPerBlock: u32 = 50;
+  pub const InvitationExpiration: BlockNumber = 7 * DAYS;
 }
 
 impl pallet_uniques::Config for Runtime {
@@ -1004,6 +1013,12 @@ impl pallet_fruniques::Config for Runtime {
   type ChildMaxLen = ChildMaxLen;
   type MaxParentsInCollection = MaxParentsInCollection;
   type Rbac = RBAC;
+  type MaxVerifiers = MaxVerifiers;
+  type MaxSchemaFields = MaxSchemaFields;
+  type MaxHistoryLength = MaxHistoryLength;
+  type MaxBatchSpawn = MaxBatchSpawn;
+  type MaxThawsPerBlock = MaxThawsPerBlock;
+  type InvitationExpiration = InvitationExpiration;
 }
 
 parameter_types! {
@@ -1107,6 +1122,7 @@ parameter_types! {
   pub const MinAdminBalance: Balance = 10_000_000_000_000;
   pub const TransferAmount: Balance = 10_000_000_000_000;
   pub const MaxRecoveryChanges:u32 = 1_000;
+  pub const FundAdminPalletId: PalletId = PalletId(*b"fundadmn");
 }
 
 impl pallet_fund_admin::Config for Runtime {
@@ -1119,6 +1135,8 @@ impl pallet_fund_admin::Config for Runtime {
     pallet_collective::EnsureProportionAtLeast<AccountId, CouncilCollective, 3, 5>,
   >;
   type Currency = Balances;
+  type Assets = MappedAssets;
+  type PalletId = FundAdminPalletId;
 
   type MaxDocuments = MaxDocuments;
   type MaxProjectsPerUser = MaxProjectsPerUser;
@@ -1143,6 +1161,29 @@ impl pallet_fund_admin::Config for Runtime {
   type TransferAmount = TransferAmount;
 }
 
+parameter_types! {
+  // Fee revenue is totaled every 30 days
+  pub const AfloatFeePeriod: u64 = 30 * 24 * 60 * 60;
+}
+
+impl pallet_afloat::Config for Runtime {
+  type RuntimeEvent = RuntimeEvent;
+  type Currency = Balances;
+  type TimeProvider = Timestamp;
+  type RemoveOrigin = EitherOfDiverse<
+    EnsureRoot<AccountId>,
+    pallet_collective::EnsureProportionAtLeast<AccountId, CouncilCollective, 3, 5>,
+  >;
+  type Rbac = RBAC;
+  type ItemId = u32;
+  type MaxOffersPerTaxCredit = ConstU32<100>;
+  type MaxOffersPerCreator = ConstU32<10_000>;
+  type MaxOfferHistoryPerTaxCredit = ConstU32<10_000>;
+  type MaxTransactionsPerUser = ConstU32<10_000>;
+  type MaxTransactionsPerTaxCredit = ConstU32<10_000>;
+  type FeePeriod = AfloatFeePeriod;
+}
+
 /// Configure the pallet-template in pallets/template.
 impl pallet_template::Config for Runtime {
   type RuntimeEvent = RuntimeEvent;
@@ -1215,6 +1256,7 @@ construct_runtime!(
     FundAdmin: pallet_fund_admin::{Pallet, Call, Storage, Event<T>}  = 157,
     TemplateModule: pallet_template::{Pallet, Call, Storage, Event<T>} = 158,
     MappedAssets: pallet_mapped_assets::{Pallet, Call, Storage, Event<T>} = 159,
+    Afloat: pallet_afloat::{Pallet, Call, Storage, Config<T>, Event<T>} = 160,
   }
 );
 
@@ -1320,6 +1362,122 @@ impl_runtime_apis! {
     }
   }
 
+  impl pallet_fruniques_runtime_api::FruniquesApi<Block, AccountId, BlockNumber> for Runtime {
+    fn provenance(
+      collection_id: pallet_fruniques::types::CollectionId,
+      item_id: pallet_fruniques::types::ItemId,
+    ) -> Option<
+      pallet_fruniques::types::Provenance<
+        AccountId,
+        pallet_fruniques::types::CollectionId,
+        pallet_fruniques::types::ItemId,
+        BlockNumber,
+      >,
+    > {
+      Fruniques::get_provenance(collection_id, item_id)
+    }
+
+    fn history(
+      collection_id: pallet_fruniques::types::CollectionId,
+      item_id: pallet_fruniques::types::ItemId,
+    ) -> Vec<pallet_fruniques::types::HistoryEntry<AccountId, BlockNumber>> {
+      Fruniques::frunique_history(collection_id, item_id).into_inner()
+    }
+  }
+
+  impl pallet_fund_admin_runtime_api::FundAdminApi<Block> for Runtime {
+    fn remaining_budget_by_expenditure(
+      project_id: pallet_fund_admin::types::ProjectId,
+    ) -> Vec<(
+      pallet_fund_admin::types::ExpenditureId,
+      pallet_fund_admin::types::ExpenditureAmount,
+    )> {
+      FundAdmin::get_remaining_budget_by_expenditure(project_id)
+    }
+
+    fn remaining_budget_by_expenditure_type(
+      project_id: pallet_fund_admin::types::ProjectId,
+    ) -> Vec<(
+      pallet_fund_admin::types::ExpenditureType,
+      pallet_fund_admin::types::ExpenditureAmount,
+    )> {
+      FundAdmin::get_remaining_budget_by_expenditure_type(project_id)
+    }
+
+    fn drawdown_over_budget(
+      project_id: pallet_fund_admin::types::ProjectId,
+      drawdown_id: pallet_fund_admin::types::DrawdownId,
+    ) -> Vec<(
+      pallet_fund_admin::types::ExpenditureId,
+      pallet_fund_admin::types::ExpenditureAmount,
+    )> {
+      FundAdmin::get_drawdown_over_budget(project_id, drawdown_id).unwrap_or_default()
+    }
+
+    fn is_disbursement_reconciled(
+      drawdown_id: pallet_fund_admin::types::DrawdownId,
+    ) -> Option<bool> {
+      FundAdmin::is_disbursement_reconciled(drawdown_id)
+    }
+  }
+
+  impl
+    pallet_afloat_runtime_api::AfloatApi<
+      Block,
+      AccountId,
+      <Runtime as pallet_uniques::Config>::ItemId,
+      pallet_afloat::types::Offer<Runtime>,
+      pallet_afloat::types::Transaction<Runtime>,
+    > for Runtime
+  {
+    fn offers_by_creator(
+      creator: AccountId,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Offer<Runtime>)> {
+      Afloat::get_offers_by_creator(creator, start_after, limit)
+    }
+
+    fn offers_by_tax_credit(
+      tax_credit_id: <Runtime as pallet_uniques::Config>::ItemId,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Offer<Runtime>)> {
+      Afloat::get_offers_by_tax_credit(tax_credit_id, start_after, limit)
+    }
+
+    fn offers_by_status(
+      status: pallet_afloat::types::OfferStatus,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Offer<Runtime>)> {
+      Afloat::get_offers_by_status(status, start_after, limit)
+    }
+
+    fn transactions_by_user(
+      user: AccountId,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Transaction<Runtime>)> {
+      Afloat::get_transactions_by_user(user, start_after, limit)
+    }
+
+    fn transactions_by_tax_credit(
+      tax_credit_id: <Runtime as pallet_uniques::Config>::ItemId,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Transaction<Runtime>)> {
+      Afloat::get_transactions_by_tax_credit(tax_credit_id, start_after, limit)
+    }
+
+    fn transactions_by_status(
+      status: pallet_afloat::types::OfferStatus,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Transaction<Runtime>)> {
+      Afloat::get_transactions_by_status(status, start_after, limit)
+    }
+  }
   impl pallet_transaction_payment_rpc_runtime_api::TransactionPaymentApi<Block, Balance> for Runtime {
     fn query_info(
       uxt: <Block as BlockT>::Extrinsic,
@@ -537,6 +537,12 @@ parameter_types! {
   pub const ValueLimit: u32 = 256;
   pub const ChildMaxLen: u32 = 100;
   pub const MaxParentsInCollection: u32 = 4_294_967_295;
+  pub const MaxVerifiers: u32 = 10;
+  pub const MaxSchemaFields: u32 = 50;
+  pub const MaxHistoryLength: u32 = 100;
+  pub const MaxBatchSpawn: u32 = 100;
+  pub const MaxThawsPerBlock: u32 = 50;
+  pub const InvitationExpiration: BlockNumber = 7 * DAYS;
   // pub const FruniquesPalletId: PalletId = PalletId(*b"frunique");
 }
 
@@ -568,6 +574,12 @@ impl pallet_fruniques::Config for Runtime {
   type Rbac = RBAC;
   type ChildMaxLen = ChildMaxLen;
   type MaxParentsInCollection = MaxParentsInCollection;
+  type MaxVerifiers = MaxVerifiers;
+  type MaxSchemaFields = MaxSchemaFields;
+  type MaxHistoryLength = MaxHistoryLength;
+  type MaxBatchSpawn = MaxBatchSpawn;
+  type MaxThawsPerBlock = MaxThawsPerBlock;
+  type InvitationExpiration = InvitationExpiration;
   // type PalletId = FruniquesPalletId;
 }
 
@@ -593,6 +605,7 @@ parameter_types! {
   pub const MinAdminBalance: Balance = 10_000_000_000_000;
   pub const TransferAmount: Balance = 10_000_000_000_000;
   pub const MaxRecoveryChanges:u32 = 1_000;
+  pub const FundAdminPalletId: PalletId = PalletId(*b"fundadmn");
 }
 
 impl pallet_fund_admin::Config for Runtime {
@@ -605,6 +618,8 @@ impl pallet_fund_admin::Config for Runtime {
     pallet_collective::EnsureProportionAtLeast<AccountId, CouncilCollective, 3, 5>,
   >;
   type Currency = Balances;
+  type Assets = MappedAssets;
+  type PalletId = FundAdminPalletId;
 
   type MaxDocuments = MaxDocuments;
   type MaxProjectsPerUser = MaxProjectsPerUser;
@@ -771,6 +786,11 @@ impl pallet_rbac::Config for Runtime {
   type MaxUsersPerRole = MaxUsersPerRole;
 }
 
+parameter_types! {
+  // Fee revenue is totaled every 30 days
+  pub const AfloatFeePeriod: u64 = 30 * 24 * 60 * 60;
+}
+
 impl pallet_afloat::Config for Runtime {
   type RuntimeEvent = RuntimeEvent;
   type Currency = Balances;
@@ -778,6 +798,12 @@ impl pallet_afloat::Config for Runtime {
   type RemoveOrigin = EnsureRoot<AccountId>;
   type Rbac = RBAC;
   type ItemId = u32;
+  type MaxOffersPerTaxCredit = ConstU32<100>;
+  type MaxOffersPerCreator = ConstU32<10_000>;
+  type MaxOfferHistoryPerTaxCredit = ConstU32<10_000>;
+  type MaxTransactionsPerUser = ConstU32<10_000>;
+  type MaxTransactionsPerTaxCredit = ConstU32<10_000>;
+  type FeePeriod = AfloatFeePeriod;
 }
 
 parameter_types! {
@@ -904,7 +930,11 @@ pub type SignedPayload = generic::SignedPayload<RuntimeCall, SignedExtra>;
 /// All migrations that will run on the next runtime upgrade.
 ///
 /// Should be cleared after every release.
-pub type Migrations = (pallet_fund_admin::migration::v1::MigrateToV1<Runtime>,);
+pub type Migrations = (
+  pallet_fund_admin::migration::v1::MigrateToV1<Runtime>,
+  pallet_fund_admin::migration::v2::MigrateToV2<Runtime>,
+  pallet_afloat::migration::v2::MigrateToV2<Runtime>,
+);
 
 /// Executive: handles dispatch to the various modules.
 pub type Executive = frame_executive::Executive<
@@ -1049,6 +1079,123 @@ impl_runtime_apis! {
     }
   }
 
+  impl pallet_fruniques_runtime_api::FruniquesApi<Block, AccountId, BlockNumber> for Runtime {
+    fn provenance(
+      collection_id: pallet_fruniques::types::CollectionId,
+      item_id: pallet_fruniques::types::ItemId,
+    ) -> Option<
+      pallet_fruniques::types::Provenance<
+        AccountId,
+        pallet_fruniques::types::CollectionId,
+        pallet_fruniques::types::ItemId,
+        BlockNumber,
+      >,
+    > {
+      Fruniques::get_provenance(collection_id, item_id)
+    }
+
+    fn history(
+      collection_id: pallet_fruniques::types::CollectionId,
+      item_id: pallet_fruniques::types::ItemId,
+    ) -> Vec<pallet_fruniques::types::HistoryEntry<AccountId, BlockNumber>> {
+      Fruniques::frunique_history(collection_id, item_id).into_inner()
+    }
+  }
+
+  impl pallet_fund_admin_runtime_api::FundAdminApi<Block> for Runtime {
+    fn remaining_budget_by_expenditure(
+      project_id: pallet_fund_admin::types::ProjectId,
+    ) -> Vec<(
+      pallet_fund_admin::types::ExpenditureId,
+      pallet_fund_admin::types::ExpenditureAmount,
+    )> {
+      FundAdmin::get_remaining_budget_by_expenditure(project_id)
+    }
+
+    fn remaining_budget_by_expenditure_type(
+      project_id: pallet_fund_admin::types::ProjectId,
+    ) -> Vec<(
+      pallet_fund_admin::types::ExpenditureType,
+      pallet_fund_admin::types::ExpenditureAmount,
+    )> {
+      FundAdmin::get_remaining_budget_by_expenditure_type(project_id)
+    }
+
+    fn drawdown_over_budget(
+      project_id: pallet_fund_admin::types::ProjectId,
+      drawdown_id: pallet_fund_admin::types::DrawdownId,
+    ) -> Vec<(
+      pallet_fund_admin::types::ExpenditureId,
+      pallet_fund_admin::types::ExpenditureAmount,
+    )> {
+      FundAdmin::get_drawdown_over_budget(project_id, drawdown_id).unwrap_or_default()
+    }
+
+    fn is_disbursement_reconciled(
+      drawdown_id: pallet_fund_admin::types::DrawdownId,
+    ) -> Option<bool> {
+      FundAdmin::is_disbursement_reconciled(drawdown_id)
+    }
+  }
+
+  impl
+    pallet_afloat_runtime_api::AfloatApi<
+      Block,
+      AccountId,
+      <Runtime as pallet_uniques::Config>::ItemId,
+      pallet_afloat::types::Offer<Runtime>,
+      pallet_afloat::types::Transaction<Runtime>,
+    > for Runtime
+  {
+    fn offers_by_creator(
+      creator: AccountId,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Offer<Runtime>)> {
+      Afloat::get_offers_by_creator(creator, start_after, limit)
+    }
+
+    fn offers_by_tax_credit(
+      tax_credit_id: <Runtime as pallet_uniques::Config>::ItemId,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Offer<Runtime>)> {
+      Afloat::get_offers_by_tax_credit(tax_credit_id, start_after, limit)
+    }
+
+    fn offers_by_status(
+      status: pallet_afloat::types::OfferStatus,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Offer<Runtime>)> {
+      Afloat::get_offers_by_status(status, start_after, limit)
+    }
+
+    fn transactions_by_user(
+      user: AccountId,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Transaction<Runtime>)> {
+      Afloat::get_transactions_by_user(user, start_after, limit)
+    }
+
+    fn transactions_by_tax_credit(
+      tax_credit_id: <Runtime as pallet_uniques::Config>::ItemId,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Transaction<Runtime>)> {
+      Afloat::get_transactions_by_tax_credit(tax_credit_id, start_after, limit)
+    }
+
+    fn transactions_by_status(
+      status: pallet_afloat::types::OfferStatus,
+      start_after: Option<pallet_afloat::types::StorageId>,
+      limit: u32,
+    ) -> Vec<(pallet_afloat::types::StorageId, pallet_afloat::types::Transaction<Runtime>)> {
+      Afloat::get_transactions_by_status(status, start_after, limit)
+    }
+  }
+
   impl pallet_transaction_payment_rpc_runtime_api::TransactionPaymentApi<Block, Balance> for Runtime {
     fn query_info(
       uxt: <Block as BlockT>::Extrinsic,